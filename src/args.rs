@@ -1,27 +1,767 @@
-use clap::{self, Parser};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::parser::ValueSource;
+use clap::{self, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use reqwest::header::{HeaderName, HeaderValue};
+
+use crate::extraction::{StartAt, TokenScheme};
+use crate::record::Kind;
+use crate::result::{Error, Result};
+use crate::store::{Compression, PrimaryKey};
 
 /// A small indexer.
 #[derive(Parser, Debug)]
 #[clap()]
 pub struct Args {
+    /// What to do instead of indexing and serving
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// A TOML file with the defaults for any of the options below, keyed by their names
+    /// in snake case; the options given on the command line take precedence
+    #[clap(long)]
+    pub config: Option<PathBuf>,
+
     /// The port to listen on for the web interface
     #[clap(short = 'P', long, default_value_t = 8989)]
     pub port: u16,
 
+    /// How to format the logs
+    #[clap(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Where to export the spans to, as an OpenTelemetry collector taking OTLP over gRPC;
+    /// nothing gets exported if unset
+    #[clap(long)]
+    pub otel_endpoint: Option<String>,
+
     /// The host to listen on for the web interface
     #[clap(short = 'H', long, default_value = "localhost")]
     pub host: String,
 
+    /// What origins the browsers may call the web interface in from, comma-separated, or `*` for any;
+    /// none but its own if unset
+    #[clap(long, value_delimiter = ',')]
+    pub cors_origins: Vec<String>,
+
+    /// How many threads serve the web interface; one per core if unset
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub workers: Option<usize>,
+
+    /// How many pending connections to the web interface may wait to be accepted
+    #[clap(long)]
+    pub backlog: Option<u32>,
+
+    /// How many records `/votes` and `/transfers` may respond with at most; the rest are cut off
+    #[clap(long, default_value_t = 100_000, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_response_records: usize,
+
+    /// How many signatures `/votes/lookup` and `/transfers/lookup` may take at once
+    #[clap(long, default_value_t = 500, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_lookup_signatures: usize,
+
+    /// A file to write the listening addresses into once the web interface is up,
+    /// and to remove once it stops
+    #[clap(long)]
+    pub ready_file: Option<PathBuf>,
+
+    /// Whether to compress the responses for the clients that accept it
+    #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub compress_responses: bool,
+
+    /// Do not compress the responses, as when a reverse proxy in front already does
+    #[clap(long, conflicts_with = "compress_responses")]
+    pub no_compress: bool,
+
     /// If set, do not talk to the network and do not fill the database,
     /// but only serve the web interface with the already existing data
     #[clap(short = 'N', long)]
     pub dry: bool,
 
-    /// The address of a Solana RPC node
-    #[clap(short, long, default_value = "https://api.mainnet-beta.solana.com")]
-    pub url: String,
+    /// If set, extract as usual, but only log the records at the debug level
+    /// instead of writing them down
+    #[clap(long, conflicts_with = "dry")]
+    pub parse_only: bool,
+
+    /// If set, do not serve the web interface,
+    /// but only fill the database until interrupted
+    #[clap(long, conflicts_with = "dry")]
+    pub no_serve: bool,
+
+    /// How many seconds to wait for the background tasks on shutdown before abandoning them
+    #[clap(long, default_value_t = 30)]
+    pub shutdown_timeout: u64,
+
+    /// The addresses of the Solana RPC nodes, comma-separated, taken in turns
+    #[clap(
+        short,
+        long,
+        env = "SURF_RPC_URL",
+        hide_env_values = true,
+        value_delimiter = ',',
+        default_value = "https://api.mainnet-beta.solana.com"
+    )]
+    pub url: Vec<String>,
+
+    /// How many seconds to set an RPC node aside for once it fails a few times in a row
+    #[clap(long, default_value_t = 30)]
+    pub rpc_quarantine_secs: u64,
+
+    /// The token to access the RPC node with, kept out of the URL and the logs
+    #[clap(long, env = "SURF_RPC_TOKEN", hide_env_values = true)]
+    pub rpc_token: Option<String>,
+
+    /// How the RPC node expects the token to be passed
+    #[clap(long, value_enum, default_value_t = TokenScheme::Bearer)]
+    pub rpc_token_scheme: TokenScheme,
+
+    /// How many times in a row to retry the same block before giving up on the extraction
+    #[clap(long, default_value_t = 3)]
+    pub max_block_retries: u32,
+
+    /// How many times to retry the extraction over the whole run before giving up on it;
+    /// unbounded if unset
+    #[clap(long)]
+    pub max_retries: Option<u32>,
+
+    /// How many milliseconds to wait before retrying the extraction, doubling with every failure in a row
+    #[clap(long, default_value_t = 500)]
+    pub retry_base_ms: u64,
+
+    /// How many milliseconds to wait before retrying the extraction, at most
+    #[clap(long, default_value_t = 30_000)]
+    pub retry_max_ms: u64,
+
+    /// How many seconds ahead of the wall clock a block time may be before the block is skipped
+    #[clap(long, default_value_t = 300)]
+    pub max_clock_skew: u64,
+
+    /// If set, take the blocks from too far in the future as of now instead of skipping them
+    #[clap(long)]
+    pub clamp_block_times: bool,
+
+    /// If set, fetch the leader schedule of every epoch, to tag each block with its leader in the logs
+    #[clap(long)]
+    pub leader_schedule: bool,
+
+    /// If set, give up on the extraction once the cluster skips more than this many blocks in a row
+    #[clap(long)]
+    pub fail_on_gap: Option<u64>,
+
+    /// How many blocks behind the tip of the cluster to fall before warning
+    #[clap(long, default_value_t = 1000)]
+    pub lag_threshold: u64,
+
+    /// How many blocks behind the tip of the cluster to fall, once caught up, before backfilling again
+    #[clap(long, default_value_t = 100)]
+    pub catch_up_threshold: u64,
+
+    /// Only index these kinds of records; repeatable
+    #[clap(long, value_enum)]
+    pub only: Vec<Kind>,
+
+    /// Index all but these kinds of records; repeatable
+    #[clap(long, value_enum, conflicts_with = "only")]
+    pub except: Vec<Kind>,
+
+    /// Extract the blocks from this one on, then exit, keeping the progress apart
+    /// from the one of the continuous extraction; along with `--backfill-to`
+    #[clap(long, requires = "backfill_to", conflicts_with = "dry")]
+    pub backfill_from: Option<u64>,
+
+    /// Extract the blocks up to this one, inclusive, then exit; along with `--backfill-from`
+    #[clap(long, requires = "backfill_from")]
+    pub backfill_to: Option<u64>,
+
+    /// Where to begin when the database is empty
+    #[clap(long, value_enum, default_value_t = StartAt::Epoch)]
+    pub start_at: StartAt,
+
+    /// How many blocks to fetch from the node at once; they still get committed in order
+    #[clap(long, default_value_t = 4, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub concurrency: usize,
+
+    /// How many extracted blocks may await the committer before the extraction stalls
+    #[clap(long, default_value_t = 64, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub channel_capacity: usize,
+
+    /// If set, keep the extracted blocks on the disk until committed, replaying them after a crash
+    #[clap(long)]
+    pub staging: bool,
+
+    /// How many slots the tip of the cluster should be ahead of a block before indexing it
+    #[clap(long, default_value_t = 0)]
+    pub confirmation_depth: u64,
+
+    /// How many milliseconds to wait before polling the tip again once caught up with it
+    #[clap(long, default_value_t = 400)]
+    pub poll_interval_ms: u64,
+
+    /// How many seconds to wait for the RPC node to respond
+    #[clap(long, default_value_t = 30)]
+    pub rpc_timeout: u64,
+
+    /// An extra HTTP header to send to the RPC node, as `key:value`; repeatable
+    #[clap(long = "rpc-header", value_parser = parse_header)]
+    pub rpc_headers: Vec<(HeaderName, HeaderValue)>,
+
+    /// A PEM file with an extra root certificate to trust the RPC node by
+    #[clap(long)]
+    pub rpc_ca_cert: Option<PathBuf>,
+
+    /// If set, trust whatever certificate the RPC node presents; only ever for testing
+    #[clap(long)]
+    pub rpc_insecure: bool,
+
+    /// What to introduce surf as to the RPC node; `surf/<version>` if unset
+    #[clap(long)]
+    pub rpc_user_agent: Option<String>,
 
     /// The directory to store the database in
     #[clap(short = 'Z', long, default_value = ".store")]
     pub store_path: String,
+
+    /// What to key the records by when creating the database; once created, it stays keyed so,
+    /// and a different choice is refused. By the signature if unset
+    #[clap(long, value_enum)]
+    pub primary_key: Option<PrimaryKey>,
+
+    /// A file to append every committed record to as well, one per line, for the consumers downstream
+    #[clap(long)]
+    pub event_log: Option<PathBuf>,
+
+    /// How many megabytes the event log may grow to before it gets set aside for a new one
+    #[clap(long, default_value_t = 256, value_parser = clap::builder::RangedU64ValueParser::<u64>::new().range(1..))]
+    pub event_log_max_mb: u64,
+
+    /// How many milliseconds may pass before the event log gets synced to the disk; 0 for every block
+    #[clap(long, default_value_t = 1000)]
+    pub event_log_sync_ms: u64,
+
+    /// If set, expose the endpoints that act on the database, like `POST /admin/checkpoint`
+    #[clap(long)]
+    pub admin: bool,
+
+    /// The directory `POST /admin/checkpoint` writes into, under the names it is given;
+    /// the endpoint is not there if unset
+    #[clap(long)]
+    pub checkpoint_dir: Option<PathBuf>,
+
+    /// If set, expose the endpoints that show the database internals, like `GET /debug/raw`
+    #[clap(long)]
+    pub debug_endpoints: bool,
+
+    /// If set, fail queries hitting unreadable rows instead of skipping them
+    #[clap(long)]
+    pub strict: bool,
+
+    /// How many kilobytes to read ahead when scanning the database
+    #[clap(long, default_value_t = 2048)]
+    pub readahead_kb: usize,
+
+    /// If set, the forward scans see through the writes happening meanwhile
+    /// instead of pinning a snapshot of the database
+    #[clap(long)]
+    pub tailing_scans: bool,
+
+    /// If set, every write waits for the database log to hit the disk
+    #[clap(long)]
+    pub sync_writes: bool,
+
+    /// How many megabytes each column family may buffer before writing them out; RocksDB's default if unset
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub db_write_buffer_mb: Option<usize>,
+
+    /// How many megabytes of the database to cache in memory; RocksDB's default if unset
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub db_block_cache_mb: Option<usize>,
+
+    /// How many files the database may keep open at once, -1 for no limit; RocksDB's default if unset
+    #[clap(long, allow_negative_numbers = true)]
+    pub db_max_open_files: Option<i32>,
+
+    /// How to compress the table files written from now on; RocksDB's default if unset
+    #[clap(long, value_enum)]
+    pub db_compression: Option<Compression>,
+
+    /// How many records may be committed before the last known block is written down
+    #[clap(long, default_value_t = 1000)]
+    pub flush_every: u64,
+
+    /// How many seconds may pass before the last known block is written down
+    #[clap(long, default_value_t = 5)]
+    pub flush_interval: u64,
+
+    /// If set, compact the whole database by hand every so many seconds
+    #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<u64>::new().range(1..))]
+    pub compact_interval: Option<u64>,
+}
+
+/// How the logs get written out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// For the humans to read.
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of each event and its spans,
+    /// for the log collectors to take in as they are.
+    Json,
+}
+
+/// One-off operations on the database.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Write a consistent copy of the database into a new directory, and exit
+    Checkpoint {
+        /// The directory to write the copy into; must not exist yet
+        #[clap(long)]
+        out: String,
+    },
+
+    /// Check random stored records against the transactions fetched anew, and exit
+    Verify {
+        /// How many records to check
+        #[clap(long, default_value_t = 100)]
+        sample: usize,
+    },
+
+    /// Check that the indexes and the records point to each other, and exit
+    Check,
+
+    /// Upgrade the database written by an older version to the current layout, and exit
+    Migrate,
+
+    /// Write all the votes and the transfers into a new file as JSON lines, and exit
+    Export {
+        /// The file to write into; must not exist yet
+        #[clap(long)]
+        out: PathBuf,
+    },
+
+    /// Write down the votes and the transfers from a file as written by `export`, and exit
+    Import {
+        /// The file to read from
+        #[clap(long)]
+        from: PathBuf,
+    },
+}
+
+/// The options as they may be given in the `--config` file.
+/// Whatever is missing here falls back to the command line and its defaults.
+#[derive(serde::Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    port: Option<u16>,
+    host: Option<String>,
+    log_format: Option<LogFormat>,
+    cors_origins: Option<Vec<String>>,
+    otel_endpoint: Option<String>,
+    workers: Option<usize>,
+    backlog: Option<u32>,
+    max_response_records: Option<usize>,
+    max_lookup_signatures: Option<usize>,
+    ready_file: Option<PathBuf>,
+    compress_responses: Option<bool>,
+    no_compress: Option<bool>,
+    dry: Option<bool>,
+    parse_only: Option<bool>,
+    no_serve: Option<bool>,
+    shutdown_timeout: Option<u64>,
+    /// Comma-separated, the same as on the command line.
+    url: Option<String>,
+    rpc_quarantine_secs: Option<u64>,
+    rpc_token: Option<String>,
+    rpc_token_scheme: Option<TokenScheme>,
+    max_block_retries: Option<u32>,
+    max_retries: Option<u32>,
+    retry_base_ms: Option<u64>,
+    retry_max_ms: Option<u64>,
+    max_clock_skew: Option<u64>,
+    clamp_block_times: Option<bool>,
+    fail_on_gap: Option<u64>,
+    lag_threshold: Option<u64>,
+    catch_up_threshold: Option<u64>,
+    leader_schedule: Option<bool>,
+    only: Option<Vec<Kind>>,
+    except: Option<Vec<Kind>>,
+    start_at: Option<StartAt>,
+    concurrency: Option<usize>,
+    channel_capacity: Option<usize>,
+    staging: Option<bool>,
+    confirmation_depth: Option<u64>,
+    poll_interval_ms: Option<u64>,
+    rpc_timeout: Option<u64>,
+    /// As `key:value`, the same as on the command line.
+    rpc_headers: Option<Vec<String>>,
+    rpc_ca_cert: Option<PathBuf>,
+    rpc_insecure: Option<bool>,
+    rpc_user_agent: Option<String>,
+    store_path: Option<String>,
+    primary_key: Option<PrimaryKey>,
+    event_log: Option<PathBuf>,
+    event_log_max_mb: Option<u64>,
+    event_log_sync_ms: Option<u64>,
+    admin: Option<bool>,
+    checkpoint_dir: Option<PathBuf>,
+    debug_endpoints: Option<bool>,
+    strict: Option<bool>,
+    readahead_kb: Option<usize>,
+    tailing_scans: Option<bool>,
+    db_write_buffer_mb: Option<usize>,
+    db_block_cache_mb: Option<usize>,
+    db_max_open_files: Option<i32>,
+    db_compression: Option<Compression>,
+    sync_writes: Option<bool>,
+    flush_every: Option<u64>,
+    flush_interval: Option<u64>,
+    compact_interval: Option<u64>,
+}
+
+/// Whether the option was given on the command line or by its environment variable,
+/// either of which takes precedence over the file.
+fn given(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+/// Take the value from the file unless the option was given otherwise.
+macro_rules! merge {
+    ($args:ident, $file:ident, $matches:ident, { $($field:ident),* $(,)? }, { $($optional:ident),* $(,)? }) => {
+        $(
+            if let Some(value) = $file.$field {
+                if !given($matches, stringify!($field)) {
+                    $args.$field = value;
+                }
+            }
+        )*
+        $(
+            if let Some(value) = $file.$optional {
+                if !given($matches, stringify!($optional)) {
+                    $args.$optional = Some(value);
+                }
+            }
+        )*
+    };
+}
+
+impl Args {
+    /// Parse the command line, filling in the gaps from the `--config` file if given.
+    pub fn load() -> Result<Self> {
+        let matches = Args::command().get_matches();
+        let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        if let Some(path) = args.config.clone() {
+            let contents = std::fs::read_to_string(&path)?;
+            let file: FileConfig = toml::from_str(&contents)
+                .map_err(|e| Error::Config(format!("`{}`: {e}", path.display())))?;
+            args.merge(file, &matches)?;
+        }
+        args.check_cors_origins()?;
+        Ok(args)
+    }
+
+    /// Refuse the origins no browser would send, as those would never be let in.
+    fn check_cors_origins(&self) -> Result<()> {
+        for origin in &self.cors_origins {
+            if origin == "*" {
+                continue;
+            }
+            // An origin is the scheme, the host, and the port alone, the way a browser writes it down:
+            let parsed = reqwest::Url::parse(origin).ok();
+            let serialized = parsed.map(|url| url.origin().ascii_serialization());
+            if serialized.as_deref() != Some(origin.as_str()) {
+                return Err(Error::Config(format!(
+                    "`cors_origins`: `{origin}` is not an origin, like `https://app.example.com`"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, file: FileConfig, matches: &ArgMatches) -> Result<()> {
+        if file.workers == Some(0)
+            || file.concurrency == Some(0)
+            || file.channel_capacity == Some(0)
+            || file.max_response_records == Some(0)
+            || file.max_lookup_signatures == Some(0)
+            || file.event_log_max_mb == Some(0)
+            || file.db_write_buffer_mb == Some(0)
+            || file.db_block_cache_mb == Some(0)
+        {
+            return Err(Error::Config(
+                "`workers`, `concurrency`, `channel_capacity`, `max_response_records`, `max_lookup_signatures`, `event_log_max_mb`, `db_write_buffer_mb`, and `db_block_cache_mb` must be positive"
+                    .to_owned(),
+            ));
+        }
+        let rpc_headers = file
+            .rpc_headers
+            .map(|pairs| {
+                let pairs = pairs.iter().map(String::as_str);
+                pairs
+                    .map(parse_header)
+                    .collect::<std::result::Result<Vec<_>, _>>()
+            })
+            .transpose()
+            .map_err(|e| Error::Config(format!("`rpc_headers`: {e}")))?;
+
+        let args = self;
+        merge!(args, file, matches, {
+            log_format, port, host, cors_origins, compress_responses, no_compress, dry, parse_only, no_serve, shutdown_timeout, rpc_quarantine_secs, rpc_token_scheme, max_block_retries, retry_base_ms, retry_max_ms, max_clock_skew, clamp_block_times,
+            lag_threshold, catch_up_threshold, leader_schedule, only, except, start_at,
+            concurrency, channel_capacity, staging, max_response_records, max_lookup_signatures, confirmation_depth, poll_interval_ms, rpc_timeout,
+            rpc_insecure, store_path, event_log_max_mb, event_log_sync_ms, admin, debug_endpoints, strict, readahead_kb, tailing_scans,
+            sync_writes, flush_every, flush_interval,
+        }, {
+            otel_endpoint, workers, backlog, ready_file, event_log, checkpoint_dir, rpc_token, rpc_ca_cert, rpc_user_agent, max_retries, fail_on_gap, primary_key, compact_interval,
+            db_write_buffer_mb, db_block_cache_mb, db_max_open_files, db_compression,
+        });
+        if let Some(rpc_headers) = rpc_headers {
+            if !given(matches, "rpc_headers") {
+                args.rpc_headers = rpc_headers;
+            }
+        }
+        if let Some(url) = file.url {
+            if !given(matches, "url") {
+                args.url = url.split(',').map(str::to_owned).collect();
+            }
+        }
+
+        if args.dry && args.no_serve {
+            return Err(Error::Config(
+                "`dry` and `no_serve` cannot be both set".to_owned(),
+            ));
+        }
+        if args.dry && args.parse_only {
+            return Err(Error::Config(
+                "`dry` and `parse_only` cannot be both set".to_owned(),
+            ));
+        }
+        if file.compress_responses.is_some() && file.no_compress == Some(true) {
+            return Err(Error::Config(
+                "`compress_responses` and `no_compress` cannot be both set".to_owned(),
+            ));
+        }
+        if !args.only.is_empty() && !args.except.is_empty() {
+            return Err(Error::Config(
+                "`only` and `except` cannot be both set".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The blocks to backfill, if any.
+    pub fn backfill(&self) -> Result<Option<RangeInclusive<u64>>> {
+        let (Some(from), Some(to)) = (self.backfill_from, self.backfill_to) else {
+            return Ok(None);
+        };
+        if from > to {
+            return Err(Error::Config(format!(
+                "`backfill_from` is past `backfill_to`: {from} > {to}"
+            )));
+        }
+        Ok(Some(from..=to))
+    }
+
+    /// The kinds of records to index, as narrowed down by `--only` or `--except`.
+    pub fn kinds(&self) -> Vec<Kind> {
+        if !self.only.is_empty() {
+            return self.only.clone();
+        }
+        let all = Kind::value_variants().iter().copied();
+        all.filter(|kind| !self.except.contains(kind)).collect()
+    }
+}
+
+/// Parse a `key:value` pair into a valid HTTP header.
+fn parse_header(pair: &str) -> std::result::Result<(HeaderName, HeaderValue), String> {
+    let (key, value) = pair
+        .split_once(':')
+        .ok_or_else(|| "expected `key:value`".to_owned())?;
+    let key = HeaderName::from_str(key.trim()).map_err(|e| format!("bad header name: {e}"))?;
+    // Not echoing the value back, as it is likely to be a secret.
+    let value =
+        HeaderValue::from_str(value.trim()).map_err(|e| format!("bad value for `{key}`: {e}"))?;
+    Ok((key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(cli: &[&str], file: &str) -> Result<Args> {
+        let matches = Args::command().try_get_matches_from(cli).unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        let file = toml::from_str(file).map_err(|e| Error::Config(e.to_string()))?;
+        args.merge(file, &matches)?;
+        Ok(args)
+    }
+
+    #[test]
+    fn file_fills_the_gaps_of_the_command_line() {
+        // Given a file setting some options, one of them also given on the command line:
+        let cli = ["surf", "--port", "9000"];
+        let file = r#"
+            port = 8000
+            max_retries = 7
+            start_at = "latest"
+            rpc_headers = ["x-api-key: secret"]
+        "#;
+
+        // When we merge them:
+        let args = merged(&cli, file).unwrap();
+
+        // Then the command line should win, and the file should beat the defaults:
+        assert_eq!(args.port, 9000);
+        assert_eq!(args.max_retries, Some(7));
+        assert_eq!(args.max_block_retries, 3);
+        assert_eq!(args.start_at, StartAt::Latest);
+        assert_eq!(args.rpc_headers.len(), 1);
+        assert_eq!(args.lag_threshold, 1000);
+        assert_eq!(args.concurrency, 4);
+    }
+
+    #[test]
+    fn bad_file_refused() {
+        // Given files with an unknown key, and with contradicting options:
+        let cli = ["surf"];
+
+        // When we merge them, then they should be refused:
+        assert!(merged(&cli, "prot = 8000").is_err());
+        assert!(merged(&cli, "dry = true\nno_serve = true").is_err());
+        assert!(merged(&cli, "only = [\"vote\"]\nexcept = [\"transfer\"]").is_err());
+        let compression = "compress_responses = true\nno_compress = true";
+        assert!(merged(&cli, compression).is_err());
+    }
+
+    #[test]
+    fn compression_contradiction_refused() {
+        // Given the compression both asked for and turned off on the command line:
+        let cli = ["surf", "--compress-responses", "true", "--no-compress"];
+
+        // When we parse it, then it should be refused:
+        assert!(Args::command().try_get_matches_from(cli).is_err());
+
+        // And either one alone should be taken:
+        let args = merged(&["surf", "--no-compress"], "").unwrap();
+        assert!(args.compress_responses && args.no_compress);
+        let args = merged(&["surf", "--compress-responses", "false"], "").unwrap();
+        assert!(!args.compress_responses && !args.no_compress);
+    }
+
+    #[test]
+    fn cors_origins_checked() {
+        // Given the origins from the command line, and from the file:
+        let checked = |cli: &[&str], file: &str| merged(cli, file)?.check_cors_origins();
+
+        // When they are origins, or any, then they should be taken:
+        let origins = "--cors-origins=https://app.example.com,http://localhost:3000";
+        assert!(checked(&["surf", origins], "").is_ok());
+        assert!(checked(&["surf"], r#"cors_origins = ["*"]"#).is_ok());
+
+        // When they are anything else, then they should be refused:
+        for origin in [
+            "app.example.com",
+            "https://app.example.com/",
+            "https://app.example.com/x",
+        ] {
+            let cli = ["surf", "--cors-origins", origin];
+            assert!(checked(&cli, "").is_err(), "{origin:?} let through");
+        }
+        assert!(checked(&["surf"], r#"cors_origins = ["https://"]"#).is_err());
+    }
+
+    #[test]
+    fn log_format_taken_from_file() {
+        // Given the format of the logs in the file:
+        let file = r#"log_format = "json""#;
+
+        // When we merge it, then it should be taken, unless given on the command line:
+        assert_eq!(merged(&["surf"], file).unwrap().log_format, LogFormat::Json);
+        let cli = ["surf", "--log-format", "text"];
+        assert_eq!(merged(&cli, file).unwrap().log_format, LogFormat::Text);
+    }
+
+    #[test]
+    fn urls_split() {
+        // Given a few nodes, on the command line and in the file:
+        let cli = [
+            "surf",
+            "--url",
+            "https://a.example.com,https://b.example.com",
+        ];
+        let file = r#"url = "https://c.example.com,https://d.example.com,https://e.example.com""#;
+
+        // When we parse them, then each node should come on its own, the command line winning:
+        assert_eq!(merged(&cli, file).unwrap().url.len(), 2);
+        assert_eq!(merged(&["surf"], file).unwrap().url.len(), 3);
+    }
+
+    #[test]
+    fn backfill_bounds_checked() {
+        // Given the blocks to backfill, in order, reversed, and only half given:
+        let cli = ["surf", "--backfill-from", "100", "--backfill-to", "200"];
+        let reversed = ["surf", "--backfill-from", "200", "--backfill-to", "100"];
+        let half = ["surf", "--backfill-from", "100"];
+
+        // When we parse them, then only the ones in order should make a backfill:
+        let blocks = merged(&cli, "").unwrap().backfill().unwrap();
+        assert_eq!(blocks, Some(100..=200));
+        assert!(merged(&reversed, "").unwrap().backfill().is_err());
+        assert!(Args::try_parse_from(half).is_err());
+    }
+
+    #[test]
+    fn kinds_narrowed_down() {
+        // Given the kinds narrowed down either way:
+        let only = merged(&["surf", "--only", "vote"], "").unwrap();
+        let except = merged(&["surf", "--except", "vote"], "").unwrap();
+        let neither = merged(&["surf"], "").unwrap();
+
+        // Then only the ones asked for should be kept:
+        assert_eq!(only.kinds(), vec![Kind::Vote]);
+        assert_eq!(
+            except.kinds(),
+            vec![
+                Kind::Transfer,
+                Kind::Reward,
+                Kind::TokenTransfer,
+                Kind::StakeDelegation
+            ]
+        );
+        assert_eq!(
+            neither.kinds(),
+            vec![
+                Kind::Vote,
+                Kind::Transfer,
+                Kind::Reward,
+                Kind::TokenTransfer,
+                Kind::StakeDelegation
+            ]
+        );
+    }
+
+    #[test]
+    fn kinds_spelled_alike_in_file() {
+        // Given the kinds in the file, spelled as on the command line:
+        let file = r#"only = ["token-transfer", "stake-delegation"]"#;
+
+        // When we merge it:
+        let args = merged(&["surf"], file).unwrap();
+
+        // Then they should be taken the same way as on the command line:
+        let cli = [
+            "surf",
+            "--only",
+            "token-transfer",
+            "--only",
+            "stake-delegation",
+        ];
+        let expected = merged(&cli, "").unwrap().kinds();
+        assert_eq!(args.kinds(), expected);
+        assert_eq!(expected, vec![Kind::TokenTransfer, Kind::StakeDelegation]);
+    }
 }
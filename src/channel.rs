@@ -0,0 +1,329 @@
+//! The channel between the extraction and the committer, watched for backpressure.
+//! When the committer cannot keep up, the channel fills, and the extraction
+//! stalls on sending without as much as a word; these make it say so.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+
+use crate::metrics::Metrics;
+use crate::record::Record;
+use crate::store::Store;
+use crate::throttle::Throttle;
+
+/// What share of the capacity counts as near-full.
+const NEAR_FULL: f64 = 0.9;
+
+/// How long the channel should stay near-full before warning.
+const SUSTAINED: Duration = Duration::from_secs(30);
+
+/// How often to warn about the committer, at most.
+const STALL_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What the extraction hands over to the committer for a block.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Extracted {
+    /// The records of the block, to be written down at once.
+    Records(Vec<Record>),
+    /// The block the cluster has skipped, so there was nothing to extract.
+    Skipped(u64),
+    /// The block processed with nothing of the indexed kinds in it.
+    Empty(u64),
+}
+
+/// What goes through the channel, along with where it is staged, if it is.
+#[derive(Debug)]
+struct Envelope {
+    seq: Option<u64>,
+    extracted: Extracted,
+}
+
+/// Where the blocks wait on the disk from the moment they are extracted until committed,
+/// so that whatever was in the channel when the process died gets committed once it is back.
+#[derive(Clone, Debug)]
+struct Staging {
+    store: Arc<Store>,
+    next_seq: Arc<AtomicU64>,
+}
+
+/// Make a channel of the given capacity, reporting its depth to the metrics if given.
+pub fn channel(capacity: usize, metrics: Option<Arc<Metrics>>) -> (Sender, Receiver) {
+    let (tx, rx) = mpsc::channel(capacity);
+    if let Some(ref metrics) = metrics {
+        metrics.channel_capacity.set(capacity as i64);
+    }
+    let stall = Stall {
+        near_full_since: None,
+        warning: Throttle::new(STALL_WARNING_INTERVAL),
+    };
+    let sender = Sender {
+        inner: tx,
+        metrics: metrics.clone(),
+        stall: Arc::new(Mutex::new(stall)),
+        staging: None,
+    };
+    let receiver = Receiver {
+        inner: rx,
+        metrics,
+        staging: None,
+        replay: VecDeque::new(),
+        unacked: None,
+    };
+    (sender, receiver)
+}
+
+/// Make a channel as [channel] does, but staging whatever gets sent in the store
+/// until the receiver acknowledges it, and handing over first whatever is left staged from before.
+pub fn staged_channel(
+    capacity: usize,
+    metrics: Option<Arc<Metrics>>,
+    store: Arc<Store>,
+) -> crate::Result<(Sender, Receiver)> {
+    let replay: VecDeque<_> = store
+        .staged()?
+        .into_iter()
+        .map(|(seq, extracted)| Envelope {
+            seq: Some(seq),
+            extracted,
+        })
+        .collect();
+    if !replay.is_empty() {
+        tracing::warn!("Replaying {} blocks left uncommitted", replay.len());
+    }
+    let next_seq = replay.back().and_then(|x| x.seq).map_or(0, |seq| seq + 1);
+    let staging = Staging {
+        store,
+        next_seq: Arc::new(AtomicU64::new(next_seq)),
+    };
+
+    let (mut sender, mut receiver) = channel(capacity, metrics);
+    sender.staging = Some(staging.clone());
+    receiver.staging = Some(staging);
+    receiver.replay = replay;
+    Ok((sender, receiver))
+}
+
+/// Since when the channel has been near-full.
+#[derive(Debug)]
+struct Stall {
+    near_full_since: Option<Instant>,
+    warning: Throttle,
+}
+
+/// The sending half, used by the extraction.
+#[derive(Clone, Debug)]
+pub struct Sender {
+    inner: mpsc::Sender<Envelope>,
+    metrics: Option<Arc<Metrics>>,
+    stall: Arc<Mutex<Stall>>,
+    staging: Option<Staging>,
+}
+
+impl Sender {
+    /// Send the records of a block, waiting for room in the channel if there is none.
+    pub async fn send(
+        &self,
+        records: Vec<Record>,
+    ) -> Result<(), mpsc::error::SendError<Extracted>> {
+        self.put(Extracted::Records(records)).await
+    }
+
+    /// Tell that the cluster has skipped the block, the same way as [Sender::send] does.
+    pub async fn skip(&self, block: u64) -> Result<(), mpsc::error::SendError<Extracted>> {
+        self.put(Extracted::Skipped(block)).await
+    }
+
+    /// Tell that the block had nothing to extract, the same way as [Sender::send] does.
+    pub async fn empty(&self, block: u64) -> Result<(), mpsc::error::SendError<Extracted>> {
+        self.put(Extracted::Empty(block)).await
+    }
+
+    /// Stage what came of a block if staging, then send it.
+    /// Failing to stage, it still goes through, only without surviving a crash.
+    async fn put(&self, extracted: Extracted) -> Result<(), mpsc::error::SendError<Extracted>> {
+        self.observe();
+        let seq = self.staging.as_ref().and_then(|staging| {
+            let seq = staging.next_seq.fetch_add(1, Ordering::AcqRel);
+            match staging.store.stage(seq, &extracted) {
+                Ok(()) => Some(seq),
+                Err(e) => {
+                    tracing::error!("Failed to stage a block: {e:?}");
+                    None
+                }
+            }
+        });
+        let envelope = Envelope { seq, extracted };
+        self.inner
+            .send(envelope)
+            .await
+            .map_err(|e| mpsc::error::SendError(e.0.extracted))
+    }
+
+    /// Note how full the channel is, and warn if it has been near-full for too long.
+    fn observe(&self) {
+        let capacity = self.inner.max_capacity();
+        let depth = capacity - self.inner.capacity();
+        if let Some(ref metrics) = self.metrics {
+            metrics.channel_depth.set(depth as i64);
+        }
+
+        let mut stall = self.stall.lock().unwrap();
+        if (depth as f64) < NEAR_FULL * capacity as f64 {
+            stall.near_full_since = None;
+            return;
+        }
+        let since = *stall.near_full_since.get_or_insert_with(Instant::now);
+        if since.elapsed() >= SUSTAINED && stall.warning.ready() {
+            tracing::warn!(
+                depth,
+                capacity,
+                stalled_for = since.elapsed().as_secs(),
+                "The committer is not keeping up with the extraction"
+            );
+        }
+    }
+}
+
+/// The receiving half, used by the committer.
+#[derive(Debug)]
+pub struct Receiver {
+    inner: mpsc::Receiver<Envelope>,
+    metrics: Option<Arc<Metrics>>,
+    staging: Option<Staging>,
+    /// What was left staged from before, to go ahead of anything sent.
+    replay: VecDeque<Envelope>,
+    /// Where the last block received is staged, until acknowledged.
+    unacked: Option<u64>,
+}
+
+impl Receiver {
+    /// Receive what came of the next block, or nothing once all the senders are gone.
+    pub async fn recv(&mut self) -> Option<Extracted> {
+        let envelope = match self.replay.pop_front() {
+            Some(envelope) => Some(envelope),
+            None => self.inner.recv().await,
+        };
+        self.open(envelope)
+    }
+
+    /// Receive what came of the next block if it is already there, without waiting.
+    pub fn try_recv(&mut self) -> Option<Extracted> {
+        let envelope = match self.replay.pop_front() {
+            Some(envelope) => Some(envelope),
+            None => self.inner.try_recv().ok(),
+        };
+        self.open(envelope)
+    }
+
+    /// Tell that the last block received has been committed, so that it is not to be replayed.
+    pub fn ack(&mut self) {
+        let (Some(staging), Some(seq)) = (&self.staging, self.unacked.take()) else {
+            return;
+        };
+        // Committed twice at worst, which only overwrites the records with the same ones.
+        if let Err(e) = staging.store.unstage(seq) {
+            tracing::error!("Failed to unstage a committed block: {e:?}");
+        }
+    }
+
+    /// Note the depth of the channel, and where the block is staged.
+    fn open(&mut self, envelope: Option<Envelope>) -> Option<Extracted> {
+        if let Some(ref metrics) = self.metrics {
+            metrics.channel_depth.set(self.inner.len() as i64);
+        }
+        let envelope = envelope?;
+        self.unacked = envelope.seq;
+        Some(envelope.extracted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::record::fixtures;
+
+    #[tokio::test]
+    async fn uncommitted_blocks_replayed() {
+        // Given a staged channel, with two blocks sent, and only the first one committed:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        {
+            let (tx, mut rx) = staged_channel(4, None, store.clone()).unwrap();
+            tx.skip(777).await.unwrap();
+            tx.skip(778).await.unwrap();
+            rx.recv().await.unwrap();
+            rx.ack();
+            rx.recv().await.unwrap();
+        }
+
+        // When the channel is made anew, as after a crash:
+        let (tx, mut rx) = staged_channel(4, None, store.clone()).unwrap();
+        tx.skip(779).await.unwrap();
+
+        // Then the uncommitted block should come first, then the new one:
+        let replayed = rx.recv().await.unwrap();
+        assert!(matches!(replayed, Extracted::Skipped(778)), "{replayed:?}");
+        rx.ack();
+        let sent = rx.recv().await.unwrap();
+        assert!(matches!(sent, Extracted::Skipped(779)), "{sent:?}");
+        rx.ack();
+
+        // And nothing should be left staged once both are committed:
+        assert!(store.staged().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn uncommitted_records_replayed() {
+        // Given a staged channel, with a block of records and an empty one sent, neither committed:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        let records = vec![
+            Record::Vote(fixtures::vote(777, 0)),
+            Record::Transfer(fixtures::transfer(777, 1)),
+        ];
+        {
+            let (tx, mut rx) = staged_channel(4, None, store.clone()).unwrap();
+            tx.send(records.clone()).await.unwrap();
+            tx.empty(778).await.unwrap();
+            rx.recv().await.unwrap();
+        }
+
+        // When the channel is made anew, as after a crash:
+        let (_tx, mut rx) = staged_channel(4, None, store.clone()).unwrap();
+
+        // Then the records should come back the same, then the empty block:
+        let replayed = rx.recv().await.unwrap();
+        assert!(
+            matches!(&replayed, Extracted::Records(x) if *x == records),
+            "{replayed:?}"
+        );
+        rx.ack();
+        let replayed = rx.recv().await.unwrap();
+        assert!(matches!(replayed, Extracted::Empty(778)), "{replayed:?}");
+        rx.ack();
+
+        // And nothing should be left staged once both are committed:
+        assert!(store.staged().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn depth_reported() {
+        // Given a watched channel:
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let (tx, mut rx) = channel(4, Some(metrics.clone()));
+        let record = Record::Vote(fixtures::vote(777, 0));
+
+        // When we send a few blocks, and receive one:
+        for _ in 0..3 {
+            tx.send(vec![record.clone()]).await.unwrap();
+        }
+        rx.recv().await.unwrap();
+
+        // Then the depth should count the ones still waiting:
+        assert_eq!(metrics.channel_capacity.get(), 4);
+        assert_eq!(metrics.channel_depth.get(), 2);
+    }
+}
@@ -0,0 +1,79 @@
+//! Compacting the database by hand every so often, on top of what it does on its own,
+//! as the indexes take many small writes that keep piling up until then.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::store::{Store, COLUMN_FAMILIES};
+
+/// Compact every column family whole once per `interval`, starting one `interval` from now,
+/// until the `stop` comes. One already under way gets finished first, as it cannot be cut short.
+pub async fn compact_periodically(store: Arc<Store>, interval: Duration, stop: CancellationToken) {
+    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = stop.cancelled() => break,
+            _ = ticks.tick() => {}
+        }
+        tracing::info!("Compacting the database...");
+        let started = Instant::now();
+        for name in COLUMN_FAMILIES {
+            if stop.is_cancelled() {
+                return;
+            }
+            let store = store.clone();
+            let compacted = tokio::task::spawn_blocking(move || {
+                let started = Instant::now();
+                store.compact(name);
+                started.elapsed()
+            })
+            .await;
+            match compacted {
+                Ok(elapsed) => tracing::debug!(cf = name, ?elapsed, "Compacted"),
+                Err(e) => tracing::error!("Failed to compact `{name}`: {e:?}"),
+            }
+        }
+        tracing::info!(elapsed = ?started.elapsed(), "Compacted the database");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::record::fixtures;
+    use crate::store::VOTES_NS;
+
+    #[tokio::test]
+    async fn compaction_keeps_records() {
+        // Given a store with a few votes, written down into as many table files,
+        // compacted every few milliseconds:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        let vote = fixtures::vote(777, 0);
+        for each in [fixtures::vote(775, 0), fixtures::vote(776, 0), vote.clone()] {
+            store.save_vote(&each).await.unwrap();
+            store.flush_table(VOTES_NS);
+        }
+        assert_eq!(store.table_files(VOTES_NS), 3);
+        let stop = CancellationToken::new();
+        let interval = Duration::from_millis(10);
+        let compaction = tokio::spawn(compact_periodically(store.clone(), interval, stop.clone()));
+
+        // When it gets a few rounds in, then stopped:
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stop.cancel();
+        compaction.await.unwrap();
+
+        // Then the votes should end up in a single table file:
+        assert_eq!(store.table_files(VOTES_NS), 1);
+
+        // And the vote should still be there, by the primary key and by the index alike:
+        let found = store.find_votes(&[vote.signature]).await.unwrap();
+        assert_eq!(found.len(), 1);
+        let found = store.find_votes_by_block_index(777).await.unwrap();
+        assert_eq!(found.len(), 1);
+    }
+}
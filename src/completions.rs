@@ -0,0 +1,66 @@
+//! Keeping track of which blocks are done, whatever order they get done in.
+//! Resuming past the highest one done would skip whatever was still in flight below it,
+//! so only the blocks done without a gap count.
+
+use std::collections::BTreeSet;
+
+/// The blocks done so far, from the one the extraction started at.
+#[derive(Debug)]
+pub struct Completions {
+    /// The first block not done yet.
+    next: u64,
+    /// The blocks done past a gap, waiting for the gap to close.
+    ahead: BTreeSet<u64>,
+}
+
+impl Completions {
+    pub fn starting_at(block: u64) -> Self {
+        Self {
+            next: block,
+            ahead: BTreeSet::new(),
+        }
+    }
+
+    /// Note that the block is done, moving past it and every block done after it without a gap.
+    pub fn complete(&mut self, block: u64) {
+        if block < self.next {
+            return;
+        }
+        self.ahead.insert(block);
+        while self.ahead.remove(&self.next) {
+            self.next += 1;
+        }
+    }
+
+    /// The first block not done yet, that is where to resume from.
+    pub fn resume_at(&self) -> u64 {
+        self.next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_order_completions_leave_no_gaps() {
+        // Given blocks started from 100:
+        let mut completions = Completions::starting_at(100);
+
+        // When they get done out of order, with one still in flight:
+        completions.complete(102);
+        completions.complete(100);
+        completions.complete(103);
+
+        // Then the resume point should stop right at the gap:
+        assert_eq!(completions.resume_at(), 101);
+
+        // And when the gap closes, it should move past all the blocks done since:
+        completions.complete(101);
+        assert_eq!(completions.resume_at(), 104);
+
+        // And the blocks done twice should change nothing:
+        completions.complete(100);
+        assert_eq!(completions.resume_at(), 104);
+    }
+}
@@ -0,0 +1,226 @@
+//! What the cluster has scheduled for the epoch at hand, fetched once per epoch.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::result::{self, Result};
+use crate::throttle::Throttle;
+
+/// How long to wait before asking for the leaders again, having failed to get them.
+const LEADERS_RETRY: Duration = Duration::from_secs(30);
+
+/// The epoch schedule, along with the leaders of the current epoch if asked for.
+#[derive(Debug)]
+pub struct Epochs {
+    schedule: EpochSchedule,
+    epoch: u64,
+    /// Whether to fetch the leaders at all, as the schedule of an epoch is sizable.
+    with_leaders: bool,
+    leaders: Option<Leaders>,
+    /// When to try again for the leaders of the epoch at hand, should they be missing.
+    retry: Throttle,
+}
+
+impl Epochs {
+    /// Fetch the schedule, and the leaders of the current epoch if `with_leaders`.
+    pub fn load(client: &RpcClient, with_leaders: bool) -> Result<Self> {
+        let schedule = client.get_epoch_schedule()?;
+        let epoch = client.get_epoch_info()?.epoch;
+        let mut epochs = Self {
+            schedule,
+            epoch,
+            with_leaders,
+            leaders: None,
+            retry: Throttle::new(LEADERS_RETRY),
+        };
+        epochs.fetch_leaders(client);
+        Ok(epochs)
+    }
+
+    /// The epoch as of the last slot looked at.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn schedule(&self) -> &EpochSchedule {
+        &self.schedule
+    }
+
+    /// Keep up with the epoch of the slot, fetching the leaders anew once it changes,
+    /// whichever way it goes, and every now and then meanwhile should they be missing.
+    pub fn roll_to(&mut self, client: &RpcClient, slot: u64) -> u64 {
+        let epoch = self.schedule.get_epoch(slot);
+        if epoch != self.epoch {
+            tracing::info!(epoch, "Rolling over to epoch {epoch}");
+            self.epoch = epoch;
+            self.leaders = None;
+            self.fetch_leaders(client);
+        } else if self.leaders.is_none() && self.retry.ready() {
+            self.fetch_leaders(client);
+        }
+        epoch
+    }
+
+    /// The validator scheduled to produce the slot, if known.
+    pub fn leader_of(&self, slot: u64) -> Option<Pubkey> {
+        self.leaders.as_ref()?.leader_of(slot)
+    }
+
+    /// Fetch the leaders of the current epoch; without them, the blocks just go untagged.
+    fn fetch_leaders(&mut self, client: &RpcClient) {
+        if !self.with_leaders {
+            return;
+        }
+        let first_slot = self.schedule.get_first_slot_in_epoch(self.epoch);
+        match client.get_leader_schedule(Some(first_slot)) {
+            Ok(Some(schedule)) => self.leaders = Some(Leaders::new(first_slot, &schedule)),
+            Ok(None) => tracing::warn!("No leader schedule for epoch {}", self.epoch),
+            Err(e) => {
+                let e = result::redacted(e);
+                tracing::warn!("Failed to get the leader schedule: {e:?}");
+            }
+        }
+    }
+}
+
+/// Who produces which slot of an epoch.
+#[derive(Debug)]
+struct Leaders {
+    first_slot: u64,
+    identities: Vec<Pubkey>,
+    /// The position among the identities of the leader of each slot, from the first one on.
+    by_slot: Vec<u32>,
+}
+
+impl Leaders {
+    /// Invert the schedule as the node sends it, by the identity, to go by the slot.
+    fn new(first_slot: u64, schedule: &HashMap<String, Vec<usize>>) -> Self {
+        let mut identities = Vec::with_capacity(schedule.len());
+        let mut by_slot = Vec::new();
+        for (identity, slots) in schedule {
+            let Ok(identity) = Pubkey::from_str(identity) else {
+                tracing::warn!("Skipping malformed leader `{identity}`");
+                continue;
+            };
+            let position = identities.len() as u32;
+            identities.push(identity);
+            for &slot in slots {
+                if by_slot.len() <= slot {
+                    by_slot.resize(slot + 1, u32::MAX);
+                }
+                by_slot[slot] = position;
+            }
+        }
+        Self {
+            first_slot,
+            identities,
+            by_slot,
+        }
+    }
+
+    fn leader_of(&self, slot: u64) -> Option<Pubkey> {
+        let offset = usize::try_from(slot.checked_sub(self.first_slot)?).ok()?;
+        let position = *self.by_slot.get(offset)?;
+        self.identities.get(position as usize).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use solana_client::client_error::ClientError;
+    use solana_client::rpc_client::RpcClientConfig;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+
+    /// A node failing to tell the leader schedule a few times before telling it.
+    struct Flaky {
+        failures: AtomicUsize,
+        leader: Pubkey,
+    }
+
+    #[async_trait]
+    impl RpcSender for Flaky {
+        async fn send(
+            &self,
+            _request: RpcRequest,
+            _params: serde_json::Value,
+        ) -> std::result::Result<serde_json::Value, ClientError> {
+            let failed = self
+                .failures
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |left| {
+                    left.checked_sub(1)
+                });
+            if failed.is_ok() {
+                return Err(std::io::Error::other("timed out").into());
+            }
+            Ok(serde_json::json!({ self.leader.to_string(): [0, 1] }))
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "flaky".to_owned()
+        }
+    }
+
+    #[test]
+    fn leaders_fetched_again_within_epoch() {
+        // Given the leaders of the epoch failing to come at first:
+        let leader = Pubkey::new_unique();
+        let flaky = Flaky {
+            failures: AtomicUsize::new(1),
+            leader,
+        };
+        let client = RpcClient::new_sender(flaky, RpcClientConfig::default());
+        let mut epochs = Epochs {
+            schedule: EpochSchedule::custom(32, 32, false),
+            epoch: 0,
+            with_leaders: true,
+            leaders: None,
+            retry: Throttle::new(Duration::ZERO),
+        };
+        epochs.fetch_leaders(&client);
+        assert_eq!(epochs.leader_of(1), None);
+
+        // When we move on within the same epoch:
+        let epoch = epochs.roll_to(&client, 1);
+
+        // Then the leaders should be asked for again, and known this time:
+        assert_eq!(epoch, 0);
+        assert_eq!(epochs.leader_of(1), Some(leader));
+    }
+
+    #[test]
+    fn leaders_found_by_slot() {
+        // Given two leaders taking turns within an epoch starting at slot 1000:
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let schedule = HashMap::from([
+            (first.to_string(), vec![0, 2]),
+            (second.to_string(), vec![1]),
+        ]);
+
+        // When we invert the schedule:
+        let leaders = Leaders::new(1000, &schedule);
+
+        // Then each slot should have its own leader:
+        assert_eq!(leaders.leader_of(1000), Some(first));
+        assert_eq!(leaders.leader_of(1001), Some(second));
+        assert_eq!(leaders.leader_of(1002), Some(first));
+
+        // And the slots outside of the epoch should have none:
+        assert_eq!(leaders.leader_of(999), None);
+        assert_eq!(leaders.leader_of(1003), None);
+    }
+}
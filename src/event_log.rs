@@ -0,0 +1,179 @@
+//! An append-only log of everything committed, for the consumers downstream.
+//! One record per line, as on the wire, rotated by size, so that the older files
+//! can be shipped off and removed while the current one keeps growing.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::record::{PrettyRecord, Record};
+use crate::result::Result;
+
+/// When to start a new file, and how often to make sure the lines are on the disk.
+#[derive(Clone, Copy, Debug)]
+pub struct Rotation {
+    /// Start a new file once the current one is this large.
+    pub max_bytes: u64,
+    /// Wait for the lines to hit the disk once this much time has passed since the last time;
+    /// after every block if zero.
+    pub sync_interval: Duration,
+}
+
+/// The file currently appended to.
+#[derive(Debug)]
+pub struct EventLog {
+    path: PathBuf,
+    file: File,
+    rotation: Rotation,
+    /// How large the current file is, with every line in it whole.
+    written: u64,
+    /// What the next file set aside gets suffixed with, going up from the last one there is.
+    next_suffix: u64,
+    last_sync: Instant,
+}
+
+impl EventLog {
+    /// Append to the file at the path, creating it if necessary.
+    pub fn open(path: PathBuf, rotation: Rotation) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        let next_suffix = last_suffix(&path)?.map_or(1, |last| last + 1);
+        Ok(Self {
+            path,
+            file,
+            rotation,
+            written,
+            next_suffix,
+            last_sync: Instant::now(),
+        })
+    }
+
+    /// Append the records of a block, each tagged with its `type`, then rotate or sync if due.
+    /// The block goes in whole or not at all, so that no partial line is ever left behind.
+    pub fn append(&mut self, records: &[Record]) -> Result<()> {
+        let mut lines = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut lines, &PrettyRecord::from(record.clone()))?;
+            lines.push(b'\n');
+        }
+        if let Err(e) = self.file.write_all(&lines) {
+            self.file.set_len(self.written)?;
+            return Err(e.into());
+        }
+        self.written += lines.len() as u64;
+        if self.written >= self.rotation.max_bytes {
+            self.rotate()?;
+        } else if self.last_sync.elapsed() >= self.rotation.sync_interval {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Wait for everything appended so far to hit the disk.
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Set the current file aside under the next suffix, and start anew.
+    fn rotate(&mut self) -> Result<()> {
+        self.sync()?;
+        let mut aside = self.path.clone().into_os_string();
+        aside.push(format!(".{}", self.next_suffix));
+        std::fs::rename(&self.path, &aside)?;
+        self.next_suffix += 1;
+        tracing::info!("Rotated the event log into `{}`", aside.to_string_lossy());
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.file = file;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+/// The greatest suffix among the files set aside from the one at the path, if there are any.
+fn last_suffix(path: &Path) -> Result<Option<u64>> {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return Ok(None);
+    };
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{name}.");
+    let mut last = None;
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?.file_name();
+        let suffix = entry
+            .to_str()
+            .and_then(|entry| entry.strip_prefix(&prefix))
+            .and_then(|suffix| suffix.parse::<u64>().ok());
+        last = last.max(suffix);
+    }
+    Ok(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::record::fixtures;
+    use crate::store::Store;
+
+    #[test]
+    fn rotated_by_size() {
+        // Given an event log rotating after every couple of lines:
+        let directory = Store::disposable_path();
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("events.ndjson");
+        let vote = Record::Vote(fixtures::vote(777, 0));
+        let line = serde_json::to_vec(&PrettyRecord::from(vote.clone())).unwrap();
+        let rotation = Rotation {
+            max_bytes: 2 * (line.len() as u64 + 1),
+            sync_interval: Duration::ZERO,
+        };
+        let mut log = EventLog::open(path.clone(), rotation).unwrap();
+
+        // When a block of two gets appended, then another of one:
+        log.append(&[vote.clone(), vote.clone()]).unwrap();
+        log.append(&[vote]).unwrap();
+        log.sync().unwrap();
+
+        // Then the first block should be set aside, and the current file should hold the last:
+        let files = std::fs::read_dir(&directory).unwrap().count();
+        assert_eq!(files, 2);
+        let current = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(current.lines().count(), 1);
+        assert!(current.starts_with(r#"{"type":"vote","#));
+    }
+
+    #[test]
+    fn rotated_past_files_set_aside() {
+        // Given a file set aside before, and an event log rotating after every line:
+        let directory = Store::disposable_path();
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("events.ndjson");
+        std::fs::write(directory.join("events.ndjson.7"), "kept\n").unwrap();
+        let rotation = Rotation {
+            max_bytes: 1,
+            sync_interval: Duration::ZERO,
+        };
+        let mut log = EventLog::open(path.clone(), rotation).unwrap();
+
+        // When two blocks get appended:
+        let vote = Record::Vote(fixtures::vote(777, 0));
+        log.append(&[vote.clone()]).unwrap();
+        log.append(&[vote]).unwrap();
+
+        // Then they should be set aside under the suffixes following it, leaving it as it was:
+        let kept = std::fs::read_to_string(directory.join("events.ndjson.7")).unwrap();
+        assert_eq!(kept, "kept\n");
+        assert!(directory.join("events.ndjson.8").exists());
+        assert!(directory.join("events.ndjson.9").exists());
+    }
+}
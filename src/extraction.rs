@@ -1,86 +1,521 @@
 //! Means of communicating with the network.
 
+use crate::health::{Health, Progress};
+use crate::metrics::Metrics;
 use crate::result::{self, Result};
 
-use crate::record::{Record, Transfer, Vote};
+use crate::channel::{Extracted, Sender};
+use crate::completions::Completions;
+use crate::epochs::Epochs;
+use crate::pool::{self, RpcClientPool};
+use crate::record::{Kind, Record, Reward, StakeDelegation, TokenTransfer, Transfer, Vote};
+use crate::throttle::Throttle;
 
+use std::collections::{HashMap, VecDeque};
+use std::ops::RangeInclusive;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use tracing::instrument;
 
-use solana_client::{rpc_client::RpcClient, rpc_config::RpcBlockConfig};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use solana_client::http_sender::HttpSender;
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_client::rpc_config::RpcBlockConfig;
+use solana_client::rpc_sender::RpcSender;
+use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::{pubkey::Pubkey, signature::Signature};
 use solana_transaction_status::{
-    EncodedTransaction, EncodedTransactionWithStatusMeta, UiInstruction, UiMessage,
-    UiParsedInstruction, UiTransactionEncoding,
+    EncodedTransaction, EncodedTransactionWithStatusMeta, Rewards, UiConfirmedBlock, UiInstruction,
+    UiMessage, UiParsedInstruction, UiParsedMessage, UiTransactionEncoding,
+    UiTransactionStatusMeta,
 };
 
-/// Dig data to decompose the vote instruction, and send it to the channel.
-/// Skip silently if not really a vote.
-async fn emit_vote(
-    tx: &mpsc::Sender<Record>,
+/// The header some of the providers expect the token in.
+const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+/// How to talk to the RPC nodes.
+#[derive(Clone, Debug)]
+pub struct Connection {
+    /// The addresses of the nodes, taken in turns.
+    pub urls: Vec<String>,
+    /// How long to set a node aside for once it keeps failing.
+    pub quarantine: Duration,
+    /// How long to wait for any single response.
+    pub timeout: Duration,
+    /// What to send along with every request, like API keys.
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    /// What to introduce ourselves as, so that the providers can tell our traffic apart.
+    pub user_agent: String,
+    /// What to prove our access with, if the node asks for it.
+    pub token: Option<Token>,
+    /// An extra root to trust, for the nodes behind a private authority.
+    pub ca_cert: Option<reqwest::Certificate>,
+    /// Whether to trust whatever certificate the node presents.
+    pub insecure: bool,
+}
+
+/// A secret granting access to the node, kept out of the logs.
+#[derive(Clone)]
+pub struct Token {
+    pub secret: String,
+    pub scheme: TokenScheme,
+}
+
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Token")
+            .field("secret", &"<redacted>")
+            .field("scheme", &self.scheme)
+            .finish()
+    }
+}
+
+/// How the node expects the token to be passed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenScheme {
+    /// As `Authorization: Bearer <token>`.
+    Bearer,
+    /// As `x-api-key: <token>`.
+    Header,
+    /// As `?api-key=<token>` in the URL.
+    Query,
+}
+
+/// Where the node is, without the path or the query, as those may carry a secret.
+fn origin_of(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => url.origin().ascii_serialization(),
+        Err(_) => "<malformed URL>".to_owned(),
+    }
+}
+
+impl Connection {
+    /// Make a client that talks to the nodes as configured, in turns.
+    pub fn client(&self) -> Result<RpcClient> {
+        if self.urls.is_empty() {
+            return Err(result::Error::Config("no `url` given".to_owned()));
+        }
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        for (name, value) in &self.headers {
+            let mut value = value.clone();
+            value.set_sensitive(true);
+            headers.append(name.clone(), value);
+        }
+        let header = self.token.as_ref().and_then(|token| match token.scheme {
+            TokenScheme::Bearer => Some((AUTHORIZATION, format!("Bearer {}", token.secret))),
+            TokenScheme::Header => Some((X_API_KEY, token.secret.clone())),
+            TokenScheme::Query => None,
+        });
+        if let Some((name, value)) = header {
+            // Not echoing the value back, as it is the secret.
+            let mut value = HeaderValue::from_str(&value).map_err(|_| {
+                result::Error::Config("bad `rpc_token`: not fit for a header".to_owned())
+            })?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+
+        let mut http = reqwest::Client::builder()
+            .default_headers(headers)
+            .user_agent(&self.user_agent)
+            .danger_accept_invalid_certs(self.insecure)
+            .timeout(self.timeout)
+            .pool_idle_timeout(self.timeout);
+        if let Some(ca_cert) = &self.ca_cert {
+            http = http.add_root_certificate(ca_cert.clone());
+        }
+        let http = http.build()?;
+
+        let mut senders: Vec<(String, Box<dyn RpcSender + Send + Sync>)> = Vec::new();
+        for url in &self.urls {
+            let mut url = url.clone();
+            if let Some(token) = self
+                .token
+                .as_ref()
+                .filter(|token| token.scheme == TokenScheme::Query)
+            {
+                let mut parsed = reqwest::Url::parse(&url)
+                    .map_err(|e| result::Error::Config(format!("bad `url`: {e}")))?;
+                parsed
+                    .query_pairs_mut()
+                    .append_pair("api-key", &token.secret);
+                url = parsed.into();
+            }
+            let origin = origin_of(&url);
+            let sender = HttpSender::new_with_client(url, http.clone());
+            senders.push((origin, Box::new(sender)));
+        }
+        let pool = RpcClientPool::new(senders, self.quarantine);
+        Ok(RpcClient::new_sender(pool, RpcClientConfig::default()))
+    }
+
+    /// Where the nodes are, without the paths or the queries, as those may carry a secret.
+    pub fn origin(&self) -> String {
+        let origins: Vec<_> = self.urls.iter().map(|url| origin_of(url)).collect();
+        origins.join(", ")
+    }
+}
+
+/// How the extraction goes about its work.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// How many times to retry the same block in a row before giving up for good.
+    pub max_block_retries: u32,
+    /// How many times to retry over the whole run before giving up for good, if at all.
+    pub max_retries: Option<u32>,
+    /// How long to wait before each retry.
+    pub backoff: Backoff,
+    /// How many blocks behind the tip of the cluster to fall before warning.
+    pub lag_threshold: u64,
+    /// How many blocks behind the tip to fall, once caught up, before backfilling again.
+    pub catch_up_threshold: u64,
+    /// How many slots the tip of the cluster should be ahead of a block before taking it.
+    pub confirmation_depth: u64,
+    /// How long to wait before looking at the tip again once caught up with it.
+    pub poll_interval: Duration,
+    /// Where to begin when nothing has been extracted yet.
+    pub start_at: StartAt,
+    /// What kinds of records to keep.
+    pub kinds: Vec<Kind>,
+    /// How far ahead of the wall clock a block time may be.
+    pub clock_skew: ClockSkew,
+    /// Whether to fetch the leader schedule of every epoch, to tag each block with its leader.
+    pub leader_schedule: bool,
+    /// How many blocks to fetch at once.
+    pub concurrency: usize,
+    /// How many blocks in a row the cluster may skip before giving up, if the extraction is to.
+    pub fail_on_gap: Option<u64>,
+    /// Whether to stop everything once given up, as when there is no `/readyz` to tell so.
+    pub stop_once_given_up: bool,
+}
+
+/// How far ahead of the wall clock a block may claim to be, and what to do about it if further.
+/// The node may report nonsense, and a time from the future would then
+/// outlive everything else in any query by time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ClockSkew {
+    pub tolerance: Duration,
+    /// Whether to take such a block as of now, rather than skip it.
+    pub clamp: bool,
+}
+
+impl ClockSkew {
+    /// The time to take the block as of, or none if it is to be skipped.
+    fn check(&self, block: u64, block_time: u64, now: u64) -> Option<u64> {
+        let ahead = block_time.saturating_sub(now);
+        if ahead <= self.tolerance.as_secs() {
+            return Some(block_time);
+        }
+        if self.clamp {
+            tracing::warn!(
+                block,
+                block_time,
+                now,
+                "Block time is {ahead}s ahead; taking it as of now"
+            );
+            Some(now)
+        } else {
+            tracing::error!(
+                block,
+                block_time,
+                now,
+                "Block time is {ahead}s ahead; skipping the block"
+            );
+            None
+        }
+    }
+}
+
+/// Where to begin when nothing has been extracted yet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartAt {
+    /// The first slot of the current epoch.
+    #[default]
+    Epoch,
+    /// The latest finalized slot, without any history.
+    Latest,
+}
+
+/// The failures of the extraction, counted both per block and per run,
+/// so that the hiccups spread over hours do not add up to giving up,
+/// while a block failing over and over still does.
+#[derive(Debug)]
+struct Retries {
+    max_block_retries: u32,
+    max_retries: Option<u32>,
+    /// The block the extraction last failed at; none if it failed before getting to any.
+    block: Option<u64>,
+    /// How many times in a row it failed there.
+    in_a_row: u32,
+    /// How many times it failed overall.
+    total: u32,
+}
+
+impl Retries {
+    fn new(max_block_retries: u32, max_retries: Option<u32>) -> Self {
+        Self {
+            max_block_retries,
+            max_retries,
+            block: None,
+            in_a_row: 0,
+            total: 0,
+        }
+    }
+
+    /// Note a failure at the given block, yielding why to give up if it is time to.
+    fn failed_at(&mut self, block: Option<u64>) -> Option<String> {
+        if block != self.block {
+            self.block = block;
+            self.in_a_row = 0;
+        }
+        self.in_a_row += 1;
+        self.total += 1;
+
+        if self.in_a_row > self.max_block_retries {
+            let at = match block {
+                Some(block) => format!("block #{block}"),
+                None => "the start".to_owned(),
+            };
+            return Some(format!(
+                "{} retries in a row at {at}",
+                self.max_block_retries
+            ));
+        }
+        match self.max_retries {
+            Some(max_retries) if self.total > max_retries => {
+                Some(format!("{max_retries} retries over the run"))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How long to wait before retrying, doubling with every failure in a row.
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl Backoff {
+    /// The wait after the given failure in a row, counting from one,
+    /// plus the given share of the base at random so that the instances do not retry in step.
+    fn delay(&self, in_a_row: u32, jitter: f64) -> Duration {
+        let factor = 1u32
+            .checked_shl(in_a_row.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.base
+            .saturating_mul(factor)
+            .saturating_add(self.base.mul_f64(jitter))
+            .min(self.max)
+    }
+}
+
+/// How often to ask the node for the latest slot.
+const TIP_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often to warn about falling behind, at most.
+const LAG_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The latest slot of the cluster, as last heard of.
+#[derive(Debug, Default)]
+struct Tip {
+    slot: Option<u64>,
+    fetched_at: Option<Instant>,
+}
+
+impl Tip {
+    /// The latest slot, asking the node anew if the one known is stale.
+    /// Keep the stale one if the node does not tell.
+    fn get(&mut self, client: &RpcClient) -> Option<u64> {
+        let stale = match self.fetched_at {
+            None => true,
+            Some(at) => at.elapsed() >= TIP_REFRESH_INTERVAL,
+        };
+        if stale {
+            self.fetched_at = Some(Instant::now());
+            match client.get_slot_with_commitment(CommitmentConfig::confirmed()) {
+                Ok(slot) => self.slot = Some(slot),
+                Err(e) => {
+                    let e = result::redacted(e);
+                    tracing::debug!("Failed to get the latest slot: {e:?}");
+                }
+            }
+        }
+        self.slot
+    }
+
+    /// Make the next [Tip::get] ask the node anew.
+    fn invalidate(&mut self) {
+        self.fetched_at = None;
+    }
+}
+
+/// How the extraction goes about the blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Behind the tip, taking the blocks one after another as fast as they come.
+    Backfilling,
+    /// Caught up, polling the tip until the next block gets deep enough to take.
+    Live,
+}
+
+impl Phase {
+    /// What to be in with `next_block` to take next, given the latest block deep enough to take:
+    /// live once past it, and backfilling again once `threshold` blocks behind it.
+    /// Unless the node tells its tip, staying as is.
+    fn next(self, next_block: u64, deepest: Option<u64>, threshold: u64) -> Self {
+        let Some(deepest) = deepest else {
+            return self;
+        };
+        match self {
+            Phase::Backfilling if next_block > deepest => Phase::Live,
+            Phase::Live if deepest.saturating_sub(next_block) >= threshold => Phase::Backfilling,
+            phase => phase,
+        }
+    }
+}
+
+/// What carries over from one try at extracting to the next.
+#[derive(Debug)]
+struct Resumption {
+    /// Where to pick up, once anything has been extracted.
+    since_block: Option<u64>,
+    /// Fetched once, on the first try.
+    epochs: Option<Epochs>,
+}
+
+/// The program that sets the priority fees.
+const COMPUTE_BUDGET_PROGRAM: &str = "ComputeBudget111111111111111111111111111111";
+
+/// What a transaction is willing to pay for its computation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct ComputeBudget {
+    unit_price: Option<u64>,
+    unit_limit: Option<u32>,
+}
+
+/// Find the settings of the compute budget among the instructions of a transaction.
+/// The RPC does not parse these, so decoding them by hand.
+fn parse_compute_budget(instructions: &[UiInstruction]) -> ComputeBudget {
+    let mut budget = ComputeBudget::default();
+    for instruction in instructions {
+        let UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(instruction)) = instruction
+        else {
+            continue;
+        };
+        if instruction.program_id != COMPUTE_BUDGET_PROGRAM {
+            continue;
+        }
+        let Ok(data) = bs58::decode(&instruction.data).into_vec() else {
+            continue;
+        };
+        // A one-byte tag, then the little-endian argument.
+        match data.split_first() {
+            Some((&2, argument)) => {
+                budget.unit_limit = argument
+                    .get(..4)
+                    .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+            }
+            Some((&3, argument)) => {
+                budget.unit_price = argument
+                    .get(..8)
+                    .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()));
+            }
+            _ => {}
+        }
+    }
+    budget
+}
+
+/// Dig data to decompose the vote instruction.
+/// Yield nothing if not really a vote.
+fn parse_vote(
     signature: &Signature,
     block_index: &u64,
+    instruction_index: &u32,
     timestamp: &u64,
+    budget: &ComputeBudget,
+    program_id: &Pubkey,
     data: &serde_json::Value,
-) -> Result<()> {
+) -> Result<Option<Record>> {
     let serde_json::Value::Object(data) = data else {
-        return Ok(());
+        return Ok(None);
     };
     let Some(serde_json::Value::Object(info)) = data.get("info") else {
-        return Ok(());
+        return Ok(None);
     };
     let Some(serde_json::Value::String(vote_account)) = info.get("voteAccount") else {
-        return Ok(());
+        return Ok(None);
     };
     let Some(serde_json::Value::String(vote_authority)) = info.get("voteAuthority") else {
-        return Ok(());
+        return Ok(None);
     };
 
     let vote_account = Pubkey::from_str(vote_account)?;
     let vote_authority = Pubkey::from_str(vote_authority)?;
 
-    let sent = tx
-        .send(Record::Vote(Vote {
-            signature: *signature,
-            block_index: block_index.to_owned(),
-            timestamp: timestamp.to_owned(),
-            author: vote_authority,
-            target: vote_account,
-        }))
-        .await;
-    if let Err(e) = sent {
-        tracing::trace!("While sending a vote: {e:?}");
-    }
-
-    Ok(())
+    Ok(Some(Record::Vote(Vote {
+        signature: *signature,
+        block_index: block_index.to_owned(),
+        instruction_index: instruction_index.to_owned(),
+        timestamp: timestamp.to_owned(),
+        author: vote_authority,
+        target: vote_account,
+        compute_unit_price: budget.unit_price,
+        compute_unit_limit: budget.unit_limit,
+        program_id: *program_id,
+        // Only known once committed:
+        indexed_at: None,
+    })))
 }
 
-/// Dig data to decompose the transfer instruction, and send it to the channel.
-/// Skip silently if not really a transfer.
-async fn emit_transfer(
-    tx: &mpsc::Sender<Record>,
+/// Dig data to decompose the transfer instruction.
+/// Withdrawing from a durable nonce account counts as a transfer from that account,
+/// and creating an account as a transfer from the funder to the new account.
+/// Yield nothing if not really a transfer.
+fn parse_transfer(
     signature: &Signature,
     block_index: &u64,
+    instruction_index: &u32,
     timestamp: &u64,
+    budget: &ComputeBudget,
+    program_id: &Pubkey,
     data: &serde_json::Value,
-) -> Result<()> {
+) -> Result<Option<Record>> {
     let serde_json::Value::Object(data) = data else {
-        return Ok(());
+        return Ok(None);
     };
     let Some(serde_json::Value::Object(info)) = data.get("info") else {
-        return Ok(());
+        return Ok(None);
     };
-    let Some(serde_json::Value::String(source)) = info.get("source") else {
-        return Ok(());
+    let kind = data.get("type").and_then(serde_json::Value::as_str);
+    let (source, destination) = match kind {
+        Some("withdrawFromNonce") => ("nonceAccount", "destination"),
+        Some("createAccount" | "createAccountWithSeed") => ("source", "newAccount"),
+        // These only look after the nonce, without moving any lamports.
+        Some("advanceNonce" | "initializeNonce" | "authorizeNonce" | "upgradeNonce") => {
+            tracing::trace!("Skipping nonce instruction: {data:?}");
+            return Ok(None);
+        }
+        _ => ("source", "destination"),
     };
-    let Some(serde_json::Value::String(destination)) = info.get("destination") else {
-        return Ok(());
+    let Some(serde_json::Value::String(source)) = info.get(source) else {
+        return Ok(None);
+    };
+    let Some(serde_json::Value::String(destination)) = info.get(destination) else {
+        return Ok(None);
     };
     let Some(serde_json::Value::Number(lamports)) = info.get("lamports") else {
-        return Ok(());
+        return Ok(None);
     };
 
     let source = Pubkey::from_str(source)?;
@@ -89,200 +524,1190 @@ async fn emit_transfer(
         .as_u64()
         .ok_or_else(|| result::Error::SolanaBadNumber(lamports.to_string()))?;
 
-    let sent = tx
-        .send(Record::Transfer(Transfer {
-            signature: *signature,
-            block_index: block_index.to_owned(),
-            timestamp: timestamp.to_owned(),
-            source,
-            destination,
-            lamports,
-        }))
-        .await;
-    if let Err(e) = sent {
-        tracing::trace!("While sending a vote: {e:?}");
+    Ok(Some(Record::Transfer(Transfer {
+        signature: *signature,
+        block_index: block_index.to_owned(),
+        instruction_index: instruction_index.to_owned(),
+        timestamp: timestamp.to_owned(),
+        source,
+        destination,
+        lamports,
+        compute_unit_price: budget.unit_price,
+        compute_unit_limit: budget.unit_limit,
+        program_id: *program_id,
+        // Only known once committed:
+        indexed_at: None,
+        instruction_kind: kind.map(str::to_owned),
+    })))
+}
+
+/// The program that moves the SPL tokens.
+const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+/// The mints of the token accounts the transaction touches, by the account,
+/// as a plain `transfer` names the accounts, but not the token.
+fn token_mints(
+    meta: Option<&UiTransactionStatusMeta>,
+    message: &UiParsedMessage,
+) -> HashMap<String, String> {
+    let mut mints = HashMap::new();
+    let Some(meta) = meta else {
+        return mints;
+    };
+    for balances in [&meta.pre_token_balances, &meta.post_token_balances] {
+        let Some(balances) = Option::<&Vec<_>>::from(balances.as_ref()) else {
+            continue;
+        };
+        for balance in balances {
+            if let Some(account) = message.account_keys.get(balance.account_index as usize) {
+                mints.insert(account.pubkey.clone(), balance.mint.clone());
+            }
+        }
     }
+    mints
+}
 
-    Ok(())
+/// Dig data to decompose the token transfer instruction, be it checked or not.
+/// Yield nothing if not really a transfer, or if the token cannot be told.
+fn parse_token_transfer(
+    signature: &Signature,
+    block_index: &u64,
+    instruction_index: &u32,
+    timestamp: &u64,
+    mints: &HashMap<String, String>,
+    data: &serde_json::Value,
+) -> Result<Option<Record>> {
+    let serde_json::Value::Object(data) = data else {
+        return Ok(None);
+    };
+    let Some(serde_json::Value::Object(info)) = data.get("info") else {
+        return Ok(None);
+    };
+    let amount = match data.get("type").and_then(serde_json::Value::as_str) {
+        Some("transfer") => info.get("amount"),
+        Some("transferChecked") => info.get("tokenAmount").and_then(|x| x.get("amount")),
+        // Minting, burning, approving, and the like move nothing between the accounts.
+        _ => return Ok(None),
+    };
+    // The amounts come as strings, as they may well exceed what a double holds.
+    let Some(serde_json::Value::String(amount)) = amount else {
+        return Ok(None);
+    };
+    let Some(serde_json::Value::String(source)) = info.get("source") else {
+        return Ok(None);
+    };
+    let Some(serde_json::Value::String(destination)) = info.get("destination") else {
+        return Ok(None);
+    };
+    // A multisig owner signs off through its signers, so the authority is its own account.
+    let owner = info
+        .get("authority")
+        .or_else(|| info.get("multisigAuthority"));
+    let Some(serde_json::Value::String(owner)) = owner else {
+        return Ok(None);
+    };
+    // Only the checked one names the token; otherwise, the balances of the accounts tell it.
+    let mint = match info.get("mint") {
+        Some(serde_json::Value::String(mint)) => Some(mint),
+        _ => mints.get(source).or_else(|| mints.get(destination)),
+    };
+    let Some(mint) = mint else {
+        tracing::trace!("Skipping token transfer of an unknown mint: {data:?}");
+        return Ok(None);
+    };
+
+    let amount = amount
+        .parse()
+        .map_err(|_| result::Error::SolanaBadNumber(amount.to_owned()))?;
+
+    Ok(Some(Record::TokenTransfer(TokenTransfer {
+        signature: *signature,
+        block_index: block_index.to_owned(),
+        instruction_index: instruction_index.to_owned(),
+        timestamp: timestamp.to_owned(),
+        mint: Pubkey::from_str(mint)?,
+        owner: Pubkey::from_str(owner)?,
+        source_ata: Pubkey::from_str(source)?,
+        destination_ata: Pubkey::from_str(destination)?,
+        amount,
+        // Only known once committed:
+        indexed_at: None,
+    })))
 }
 
-/// Record all the transactions contained in a given block.
-/// This expects the block to be loaded with `UiTransactionEncoding::JsonParsed`.
-async fn extract_transactions(
-    tx: &mpsc::Sender<Record>,
+/// The program that keeps the stake accounts.
+const STAKE_PROGRAM: &str = "Stake11111111111111111111111111111111111111";
+
+/// Dig data to decompose the stake delegation instruction.
+/// Yield nothing if not really a delegation.
+fn parse_stake_delegation(
+    signature: &Signature,
+    block_index: &u64,
+    instruction_index: &u32,
+    timestamp: &u64,
+    data: &serde_json::Value,
+) -> Result<Option<Record>> {
+    let serde_json::Value::Object(data) = data else {
+        return Ok(None);
+    };
+    // Splitting, withdrawing, deactivating, and the like leave the delegation be.
+    if data.get("type").and_then(serde_json::Value::as_str) != Some("delegate") {
+        return Ok(None);
+    }
+    let Some(serde_json::Value::Object(info)) = data.get("info") else {
+        return Ok(None);
+    };
+    let Some(serde_json::Value::String(stake_account)) = info.get("stakeAccount") else {
+        return Ok(None);
+    };
+    let Some(serde_json::Value::String(vote_account)) = info.get("voteAccount") else {
+        return Ok(None);
+    };
+    let Some(serde_json::Value::String(authority)) = info.get("stakeAuthority") else {
+        return Ok(None);
+    };
+
+    Ok(Some(Record::StakeDelegation(StakeDelegation {
+        signature: *signature,
+        block_index: block_index.to_owned(),
+        instruction_index: instruction_index.to_owned(),
+        timestamp: timestamp.to_owned(),
+        stake_account: Pubkey::from_str(stake_account)?,
+        vote_account: Pubkey::from_str(vote_account)?,
+        authority: Pubkey::from_str(authority)?,
+        // Only known once committed:
+        indexed_at: None,
+    })))
+}
+
+/// Decompose a single transaction into the records of interest.
+/// This expects the transaction to be loaded with `UiTransactionEncoding::JsonParsed`.
+/// Only the programs of the given kinds get parsed, so that the rest cannot fail it.
+/// The `position` is where the first instruction of the transaction stands within its block,
+/// and it gets advanced past the last one.
+pub fn parse_transaction(
+    kinds: &[Kind],
+    transaction: &EncodedTransactionWithStatusMeta,
     block_index: &u64,
     block_time: &u64,
-    transactions: &[EncodedTransactionWithStatusMeta],
-) -> Result<()> {
-    for transaction in transactions {
-        let transaction = match &transaction.transaction {
-            // Encoding variant is set by the requestor,
-            // so any other branch means the RPC did not abide by the spec.
-            EncodedTransaction::Json(transaction) => transaction,
-            transaction => {
-                tracing::warn!("Skipping improperly encoded transaction: {transaction:?}");
-                continue;
-            }
-        };
-        // The first signature uniquely identifies the transaction.
-        let main_signature = Signature::from_str(&transaction.signatures[0]);
-        let main_signature = match main_signature {
-            Err(e) => {
-                tracing::warn!("Skipping transaction with less than one signature: {e:?}");
+    position: &mut u32,
+) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+
+    let meta = transaction.meta.as_ref();
+    let transaction = match &transaction.transaction {
+        // Encoding variant is set by the requestor,
+        // so any other branch means the RPC did not abide by the spec.
+        EncodedTransaction::Json(transaction) => transaction,
+        transaction => {
+            tracing::warn!("Skipping improperly encoded transaction: {transaction:?}");
+            return Ok(records);
+        }
+    };
+    // The first signature uniquely identifies the transaction.
+    let main_signature = Signature::from_str(&transaction.signatures[0]);
+    let main_signature = match main_signature {
+        Err(e) => {
+            tracing::warn!("Skipping transaction with less than one signature: {e:?}");
+            return Ok(records);
+        }
+        Ok(main_signature) => main_signature,
+    };
+    let message = match &transaction.message {
+        UiMessage::Parsed(message) => message,
+        message => {
+            tracing::warn!("Skipping transaction with bad message: {message:?}");
+            return Ok(records);
+        }
+    };
+    // Applies to the whole transaction, wherever it is set.
+    let budget = parse_compute_budget(&message.instructions);
+    let mints = token_mints(meta, message);
+
+    for instruction in &message.instructions {
+        let instruction_index = *position;
+        *position += 1;
+
+        let instruction = match instruction {
+            UiInstruction::Parsed(UiParsedInstruction::Parsed(instruction)) => instruction,
+            _ => {
+                // Skipping partially decoded instructions silently.
                 continue;
             }
-            Ok(main_signature) => main_signature,
         };
-        let message = match &transaction.message {
-            UiMessage::Parsed(message) => message,
-            message => {
-                tracing::warn!("Skipping transaction with bad message: {message:?}");
+
+        // We're only interested in vote, transfer, token transfer, and delegation instructions,
+        // and only in those of the kinds asked for.
+        let wanted = |kind| kinds.contains(&kind);
+        let record = match instruction.program_id.as_str() {
+            "Vote111111111111111111111111111111111111111" if wanted(Kind::Vote) => parse_vote(
+                &main_signature,
+                block_index,
+                &instruction_index,
+                block_time,
+                &budget,
+                &Pubkey::from_str(&instruction.program_id)?,
+                &instruction.parsed,
+            )?,
+            "11111111111111111111111111111111" if wanted(Kind::Transfer) => parse_transfer(
+                &main_signature,
+                block_index,
+                &instruction_index,
+                block_time,
+                &budget,
+                &Pubkey::from_str(&instruction.program_id)?,
+                &instruction.parsed,
+            )?,
+            TOKEN_PROGRAM if wanted(Kind::TokenTransfer) => parse_token_transfer(
+                &main_signature,
+                block_index,
+                &instruction_index,
+                block_time,
+                &mints,
+                &instruction.parsed,
+            )?,
+            STAKE_PROGRAM if wanted(Kind::StakeDelegation) => parse_stake_delegation(
+                &main_signature,
+                block_index,
+                &instruction_index,
+                block_time,
+                &instruction.parsed,
+            )?,
+            _ => {
+                // If unsupported or not asked for, skipping it silently.
                 continue;
             }
         };
-        for instruction in &message.instructions {
-            let instruction = match instruction {
-                UiInstruction::Parsed(UiParsedInstruction::Parsed(instruction)) => instruction,
-                _ => {
-                    // Skipping partially decoded instructions silently.
-                    continue;
-                }
-            };
+        records.extend(record);
+    }
+    Ok(records)
+}
 
-            // We're only interested in vote and transfer instructions.
-            match instruction.program_id.as_str() {
-                "Vote111111111111111111111111111111111111111" => {
-                    emit_vote(
-                        tx,
-                        &main_signature,
-                        block_index,
-                        block_time,
-                        &instruction.parsed,
-                    )
-                    .await?
-                }
-                "11111111111111111111111111111111" => {
-                    emit_transfer(
-                        tx,
-                        &main_signature,
-                        block_index,
-                        block_time,
-                        &instruction.parsed,
-                    )
-                    .await?
-                }
-                _ => {
-                    // If unsupported instruction, skipping it silently.
-                    continue;
-                }
-            }
+/// Make records out of the rewards the block lists, keeping their order.
+fn parse_rewards(block_index: &u64, block_time: &u64, rewards: &Rewards) -> Result<Vec<Record>> {
+    let mut records = Vec::with_capacity(rewards.len());
+    for (reward_index, reward) in rewards.iter().enumerate() {
+        records.push(Record::Reward(Reward {
+            block_index: *block_index,
+            reward_index: reward_index as u32,
+            timestamp: *block_time,
+            pubkey: Pubkey::from_str(&reward.pubkey)?,
+            lamports: reward.lamports,
+            post_balance: reward.post_balance,
+            reward_type: reward.reward_type.map(Into::into),
+            commission: reward.commission,
+            // Only known once committed:
+            indexed_at: None,
+        }));
+    }
+    Ok(records)
+}
+
+/// Make records out of all the transactions contained in a given block, along with its rewards.
+/// This expects the block to be loaded with `UiTransactionEncoding::JsonParsed`.
+fn extract_transactions(
+    kinds: &[Kind],
+    block_index: &u64,
+    block_time: &u64,
+    rewards: &Rewards,
+    transactions: &[EncodedTransactionWithStatusMeta],
+) -> Result<Vec<Record>> {
+    // Where each instruction stands within the block, across the transactions.
+    let mut position = 0u32;
+    let mut records = Vec::new();
+    for transaction in transactions {
+        records.extend(parse_transaction(
+            kinds,
+            transaction,
+            block_index,
+            block_time,
+            &mut position,
+        )?);
+    }
+    if kinds.contains(&Kind::Reward) {
+        records.extend(parse_rewards(block_index, block_time, rewards)?);
+    }
+    Ok(records)
+}
+
+/// Hand whatever came of the block over to the committer, if anything did.
+async fn send_extracted(tx: &Sender, block: u64, extracted: Option<Extracted>) {
+    let sent = match extracted {
+        // All at once, so that the committer can write the whole block down in one go.
+        Some(Extracted::Records(records)) if !records.is_empty() => tx.send(records).await,
+        // Noted down, so that it does not count as a gap.
+        Some(Extracted::Skipped(skipped)) => tx.skip(skipped).await,
+        // Likewise.
+        Some(Extracted::Empty(empty)) => tx.empty(empty).await,
+        _ => return,
+    };
+    if let Err(e) = sent {
+        tracing::trace!("While sending what came of block #{block}: {e:?}");
+    }
+}
+
+/// The transactions of the block, if there is anything to extract from it.
+/// A block may well have none; but the node leaving them out altogether is odd.
+fn transactions_of(
+    block: u64,
+    block_data: UiConfirmedBlock,
+) -> Option<Vec<EncodedTransactionWithStatusMeta>> {
+    match block_data.transactions {
+        None => {
+            tracing::warn!("Block #{block} came without the transactions, skipping...");
+            None
         }
+        Some(transactions) if transactions.is_empty() => {
+            tracing::debug!("Block #{block} is empty");
+            None
+        }
+        Some(transactions) => Some(transactions),
     }
-    Ok(())
 }
 
-/// Load the block and get all the transactions in it.
-#[instrument(name = "extract", level = "info", skip(client, tx))]
-async fn extract_all_transactions_in_block(
-    tx: &mpsc::Sender<Record>,
+/// Load the block and make records out of all the transactions in it,
+/// or tell that the cluster has skipped it; none if there is nothing to hand over.
+/// This blocks, as the client does.
+#[instrument(
+    name = "extract",
+    level = "info",
+    skip(client, metrics, kinds, clock_skew),
+    err
+)]
+fn fetch_block(
     client: &RpcClient,
+    metrics: &Metrics,
+    kinds: &[Kind],
+    clock_skew: &ClockSkew,
     block: u64,
-) -> Result<()> {
+) -> Result<Option<Extracted>> {
     use solana_client::client_error::{ClientError, ClientErrorKind};
     use solana_client::rpc_request::RpcError::RpcResponseError;
-    use solana_sdk::commitment_config::CommitmentConfig;
 
     tracing::info!("Extracting block #{block}...");
+    let timer = metrics.rpc_get_block.start_timer();
     let block_data = client.get_block_with_config(
         block,
         RpcBlockConfig {
             encoding: Some(UiTransactionEncoding::JsonParsed),
             commitment: Some(CommitmentConfig::confirmed()),
             max_supported_transaction_version: Some(0),
+            // Sizable, so only asked for when looked at:
+            rewards: Some(kinds.contains(&Kind::Reward)),
             ..Default::default()
         },
     );
+    timer.observe_duration();
     tracing::trace!("Loaded block data");
-    let block_data = match block_data {
+    let mut block_data = match block_data {
         Err(ClientError {
             kind: ClientErrorKind::RpcError(RpcResponseError { code: -32007, .. }),
             ..
         }) => {
             // This is benign, and we don't want to pollute the logs with it.
             tracing::info!("Block #{block} is missing, skipping...");
-            return Ok(());
+            return Ok(Some(Extracted::Skipped(block)));
+        }
+        // No node has it yet, so it is to be retried rather than passed over.
+        Err(e) if pool::is_behind(&e) => {
+            metrics.rpc_errors.inc();
+            return Err(e.into());
         }
         Err(e) => {
+            metrics.rpc_errors.inc();
+            let e = result::redacted(e);
             tracing::error!("Failed to get block #{block}: {e:?}, skipping...");
-            return Ok(());
+            return Ok(None);
         }
         Ok(block_data) => block_data,
     };
-    let block_time = client.get_block_time(block).map(|t| t as u64)?;
+    let rewards = block_data.rewards.take().unwrap_or_default();
+    let empty = block_data.transactions.as_ref().is_some_and(Vec::is_empty);
+    let transactions = match transactions_of(block, block_data) {
+        Some(transactions) => transactions,
+        None if !rewards.is_empty() => Vec::new(),
+        None if empty => return Ok(Some(Extracted::Empty(block))),
+        None => return Ok(None),
+    };
+    let block_time = client.get_block_time(block).map_err(|e| {
+        metrics.rpc_errors.inc();
+        e
+    })? as u64;
     tracing::trace!("Block #{block} was mined at {block_time}");
-    let Some(transactions) = block_data.transactions else {
-        tracing::warn!("Block #{block} has no transactions, skipping...");
-        return Ok(());
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let checked = clock_skew.check(block, block_time, now);
+    if checked != Some(block_time) {
+        metrics.future_block_times.inc();
+    }
+    let Some(block_time) = checked else {
+        return Ok(None);
     };
-    extract_transactions(tx, &block, &block_time, &transactions).await
+    let records = extract_transactions(kinds, &block, &block_time, &rewards, &transactions)?;
+    if records.is_empty() {
+        return Ok(Some(Extracted::Empty(block)));
+    }
+    Ok(Some(Extracted::Records(records)))
+}
+
+/// Load the block and get all the transactions in it.
+pub async fn extract_all_transactions_in_block(
+    tx: &Sender,
+    client: &RpcClient,
+    metrics: &Metrics,
+    kinds: &[Kind],
+    clock_skew: &ClockSkew,
+    block: u64,
+) -> Result<()> {
+    let extracted = fetch_block(client, metrics, kinds, clock_skew, block)?;
+    send_extracted(tx, block, extracted).await;
+    Ok(())
+}
+
+/// A block being fetched, the oldest first.
+struct InFlight {
+    block: u64,
+    timer: prometheus::HistogramTimer,
+    task: JoinHandle<Result<Option<Extracted>>>,
+}
+
+/// The blocks being fetched side by side, handed over in the order they were taken,
+/// so that the last known block only ever moves forward.
+struct Window {
+    client: Arc<RpcClient>,
+    metrics: Arc<Metrics>,
+    kinds: Arc<[Kind]>,
+    clock_skew: ClockSkew,
+    size: usize,
+    in_flight: VecDeque<InFlight>,
+}
+
+impl Window {
+    fn new(client: Arc<RpcClient>, metrics: Arc<Metrics>, settings: &Settings) -> Self {
+        Self {
+            client,
+            metrics,
+            kinds: settings.kinds.clone().into(),
+            clock_skew: settings.clock_skew,
+            size: settings.concurrency,
+            in_flight: VecDeque::with_capacity(settings.concurrency),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.in_flight.len() >= self.size
+    }
+
+    fn is_empty(&self) -> bool {
+        self.in_flight.is_empty()
+    }
+
+    /// Start fetching the block on the blocking pool, as the client blocks.
+    fn take(&mut self, block: u64, span: tracing::Span) {
+        let timer = self.metrics.block_processing.start_timer();
+        let (client, metrics, kinds) = (
+            self.client.clone(),
+            self.metrics.clone(),
+            self.kinds.clone(),
+        );
+        let clock_skew = self.clock_skew;
+        let task = tokio::task::spawn_blocking(move || {
+            span.in_scope(|| fetch_block(&client, &metrics, &kinds, &clock_skew, block))
+        });
+        self.in_flight.push_back(InFlight { block, timer, task });
+    }
+
+    /// Wait for the oldest block taken, and hand it over to the committer,
+    /// yielding it along with whether the cluster has skipped it; none if nothing is in flight.
+    async fn hand_over(&mut self, tx: &Sender) -> Result<Option<(u64, bool)>> {
+        let Some(InFlight { block, timer, task }) = self.in_flight.pop_front() else {
+            return Ok(None);
+        };
+        let extracted = task.await.map_err(std::io::Error::other)??;
+        let skipped = matches!(extracted, Some(Extracted::Skipped(_)));
+        send_extracted(tx, block, extracted).await;
+        timer.observe_duration();
+        self.metrics.blocks_processed.inc();
+        Ok(Some((block, skipped)))
+    }
 }
 
 /// [extract_continuously] sans retries.
 async fn do_extract_continuously(
-    tx: &mpsc::Sender<Record>,
+    tx: &Sender,
     stop: CancellationToken,
-    rpc_url: &str,
-    since_block: &mut Option<u64>,
+    connection: &Connection,
+    settings: &Settings,
+    health: &Health,
+    metrics: &Arc<Metrics>,
+    resumption: &mut Resumption,
 ) -> Result<()> {
-    let client = RpcClient::new(rpc_url);
-    tracing::info!("Connected to `{}`", client.url());
+    let client = Arc::new(connection.client()?);
+    tracing::info!("Connected to `{}`", connection.origin());
 
-    let mut next_block = match since_block {
-        None => {
-            let epoch_schedule = client.get_epoch_schedule()?;
-            let current_epoch = client.get_epoch_info()?.epoch;
-            epoch_schedule.get_first_slot_in_epoch(current_epoch)
+    if resumption.epochs.is_none() {
+        resumption.epochs = Some(Epochs::load(&client, settings.leader_schedule)?);
+    }
+    let epochs = resumption.epochs.as_mut().unwrap();
+    let mut next_block = match resumption.since_block {
+        None if settings.start_at == StartAt::Latest => {
+            client.get_slot_with_commitment(CommitmentConfig::finalized())?
         }
-        Some(block) => *block,
+        None => epochs.schedule().get_first_slot_in_epoch(epochs.epoch()),
+        Some(block) => block,
     };
 
     tracing::info!("Starting with block #{next_block}...");
 
+    let mut completions = Completions::starting_at(next_block);
+    let mut window = Window::new(client.clone(), metrics.clone(), settings);
+
+    let mut tip = Tip::default();
+    let mut lag_warning = Throttle::new(LAG_WARNING_INTERVAL);
+    let mut phase = Phase::Backfilling;
     loop {
-        extract_all_transactions_in_block(tx, &client, next_block).await?;
+        health.beat();
+        if !window.is_full() {
+            let latest = tip.get(&client);
+            if let Some(latest) = latest {
+                let lag = latest.saturating_sub(next_block);
+                if lag > settings.lag_threshold && lag_warning.ready() {
+                    tracing::warn!(lag, next_block, latest, "Falling behind the cluster");
+                }
+            }
+
+            // Not touching the blocks that could still get orphaned:
+            let deepest = latest.map(|latest| latest.saturating_sub(settings.confirmation_depth));
+            let previous = phase;
+            phase = phase.next(next_block, deepest, settings.catch_up_threshold);
+            if phase != previous {
+                tracing::info!(?phase, next_block, latest, "Switching phase");
+            }
+            health.progressed(Progress {
+                phase: Some(phase),
+                next_block: Some(next_block),
+                tip: latest,
+            });
+            let shallow =
+                phase == Phase::Live && deepest.is_some_and(|deepest| next_block > deepest);
+            if !shallow {
+                let epoch = epochs.roll_to(&client, next_block);
+                let span = tracing::info_span!("block", epoch, leader = tracing::field::Empty);
+                if let Some(leader) = epochs.leader_of(next_block) {
+                    span.record("leader", tracing::field::display(leader));
+                }
+                window.take(next_block, span);
+                next_block += 1;
+                continue;
+            }
+            if window.is_empty() {
+                tracing::trace!("Waiting for block #{next_block} to get deep enough...");
+                tokio::select! {
+                    _ = stop.cancelled() => break Ok(()),
+                    _ = tokio::time::sleep(settings.poll_interval) => {}
+                }
+                tip.invalidate();
+                continue;
+            }
+        }
+
+        // Either the window is full, or there is nothing more to take yet:
+        let Some((block, skipped)) = window.hand_over(tx).await? else {
+            continue;
+        };
 
         if stop.is_cancelled() {
             break Ok(());
         }
 
-        next_block += 1;
-        *since_block = Some(next_block);
+        completions.complete(block);
+        resumption.since_block = Some(completions.resume_at());
+
+        let in_a_row = health.block_processed(skipped);
+        if settings
+            .fail_on_gap
+            .is_some_and(|threshold| in_a_row > threshold)
+        {
+            let reason =
+                format!("{in_a_row} blocks in a row skipped by the cluster, up to #{block}");
+            tracing::error!("Giving up after {reason}.");
+            health.extraction_failed(format!("extraction gave up after {reason}"));
+            break Ok(());
+        }
     }
 }
 
-/// Connect to the provided RPC URL and extract all the transaction data for the current epoch
+/// [backfill] sans retries, moving the start of the blocks past each one handed over.
+async fn do_backfill(
+    tx: &Sender,
+    stop: &CancellationToken,
+    connection: &Connection,
+    settings: &Settings,
+    metrics: &Arc<Metrics>,
+    blocks: &mut RangeInclusive<u64>,
+) -> Result<()> {
+    let client = Arc::new(connection.client()?);
+    tracing::info!("Connected to `{}`", connection.origin());
+
+    let mut window = Window::new(client, metrics.clone(), settings);
+    let mut next_block = *blocks.start();
+    loop {
+        if !window.is_full() && blocks.contains(&next_block) {
+            window.take(next_block, tracing::info_span!("backfill"));
+            next_block += 1;
+            continue;
+        }
+        let Some((block, _)) = window.hand_over(tx).await? else {
+            break Ok(());
+        };
+        if stop.is_cancelled() {
+            break Ok(());
+        }
+        *blocks = block + 1..=*blocks.end();
+    }
+}
+
+/// Note the failure, and wait a while before retrying,
+/// unless it is time to give up, telling the `health` so, or to stop.
+/// Yield whether to retry.
+async fn recover(
+    e: result::Error,
+    at: Option<u64>,
+    retries: &mut Retries,
+    settings: &Settings,
+    stop: &CancellationToken,
+    health: &Health,
+) -> bool {
+    tracing::error!("Failed to extract: {e:?}");
+    if let Some(reason) = retries.failed_at(at) {
+        tracing::error!("Giving up after {reason}.");
+        health.extraction_failed(format!("extraction gave up after {reason}: {e}"));
+        return false;
+    }
+    let delay = settings.backoff.delay(retries.in_a_row, rand::random());
+    tracing::info!("Retrying in {delay:?}...");
+    tokio::select! {
+        _ = stop.cancelled() => false,
+        _ = tokio::time::sleep(delay) => true,
+    }
+}
+
+/// Connect to the provided RPC node and extract all the transaction data for the current epoch
 /// and onwards, sending them by the channel.
 /// Stop if there are no readily available finalized blocks.
-/// Retry up to 3 times if anything goes wrong, then give up.
+/// Backfill as fast as the node serves the blocks, then poll for each new one once caught up,
+/// telling the `health` which of the two it is doing.
+/// Retry up to `max_retries` times if anything goes wrong, then give up,
+/// telling the `health` so that the process stops passing for ready.
 pub async fn extract_continuously(
-    tx: mpsc::Sender<Record>,
+    tx: Sender,
     stop: CancellationToken,
-    rpc_url: String,
+    connection: Connection,
+    settings: Settings,
     since_block: Option<u64>,
+    health: Arc<Health>,
+    metrics: Arc<Metrics>,
 ) {
-    let mut since_block = since_block;
-    let mut retries = 0;
+    let mut retries = Retries::new(settings.max_block_retries, settings.max_retries);
+    let mut resumption = Resumption {
+        since_block,
+        epochs: None,
+    };
     loop {
-        match do_extract_continuously(&tx, stop.clone(), &rpc_url, &mut since_block).await {
-            Ok(()) => break,
-            Err(e) => {
-                tracing::error!("Failed to extract: {e:?}");
-                retries += 1;
-                if retries > 3 {
-                    tracing::error!("Giving up after 3 retries.");
-                    break;
-                }
+        let extracted = do_extract_continuously(
+            &tx,
+            stop.clone(),
+            &connection,
+            &settings,
+            &health,
+            &metrics,
+            &mut resumption,
+        )
+        .await;
+        let Err(e) = extracted else {
+            break;
+        };
+        let at = resumption.since_block;
+        if !recover(e, at, &mut retries, &settings, &stop, &health).await {
+            break;
+        }
+    }
+    if settings.stop_once_given_up && health.unready_reason().is_some() {
+        stop.cancel();
+    }
+}
+
+/// Extract the given blocks the same way as [extract_continuously] does, then stop everything.
+/// Meant for catching up on the history, with the store keeping the progress of the backfill
+/// apart from the one of the continuous extraction.
+pub async fn backfill(
+    tx: Sender,
+    stop: CancellationToken,
+    connection: Connection,
+    settings: Settings,
+    mut blocks: RangeInclusive<u64>,
+    health: Arc<Health>,
+    metrics: Arc<Metrics>,
+) {
+    let (from, to) = (*blocks.start(), *blocks.end());
+    tracing::info!("Backfilling blocks #{from} to #{to}...");
+    let mut retries = Retries::new(settings.max_block_retries, settings.max_retries);
+    loop {
+        let backfilled =
+            do_backfill(&tx, &stop, &connection, &settings, &metrics, &mut blocks).await;
+        let Err(e) = backfilled else {
+            if blocks.is_empty() {
+                tracing::info!("Backfilled blocks #{from} to #{to}");
             }
+            break;
+        };
+        let at = Some(*blocks.start());
+        if !recover(e, at, &mut retries, &settings, &stop, &health).await {
+            break;
         }
     }
+    stop.cancel();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::ValueEnum;
+    use solana_transaction_status::UiPartiallyDecodedInstruction;
+
+    fn compute_budget_instruction(data: &[u8]) -> UiInstruction {
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
+            UiPartiallyDecodedInstruction {
+                program_id: COMPUTE_BUDGET_PROGRAM.to_owned(),
+                accounts: vec![],
+                data: bs58::encode(data).into_string(),
+                stack_height: None,
+            },
+        ))
+    }
+
+    #[test]
+    fn compute_budget_parsed() {
+        // Given a transaction setting both the price and the limit:
+        let mut set_price = vec![3];
+        set_price.extend_from_slice(&25_000u64.to_le_bytes());
+        let mut set_limit = vec![2];
+        set_limit.extend_from_slice(&200_000u32.to_le_bytes());
+        let instructions = [
+            compute_budget_instruction(&set_limit),
+            compute_budget_instruction(&set_price),
+        ];
+
+        // When we look for the budget:
+        let budget = parse_compute_budget(&instructions);
+
+        // Then both should be found:
+        assert_eq!(
+            budget,
+            ComputeBudget {
+                unit_price: Some(25_000),
+                unit_limit: Some(200_000),
+            }
+        );
+    }
+
+    #[test]
+    fn empty_block_has_nothing_to_extract() {
+        // Given a block without any transactions, as the node sends it:
+        let fixture = include_str!("../fixtures/empty_block.json");
+        let block_data: UiConfirmedBlock = serde_json::from_str(fixture).unwrap();
+
+        // When we look for the transactions:
+        let transactions = transactions_of(777, block_data);
+
+        // Then there should be nothing to extract:
+        assert!(transactions.is_none());
+    }
+
+    #[test]
+    fn token_kept_out_of_logs() {
+        // Given a connection with a token, to a node with a key in its URL as well:
+        let connection = Connection {
+            urls: vec!["https://rpc.example.com/v1/hunter2?api-key=hunter3".to_owned()],
+            quarantine: Duration::from_secs(30),
+            timeout: Duration::from_secs(30),
+            headers: vec![],
+            user_agent: "surf/test".to_owned(),
+            token: Some(Token {
+                secret: "hunter4".to_owned(),
+                scheme: TokenScheme::Query,
+            }),
+            ca_cert: None,
+            insecure: false,
+        };
+
+        // When we tell where it goes, or how it is set up:
+        let origin = connection.origin();
+        let token = format!("{:?}", connection.token);
+
+        // Then none of the secrets should show:
+        assert_eq!(origin, "https://rpc.example.com");
+        assert!(!token.contains("hunter4"));
+
+        // And the client should still get made:
+        assert!(connection.client().is_ok());
+    }
+
+    #[test]
+    fn nonce_instructions_parsed() {
+        // Given a transaction advancing a nonce before a transfer, and another withdrawing from it:
+        let fixture = include_str!("../fixtures/nonce_transactions.json");
+        let transactions: Vec<EncodedTransactionWithStatusMeta> =
+            serde_json::from_str(fixture).unwrap();
+        let nonce = Pubkey::from_str("96GzYFvs4dEeswTaQFQhvbGi6NHUcNHhEgXnPrRjyF2s").unwrap();
+        let payee = Pubkey::from_str("9h3NctATCQvBt2QD7k7LYBZEmrEuPyvWNRicVZwPBc6M").unwrap();
+
+        // When we parse them:
+        let mut position = 0;
+        let advanced = parse_transaction(
+            Kind::value_variants(),
+            &transactions[0],
+            &777,
+            &0,
+            &mut position,
+        )
+        .unwrap();
+        let withdrawn = parse_transaction(
+            Kind::value_variants(),
+            &transactions[1],
+            &777,
+            &0,
+            &mut position,
+        )
+        .unwrap();
+
+        // Then advancing should yield nothing but the transfer after it:
+        let [Record::Transfer(transfer)] = advanced.as_slice() else {
+            panic!("expected the transfer alone: {advanced:?}");
+        };
+        assert_eq!((transfer.instruction_index, transfer.lamports), (1, 5000));
+
+        // And withdrawing should move the lamports out of the nonce account:
+        let [Record::Transfer(transfer)] = withdrawn.as_slice() else {
+            panic!("expected the withdrawal: {withdrawn:?}");
+        };
+        assert_eq!(transfer.instruction_index, 2);
+        assert_eq!((transfer.source, transfer.destination), (nonce, payee));
+        assert_eq!(transfer.lamports, 1_447_680);
+        assert_eq!(
+            transfer.instruction_kind.as_deref(),
+            Some("withdrawFromNonce")
+        );
+    }
+
+    #[test]
+    fn token_transfers_parsed() {
+        // Given a plain token transfer, and a checked one by a delegate after approving it:
+        let fixture = include_str!("../fixtures/token_transactions.json");
+        let transactions: Vec<EncodedTransactionWithStatusMeta> =
+            serde_json::from_str(fixture).unwrap();
+        let mint = Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap();
+        let owner = Pubkey::from_str("Af2Y56WUFQuTTTYHMCjMozYsDxvTvSM6YQnyv8E6EK3v").unwrap();
+        let delegate = Pubkey::from_str("7Np41oeYqPefeNQEHSv1UDhYrehxin3NStELsSKCT4K2").unwrap();
+        let source = Pubkey::from_str("96GzYFvs4dEeswTaQFQhvbGi6NHUcNHhEgXnPrRjyF2s").unwrap();
+
+        // When we parse them:
+        let mut position = 0;
+        let plain = parse_transaction(
+            Kind::value_variants(),
+            &transactions[0],
+            &777,
+            &0,
+            &mut position,
+        )
+        .unwrap();
+        let checked = parse_transaction(
+            Kind::value_variants(),
+            &transactions[1],
+            &777,
+            &0,
+            &mut position,
+        )
+        .unwrap();
+
+        // Then the plain one should get its token from the balances:
+        let [Record::TokenTransfer(transfer)] = plain.as_slice() else {
+            panic!("expected the transfer alone: {plain:?}");
+        };
+        assert_eq!((transfer.mint, transfer.owner), (mint, owner));
+        assert_eq!((transfer.source_ata, transfer.amount), (source, 1_000_000));
+
+        // And the checked one should tell it by itself, while the approval should yield nothing:
+        let [Record::TokenTransfer(transfer)] = checked.as_slice() else {
+            panic!("expected the checked transfer alone: {checked:?}");
+        };
+        assert_eq!((transfer.mint, transfer.owner), (mint, delegate));
+        assert_eq!((transfer.instruction_index, transfer.amount), (2, 250_000));
+    }
+
+    #[test]
+    fn kinds_not_asked_for_left_unparsed() {
+        // Given a token transfer of an amount that makes no sense:
+        let fixture = include_str!("../fixtures/token_transactions.json");
+        let fixture = fixture.replacen(r#""amount": "1000000""#, r#""amount": "a lot""#, 1);
+        let transactions: Vec<EncodedTransactionWithStatusMeta> =
+            serde_json::from_str(&fixture).unwrap();
+
+        // When we parse it for the votes alone, then it should yield nothing, rather than fail:
+        let records = parse_transaction(&[Kind::Vote], &transactions[0], &777, &0, &mut 0);
+        assert_eq!(records.unwrap(), vec![]);
+
+        // And when we parse it for the token transfers, then it should fail:
+        let kinds = [Kind::TokenTransfer];
+        assert!(parse_transaction(&kinds, &transactions[0], &777, &0, &mut 0).is_err());
+    }
+
+    #[test]
+    fn stake_delegations_parsed() {
+        // Given a delegation, and a deactivation of the same stake:
+        let (stake, vote, authority) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let delegate = serde_json::json!({
+            "type": "delegate",
+            "info": {
+                "stakeAccount": stake.to_string(),
+                "voteAccount": vote.to_string(),
+                "clockSysvar": "SysvarC1ock11111111111111111111111111111111",
+                "stakeAuthority": authority.to_string(),
+            },
+        });
+        let deactivate = serde_json::json!({
+            "type": "deactivate",
+            "info": {
+                "stakeAccount": stake.to_string(),
+                "stakeAuthority": authority.to_string(),
+            },
+        });
+
+        // When we parse them:
+        let signature = Signature::new_unique();
+        let delegated = parse_stake_delegation(&signature, &777, &3, &0, &delegate).unwrap();
+        let deactivated = parse_stake_delegation(&signature, &777, &4, &0, &deactivate).unwrap();
+
+        // Then only the delegation should yield a record, telling where the stake goes:
+        let Some(Record::StakeDelegation(delegation)) = delegated else {
+            panic!("expected the delegation: {delegated:?}");
+        };
+        assert_eq!(
+            (delegation.stake_account, delegation.vote_account),
+            (stake, vote)
+        );
+        assert_eq!(delegation.authority, authority);
+        assert_eq!(deactivated, None);
+    }
+
+    #[test]
+    fn account_creation_parsed() {
+        // Given a block creating and funding an account, then assigning it to a program:
+        let fixture = include_str!("../fixtures/account_creation_block.json");
+        let block_data: UiConfirmedBlock = serde_json::from_str(fixture).unwrap();
+        let funder = Pubkey::from_str("Af2Y56WUFQuTTTYHMCjMozYsDxvTvSM6YQnyv8E6EK3v").unwrap();
+        let created = Pubkey::from_str("7Np41oeYqPefeNQEHSv1UDhYrehxin3NStELsSKCT4K2").unwrap();
+
+        // When we parse its transactions:
+        let transactions = transactions_of(777, block_data).unwrap();
+        let records =
+            parse_transaction(Kind::value_variants(), &transactions[0], &777, &0, &mut 0).unwrap();
+
+        // Then the funding should count as a transfer to the new account, telling where it came from,
+        // while the assignment, moving no lamports, should yield nothing:
+        let [Record::Transfer(transfer)] = records.as_slice() else {
+            panic!("expected the funding alone: {records:?}");
+        };
+        assert_eq!((transfer.source, transfer.destination), (funder, created));
+        assert_eq!(transfer.lamports, 2_039_280);
+        assert_eq!(transfer.instruction_kind.as_deref(), Some("createAccount"));
+    }
+
+    #[test]
+    fn intermittent_failures_never_give_up() {
+        // Given the retries allowed per block, but not per run:
+        let mut retries = Retries::new(3, None);
+
+        // When the failures keep coming, but with some progress between them:
+        let reasons: Vec<_> = (0..10)
+            .map(|block| retries.failed_at(Some(block)))
+            .collect();
+
+        // Then the extraction should keep going:
+        assert!(reasons.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn failing_block_gives_up() {
+        // Given the retries allowed per block:
+        let mut retries = Retries::new(3, None);
+
+        // When the same block keeps failing, after some other block failed:
+        assert_eq!(retries.failed_at(Some(776)), None);
+        for _ in 0..3 {
+            assert_eq!(retries.failed_at(Some(777)), None);
+        }
+
+        // Then the extraction should give up on one more failure:
+        let reason = retries.failed_at(Some(777)).unwrap();
+        assert_eq!(reason, "3 retries in a row at block #777");
+    }
+
+    #[test]
+    fn run_cap_still_applies() {
+        // Given a cap over the whole run:
+        let mut retries = Retries::new(3, Some(2));
+
+        // When the failures spread across the blocks add up past it:
+        let reasons: Vec<_> = (0..3).map(|block| retries.failed_at(Some(block))).collect();
+
+        // Then the extraction should give up on the last one:
+        assert_eq!(reasons[..2], [None, None]);
+        assert_eq!(reasons[2].as_deref(), Some("2 retries over the run"));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_cap() {
+        // Given a backoff from half a second up to ten:
+        let backoff = Backoff {
+            base: Duration::from_millis(500),
+            max: Duration::from_secs(10),
+        };
+
+        // When the failures keep coming in a row:
+        let delays: Vec<_> = (1..=6).map(|k| backoff.delay(k, 0.0)).collect();
+
+        // Then the wait should double each time, stopping at the cap:
+        let millis: Vec<_> = delays.iter().map(Duration::as_millis).collect();
+        assert_eq!(millis, [500, 1000, 2000, 4000, 8000, 10000]);
+
+        // And the jitter should add at most the base, even far along:
+        assert_eq!(backoff.delay(2, 0.5), Duration::from_millis(1250));
+        assert_eq!(backoff.delay(100, 0.9), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn phases_follow_tip() {
+        // Given the latest block deep enough to take at #1000, and a threshold of 100 blocks:
+        let deepest = Some(1000);
+        let threshold = 100;
+
+        // When backfilling, then it should go on until past the tip, and only then go live:
+        let backfilling = Phase::Backfilling;
+        assert_eq!(
+            backfilling.next(999, deepest, threshold),
+            Phase::Backfilling
+        );
+        assert_eq!(
+            backfilling.next(1000, deepest, threshold),
+            Phase::Backfilling
+        );
+        assert_eq!(backfilling.next(1001, deepest, threshold), Phase::Live);
+
+        // And when live, then it should stay so while a little behind,
+        // and go back to backfilling once the threshold behind:
+        let live = Phase::Live;
+        assert_eq!(live.next(1001, deepest, threshold), Phase::Live);
+        assert_eq!(live.next(901, deepest, threshold), Phase::Live);
+        assert_eq!(live.next(900, deepest, threshold), Phase::Backfilling);
+
+        // And when the node does not tell its tip, then either should stay as is:
+        assert_eq!(backfilling.next(1001, None, threshold), Phase::Backfilling);
+        assert_eq!(live.next(0, None, threshold), Phase::Live);
+    }
+
+    #[test]
+    fn future_block_times_caught() {
+        // Given a tolerance of a minute, either skipping or clamping:
+        let tolerance = Duration::from_secs(60);
+        let skipping = ClockSkew {
+            tolerance,
+            clamp: false,
+        };
+        let clamping = ClockSkew {
+            tolerance,
+            clamp: true,
+        };
+        let now = 1_700_000_000;
+
+        // When the block is within the tolerance, or in the past, then it should pass as is:
+        assert_eq!(skipping.check(777, now + 60, now), Some(now + 60));
+        assert_eq!(skipping.check(777, now - 3600, now), Some(now - 3600));
+
+        // And when it is further ahead, then it should be skipped or clamped:
+        assert_eq!(skipping.check(777, now + 61, now), None);
+        assert_eq!(clamping.check(777, now + 61, now), Some(now));
+    }
+
+    #[test]
+    fn compute_budget_absent() {
+        // Given a transaction with an unrelated instruction only:
+        let instructions = [compute_budget_instruction(&[0xff])];
+
+        // When we look for the budget:
+        let budget = parse_compute_budget(&instructions);
+
+        // Then nothing should be found:
+        assert_eq!(budget, ComputeBudget::default());
+    }
+
+    #[tokio::test]
+    async fn headless_extraction_stops_once_given_up() {
+        // Given the extraction with nowhere to connect to, no retries, and nothing serving:
+        let connection = Connection {
+            urls: vec![],
+            quarantine: Duration::ZERO,
+            timeout: Duration::ZERO,
+            headers: vec![],
+            user_agent: "surf/test".to_owned(),
+            token: None,
+            ca_cert: None,
+            insecure: false,
+        };
+        let settings = Settings {
+            max_block_retries: 0,
+            max_retries: None,
+            backoff: Backoff {
+                base: Duration::ZERO,
+                max: Duration::ZERO,
+            },
+            lag_threshold: 1000,
+            catch_up_threshold: 100,
+            confirmation_depth: 0,
+            poll_interval: Duration::ZERO,
+            start_at: StartAt::default(),
+            kinds: vec![],
+            clock_skew: ClockSkew::default(),
+            leader_schedule: false,
+            concurrency: 1,
+            fail_on_gap: None,
+            stop_once_given_up: true,
+        };
+        let (tx, _rx) = crate::channel::channel(1, None);
+        let stop = CancellationToken::new();
+        let health = Arc::new(Health::default());
+        let metrics = Arc::new(Metrics::new().unwrap());
+
+        // When it runs:
+        extract_continuously(
+            tx,
+            stop.clone(),
+            connection,
+            settings,
+            None,
+            health.clone(),
+            metrics,
+        )
+        .await;
+
+        // Then it should give up, and stop the rest along with it:
+        assert!(health.unready_reason().is_some());
+        assert!(stop.is_cancelled());
+    }
 }
@@ -0,0 +1,107 @@
+//! What the orchestration needs to know about the process.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::extraction::Phase;
+
+/// How long the extraction may go without a heartbeat before it counts as stuck.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Whether the parts of the process are doing their job.
+#[derive(Debug, Default)]
+pub struct Health {
+    /// Why the extraction has given up for good, if it has.
+    extraction_failure: Mutex<Option<String>>,
+    /// How far the extraction has got, as of the last block it looked at.
+    progress: Mutex<Progress>,
+    /// How many blocks the extraction has gone through, skipped ones included.
+    skips: Mutex<SkipStats>,
+    /// When the extraction last said it is still going, in seconds since the epoch; zero if never.
+    heartbeat: AtomicU64,
+}
+
+/// How far the extraction has got; all unknown until it starts.
+#[derive(Clone, Copy, Debug, Default, serde::Serialize)]
+pub struct Progress {
+    pub phase: Option<Phase>,
+    /// The block to be extracted next.
+    pub next_block: Option<u64>,
+    /// The latest slot of the cluster, as last heard of.
+    pub tip: Option<u64>,
+}
+
+/// How many blocks the extraction has gone through, and how many of them the cluster has skipped.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SkipStats {
+    pub processed: u64,
+    pub skipped: u64,
+    /// How many blocks in a row the cluster has skipped, up to the last one gone through.
+    pub skipped_in_a_row: u64,
+}
+
+impl Health {
+    /// Remember that the extraction is not coming back, and why.
+    pub fn extraction_failed(&self, reason: String) {
+        *self.extraction_failure.lock().unwrap() = Some(reason);
+    }
+
+    /// Why the process is not fit to serve fresh data, if it is not.
+    pub fn unready_reason(&self) -> Option<String> {
+        self.extraction_failure.lock().unwrap().clone()
+    }
+
+    /// Remember how far the extraction has got.
+    pub fn progressed(&self, progress: Progress) {
+        *self.progress.lock().unwrap() = progress;
+    }
+
+    /// How far the extraction has got, as last told.
+    pub fn progress(&self) -> Progress {
+        *self.progress.lock().unwrap()
+    }
+
+    /// Note that the extraction has gone through a block,
+    /// yielding how many blocks in a row the cluster has skipped up to it.
+    pub fn block_processed(&self, skipped: bool) -> u64 {
+        let mut skips = self.skips.lock().unwrap();
+        skips.processed += 1;
+        if skipped {
+            skips.skipped += 1;
+            skips.skipped_in_a_row += 1;
+        } else {
+            skips.skipped_in_a_row = 0;
+        }
+        skips.skipped_in_a_row
+    }
+
+    /// How many blocks the extraction has gone through so far.
+    pub fn skip_stats(&self) -> SkipStats {
+        *self.skips.lock().unwrap()
+    }
+
+    /// Note that the extraction is still going.
+    pub fn beat(&self) {
+        self.heartbeat.store(unix_now(), Ordering::Release);
+    }
+
+    /// Why the process is not fit to serve at all, if it is not:
+    /// the extraction having given up, or gone quiet for too long.
+    /// Without any extraction, as with `--dry`, there is nothing to go quiet.
+    pub fn degraded_reason(&self) -> Option<String> {
+        if let Some(reason) = self.unready_reason() {
+            return Some(reason);
+        }
+        let heartbeat = self.heartbeat.load(Ordering::Acquire);
+        let quiet = unix_now().saturating_sub(heartbeat);
+        (heartbeat != 0 && quiet > HEARTBEAT_TIMEOUT.as_secs())
+            .then(|| format!("the extraction has not been heard of for {quiet} seconds"))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
@@ -1,125 +1,2041 @@
 /* spellchecker:words blockheight */
 //! What the users see.
 
+use std::cmp::Reverse;
 use std::fmt::Debug;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 
-use actix_web::middleware::Logger;
-use actix_web::{web, App, HttpServer};
+use actix_cors::Cors;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::error::{JsonPayloadError, QueryPayloadError};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::middleware::{Compress, Condition, Logger};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use futures::future::join_all;
+use serde::de::DeserializeOwned;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
 use std::net::ToSocketAddrs;
 use tokio_util::sync::CancellationToken;
 
+mod bounding;
+mod caching;
+mod counting;
+mod debugging;
+mod etag_middleware;
+mod finding_balances;
+mod finding_changes;
+mod finding_flows;
+mod finding_rewards;
+mod finding_stake_delegations;
+mod finding_token_transfers;
 mod finding_transfers;
 mod finding_votes;
+mod paging;
+mod planning;
+mod reindexing;
+mod streaming;
 
-use crate::record::{PrettyTransfer, PrettyVote};
+use bounding::Bounded;
+use caching::Freshness;
+use etag_middleware::Etag;
+use paging::{total, PageBody, Paging};
+use planning::{Filters, QueryLog, QueryPlan, Strategy};
+use streaming::NDJSON;
+
+use crate::extraction::{ClockSkew, Connection};
+use crate::health::Health;
+use crate::metrics::Metrics;
+use crate::record::{
+    Kind, PrettyRecord, PrettyReward, PrettyStakeDelegation, PrettyTokenTransfer, PrettyTransfer,
+    PrettyVote, StakeDelegation, Tabular, Transfer, Vote,
+};
+use crate::result::Error;
 use crate::store::Store;
 use crate::Result;
 
+/// How many signatures one request may ask for at most.
+const MAX_SIGNATURES: usize = 100;
+
+/// Whether the records got cut off to fit the response.
+const TRUNCATED: &str = "x-truncated";
+
+/// How many records there were before the cut.
+const TOTAL_MATCHED: &str = "x-total-matched";
+
+/// How many records one response may carry at most.
+#[derive(Clone, Copy, Debug)]
+struct ResponseCap(usize);
+
+impl ResponseCap {
+    /// Cut the records down to the cap, telling how many there were if any got cut off.
+    fn apply<T>(&self, records: &mut Vec<T>) -> Option<usize> {
+        let total = records.len();
+        (total > self.0).then(|| {
+            records.truncate(self.0);
+            total
+        })
+    }
+}
+
+/// How many signatures one lookup may carry at most.
+#[derive(Clone, Copy, Debug)]
+struct LookupCap(usize);
+
+/// Where the checkpoints get written into, each under a name of its own.
+#[derive(Clone, Debug)]
+struct CheckpointDir(PathBuf);
+
+impl CheckpointDir {
+    /// The path of the checkpoint of the given name, refusing anything but a plain name,
+    /// so that the clients never get to write anywhere else.
+    fn resolve(&self, name: &str) -> Result<PathBuf> {
+        let plain =
+            !name.is_empty() && name != "." && !name.contains("..") && !name.contains(['/', '\\']);
+        if !plain {
+            return Err(Error::BadQueryParam {
+                param: "out".into(),
+                reason: "expected a plain name, without `/` or `..`".to_owned(),
+            });
+        }
+        Ok(self.0.join(name))
+    }
+}
+
+/// Tell the client how many records matched, if not all of them made it into the response.
+fn label_truncated(mut response: HttpResponse, total: Option<usize>) -> HttpResponse {
+    if let Some(total) = total {
+        let headers = response.headers_mut();
+        headers.insert(
+            HeaderName::from_static(TRUNCATED),
+            HeaderValue::from_static("true"),
+        );
+        headers.insert(HeaderName::from_static(TOTAL_MATCHED), total.into());
+    }
+    response
+}
+
+/// Records laid out as CSV, to be saved as a file rather than shown.
+struct CsvResponseBody(String);
+
+impl CsvResponseBody {
+    const CONTENT_TYPE: &'static str = "text/csv; charset=utf-8";
+
+    /// Whether the client asked for CSV, by the query or by `Accept`.
+    fn wanted(request: &HttpRequest, criteria: &Criteria) -> bool {
+        match criteria.format {
+            Some(format) => format == Format::Csv,
+            None => streaming::accepts(request, "text/csv"),
+        }
+    }
+
+    fn of<T: Tabular>(records: &[T]) -> Self {
+        Self(crate::record::to_csv(records))
+    }
+
+    /// The answer, to be saved under the name given.
+    fn respond(self, freshness: &Freshness, file_name: &str) -> HttpResponse {
+        let mut response = freshness.respond(self.0);
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(Self::CONTENT_TYPE),
+        );
+        as_attachment(response, file_name)
+    }
+}
+
+/// Have the client save the answer under the name given rather than show it.
+fn as_attachment(mut response: HttpResponse, file_name: &str) -> HttpResponse {
+    let disposition = format!("attachment; filename=\"{file_name}\"");
+    if let Ok(disposition) = HeaderValue::from_str(&disposition) {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    response
+}
+
 /// What a user can filter by using the query string.
 #[derive(Debug, serde::Deserialize)]
 struct Criteria {
     block: Option<u64>,
+    /// The first of the blocks to look at, inclusive.
+    block_from: Option<u64>,
+    /// The last of the blocks to look at, inclusive.
+    block_to: Option<u64>,
+    /// The earliest time of the blocks to look at, in seconds since the Unix epoch, inclusive.
+    ts_from: Option<u64>,
+    /// The latest time of the blocks to look at, in seconds since the Unix epoch, inclusive.
+    ts_to: Option<u64>,
     signature: Option<String>,
+    signatures: Option<String>,
     to: Option<String>,
     from: Option<String>,
+    program: Option<String>,
+    /// The exact amount, for the transfers only.
+    lamports: Option<u64>,
+    /// The earliest time of writing down, in seconds since the Unix epoch.
+    indexed_since: Option<u64>,
+    sort: Option<Sort>,
+    /// The cursor the page before ended with, to go on from.
+    after: Option<String>,
+    /// How many of the records to pass over before the page starts.
+    offset: Option<usize>,
+    /// How many records a page should carry.
+    limit: Option<usize>,
+    /// Whether a page should tell how many records there are across all the pages.
+    with_total: Option<bool>,
+    /// How to lay the records out, unless told by `Accept`.
+    format: Option<Format>,
+}
+
+impl Criteria {
+    /// The name to save the records under, telling the blocks and the accounts asked for,
+    /// like `transfers-777-790-to-<account>.csv`.
+    fn file_name(&self, kind: &str, extension: &str) -> String {
+        let mut name = kind.to_owned();
+        match (self.block, self.block_from, self.block_to) {
+            (Some(block), ..) => name += &format!("-{block}"),
+            (None, None, None) => {}
+            (None, from, to) => {
+                let to = to.map_or_else(|| "latest".to_owned(), |to| to.to_string());
+                name += &format!("-{}-{to}", from.unwrap_or(0));
+            }
+        }
+        for (side, account) in [("from", &self.from), ("to", &self.to)] {
+            if let Some(account) = account {
+                name += &format!("-{side}-{account}");
+            }
+        }
+        format!("{name}.{extension}")
+    }
+}
+
+/// How the records should be laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+/// The signatures to look up in one go.
+#[derive(Debug, serde::Deserialize)]
+struct Lookup {
+    signatures: Vec<String>,
+}
+
+impl Lookup {
+    /// The signatures, as long as there are not too many and all of them are well-formed.
+    fn signatures(&self, cap: LookupCap) -> Result<Vec<Signature>> {
+        if self.signatures.len() > cap.0 {
+            return Err(Error::BadBody(format!(
+                "at most {} signatures per lookup",
+                cap.0
+            )));
+        }
+        let mut signatures = Vec::with_capacity(self.signatures.len());
+        let mut malformed = Vec::new();
+        for each in &self.signatures {
+            match Signature::from_str(each) {
+                Ok(signature) => signatures.push(signature),
+                Err(_) => malformed.push(each.as_str()),
+            }
+        }
+        if !malformed.is_empty() {
+            return Err(Error::BadBody(format!(
+                "malformed signatures: {}",
+                malformed.join(", ")
+            )));
+        }
+        Ok(signatures)
+    }
+}
+
+/// In what order the records should come.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    /// By block, then by position within the block.
+    BlockAsc,
+    /// By block, the latest first.
+    BlockDesc,
+    /// By the time of the block, the earliest first.
+    TimestampAsc,
+    /// By the time of the block, the latest first.
+    TimestampDesc,
+    /// By the amount, the smallest first; for the transfers only.
+    LamportsAsc,
+    /// By the amount, the largest first; for the transfers only.
+    LamportsDesc,
+    /// By the compute unit price, the highest priority fees first.
+    PriorityFeeDesc,
+    /// By when they were written down, the latest first.
+    IndexedAtDesc,
+}
+
+/// What the records get put in order by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortField {
+    Block,
+    Timestamp,
+    Lamports,
+    PriorityFee,
+    IndexedAt,
+}
+
+/// Which way the records go.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl Sort {
+    /// The field and the way to order by, so that an index yielding the records
+    /// in that order already can be told from the ones that need sorting in memory.
+    pub fn split(self) -> (SortField, SortOrder) {
+        match self {
+            Sort::BlockAsc => (SortField::Block, SortOrder::Asc),
+            Sort::BlockDesc => (SortField::Block, SortOrder::Desc),
+            Sort::TimestampAsc => (SortField::Timestamp, SortOrder::Asc),
+            Sort::TimestampDesc => (SortField::Timestamp, SortOrder::Desc),
+            Sort::LamportsAsc => (SortField::Lamports, SortOrder::Asc),
+            Sort::LamportsDesc => (SortField::Lamports, SortOrder::Desc),
+            Sort::PriorityFeeDesc => (SortField::PriorityFee, SortOrder::Desc),
+            Sort::IndexedAtDesc => (SortField::IndexedAt, SortOrder::Desc),
+        }
+    }
+}
+
+/// Put the records in order by the key, keeping the ties in the order they came in.
+fn sort_in_memory<T, K: Ord>(records: &mut [T], order: SortOrder, key: impl Fn(&T) -> K) {
+    match order {
+        SortOrder::Asc => records.sort_by_key(key),
+        SortOrder::Desc => records.sort_by_key(|x| Reverse(key(x))),
+    }
+}
+
+/// Put the votes in the order asked, after all the filters.
+fn sort_votes(votes: &mut [Vote], sort: Sort) {
+    let (field, order) = sort.split();
+    match field {
+        SortField::Block => sort_in_memory(votes, order, |x| (x.block_index, x.instruction_index)),
+        SortField::Timestamp => sort_in_memory(votes, order, |x| {
+            (x.timestamp, x.block_index, x.instruction_index)
+        }),
+        // Turned away by the plan.
+        SortField::Lamports => {}
+        SortField::PriorityFee => sort_in_memory(votes, order, |x| x.compute_unit_price),
+        SortField::IndexedAt => sort_in_memory(votes, order, |x| x.indexed_at),
+    }
+}
+
+/// Put the stake delegations in the order asked, after all the filters.
+fn sort_stake_delegations(delegations: &mut [StakeDelegation], sort: Sort) {
+    let (field, order) = sort.split();
+    match field {
+        SortField::Block => {
+            sort_in_memory(delegations, order, |x| (x.block_index, x.instruction_index))
+        }
+        SortField::Timestamp => sort_in_memory(delegations, order, |x| {
+            (x.timestamp, x.block_index, x.instruction_index)
+        }),
+        // Turned away by the handler.
+        SortField::Lamports | SortField::PriorityFee => {}
+        SortField::IndexedAt => sort_in_memory(delegations, order, |x| x.indexed_at),
+    }
+}
+
+/// Put the transfers in the order asked, after all the filters.
+fn sort_transfers(transfers: &mut [Transfer], sort: Sort) {
+    let (field, order) = sort.split();
+    match field {
+        SortField::Block => {
+            sort_in_memory(transfers, order, |x| (x.block_index, x.instruction_index))
+        }
+        SortField::Timestamp => sort_in_memory(transfers, order, |x| {
+            (x.timestamp, x.block_index, x.instruction_index)
+        }),
+        SortField::Lamports => sort_in_memory(transfers, order, |x| x.lamports),
+        SortField::PriorityFee => sort_in_memory(transfers, order, |x| x.compute_unit_price),
+        SortField::IndexedAt => sort_in_memory(transfers, order, |x| x.indexed_at),
+    }
+}
+
+/// How the web interface is set up.
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    /// Whether to expose the endpoints that act on the database.
+    pub admin: bool,
+    /// Whether to expose the endpoints that show the database internals.
+    pub debug_endpoints: bool,
+    /// How many threads serve the requests; one per core if unset.
+    pub workers: Option<usize>,
+    /// How many pending connections may wait to be accepted.
+    pub backlog: Option<u32>,
+    /// Where to write down the bound addresses once listening, removed once stopped.
+    pub ready_file: Option<PathBuf>,
+    /// What kinds of records to keep when reindexing.
+    pub kinds: Vec<Kind>,
+    /// How far ahead of the wall clock a block time may be when reindexing.
+    pub clock_skew: ClockSkew,
+    /// Whether to compress the responses for the clients that accept it.
+    pub compress: bool,
+    /// How many records `/votes` and `/transfers` may respond with at most.
+    pub max_response_records: usize,
+    /// How many signatures one lookup may carry at most.
+    pub max_lookup_signatures: usize,
+    /// What origins the browsers may call in from; none but the own if empty, any with `*`.
+    pub cors_origins: Vec<String>,
+    /// Where `POST /admin/checkpoint` may write into; the endpoint is not there if unset.
+    pub checkpoint_dir: Option<PathBuf>,
+}
+
+/// Let the browsers call in from the given origins, or from any with `*`.
+fn cors(origins: &[String]) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(["GET", "HEAD", "POST"])
+        .allow_any_header()
+        .max_age(3600);
+    for origin in origins {
+        cors = match origin.as_str() {
+            "*" => cors.allow_any_origin(),
+            origin => cors.allowed_origin(origin),
+        };
+    }
+    cors
+}
+
+/// Split a comma-separated list of signatures, naming all the malformed ones.
+fn parse_signatures(list: &str) -> Result<Vec<Signature>> {
+    let list: Vec<_> = list.split(',').map(str::trim).collect();
+    if list.len() > MAX_SIGNATURES {
+        return Err(Error::BadQueryParam {
+            param: "signatures".into(),
+            reason: format!("at most {MAX_SIGNATURES} per request"),
+        });
+    }
+
+    let mut signatures = Vec::with_capacity(list.len());
+    let mut malformed = Vec::new();
+    for each in list {
+        match Signature::from_str(each) {
+            Ok(signature) => signatures.push(signature),
+            Err(_) => malformed.push(each.to_owned()),
+        }
+    }
+    if !malformed.is_empty() {
+        return Err(Error::BadQueryParam {
+            param: "signatures".into(),
+            reason: format!("malformed: {}", malformed.join(", ")),
+        });
+    }
+    Ok(signatures)
+}
+
+/// Parse an account address, naming the parameter it came from if it is malformed.
+fn parse_pubkey(param: &'static str, value: Option<&str>) -> Result<Option<Pubkey>> {
+    value
+        .map(|value| {
+            Pubkey::from_str(value).map_err(|e| Error::BadQueryParam {
+                param: param.into(),
+                reason: e.to_string(),
+            })
+        })
+        .transpose()
+}
+
+/// Name the parameter the query string fails on, instead of the bare complaint of the parser.
+fn query_config<T: DeserializeOwned>() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|e, request| {
+        // Leaving out the prefix actix puts in front:
+        let reason = match e {
+            QueryPayloadError::Deserialize(e) => e.to_string(),
+            e => e.to_string(),
+        };
+        let error = match offending_param::<T>(request.query_string()) {
+            Some(param) => Error::BadQueryParam {
+                param: param.into(),
+                reason,
+            },
+            None => Error::BadQuery(reason),
+        };
+        error.into()
+    })
+}
+
+/// Tell what is wrong with the body, instead of the bare complaint of the parser.
+fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|e, _| {
+        // Leaving out the prefix actix puts in front:
+        let reason = match e {
+            JsonPayloadError::Deserialize(e) => e.to_string(),
+            e => e.to_string(),
+        };
+        Error::BadBody(reason).into()
+    })
+}
+
+/// The first parameter that fails to parse on its own.
+/// A lone parameter may well miss the required ones, so that does not count.
+fn offending_param<T: DeserializeOwned>(query: &str) -> Option<String> {
+    let pairs = query.split('&').filter(|pair| !pair.is_empty());
+    pairs
+        .filter(|pair| match web::Query::<T>::from_query(pair) {
+            Ok(_) => false,
+            Err(e) => !e.to_string().contains("missing field"),
+        })
+        .map(|pair| pair.split_once('=').map_or(pair, |(key, _)| key).to_owned())
+        .next()
+}
+
+/// Which row to show as stored.
+#[derive(Debug, serde::Deserialize)]
+struct RawCriteria {
+    cf: String,
+    key: String,
+}
+
+/// What to name a checkpoint.
+#[derive(Debug, serde::Deserialize)]
+struct CheckpointCriteria {
+    out: String,
+}
+
+/// Which transfers to rank by amount.
+#[derive(Debug, serde::Deserialize)]
+struct LargestCriteria {
+    from_block: u64,
+    to_block: u64,
+    n: Option<usize>,
+}
+
+/// Where to look for the blocks missing from the store.
+#[derive(Debug, serde::Deserialize)]
+struct GapsCriteria {
+    from: u64,
+    to: u64,
+}
+
+/// Whose transfers to sum up by the counterparty.
+#[derive(Debug, serde::Deserialize)]
+struct FlowCriteria {
+    account: String,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+    edges: Option<usize>,
+}
+
+/// How many of the newest records to show.
+#[derive(Debug, serde::Deserialize)]
+struct RecentCriteria {
+    limit: Option<usize>,
+}
+
+/// Where to pick up the records indexed since.
+#[derive(Debug, serde::Deserialize)]
+struct ChangesCriteria {
+    since_block: u64,
+    limit: Option<usize>,
+}
+
+/// Whose rewards to show.
+#[derive(Debug, serde::Deserialize)]
+struct RewardCriteria {
+    block: Option<u64>,
+    pubkey: Option<String>,
+}
+
+/// Whose token transfers to show.
+#[derive(Debug, serde::Deserialize)]
+struct TokenTransferCriteria {
+    block: Option<u64>,
+    /// The mint, the owner, or either token account.
+    account: Option<String>,
+}
+
+/// What a user can pass when asking for an account balance.
+#[derive(Debug, serde::Deserialize)]
+struct BalanceCriteria {
+    at_block: Option<u64>,
 }
 
 async fn index() -> &'static str {
     "Refer to README.md for more information."
 }
 
-async fn get_last_known_block(store: web::Data<Arc<Store>>) -> Result<String> {
-    let last_known_block = store.last_known_block().await;
-    Ok(last_known_block.map_or_else(|| "null".to_owned(), |block| block.to_string()))
+async fn get_readiness(health: web::Data<Arc<Health>>) -> HttpResponse {
+    match health.unready_reason() {
+        None => HttpResponse::Ok().body("ready"),
+        Some(reason) => HttpResponse::ServiceUnavailable().body(reason),
+    }
+}
+
+async fn get_health(store: web::Data<Arc<Store>>, health: web::Data<Arc<Health>>) -> HttpResponse {
+    let reason = match store.probe() {
+        Err(e) => Some(format!("the store does not answer: {e}")),
+        Ok(()) => health.degraded_reason(),
+    };
+    match reason {
+        None => HttpResponse::Ok().json(serde_json::json!({
+            "status": "ok",
+            "last_known_block": store.last_known_block().await,
+        })),
+        Some(reason) => HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "degraded",
+            "reason": reason,
+        })),
+    }
+}
+
+async fn get_progress(health: web::Data<Arc<Health>>) -> Result<String> {
+    Ok(serde_json::to_string(&health.progress())?)
+}
+
+/// How many blocks the extraction has gone through, and what the store holds.
+#[derive(Debug, serde::Serialize)]
+struct Stats {
+    blocks_processed: u64,
+    blocks_skipped: u64,
+    /// About how many votes there are.
+    votes: u64,
+    /// About how many transfers there are.
+    transfers: u64,
+    first_block: Option<u64>,
+    last_block: Option<u64>,
+    /// How much the table files take up on disk.
+    db_size_bytes: u64,
+}
+
+async fn get_stats(store: web::Data<Arc<Store>>, health: web::Data<Arc<Health>>) -> Result<String> {
+    let skips = health.skip_stats();
+    let stats = Stats {
+        blocks_processed: skips.processed,
+        blocks_skipped: skips.skipped,
+        votes: store.estimated_votes()?,
+        transfers: store.estimated_transfers()?,
+        first_block: store.first_known_block().await,
+        last_block: store.last_known_block().await,
+        db_size_bytes: store.approx_size_bytes()?,
+    };
+    Ok(serde_json::to_string(&stats)?)
+}
+
+/// Count the request answered, by the route it took rather than by its path,
+/// so that every signature or account asked about does not make a series of its own.
+fn count_request<B>(metrics: &Metrics, response: &ServiceResponse<B>) {
+    let request = response.request();
+    let path = request
+        .match_pattern()
+        .unwrap_or_else(|| "<unmatched>".to_owned());
+    let labels = [request.method().as_str(), &path, response.status().as_str()];
+    metrics.api_requests.with_label_values(&labels).inc();
+}
+
+async fn get_metrics(metrics: web::Data<Arc<Metrics>>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(metrics.render()?))
+}
+
+async fn get_last_known_block(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+) -> Result<HttpResponse> {
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+    let body = freshness
+        .last_known_block()
+        .map_or_else(|| "null".to_owned(), |block| block.to_string());
+    Ok(freshness.respond(body))
+}
+
+async fn head_transaction(
+    store: web::Data<Arc<Store>>,
+    signature: web::Path<String>,
+) -> Result<HttpResponse> {
+    let signature = Signature::from_str(&signature)?;
+    Ok(match store.has_signature(&signature).await? {
+        true => HttpResponse::Ok().finish(),
+        false => HttpResponse::NotFound().finish(),
+    })
 }
 
 async fn get_votes(
+    request: HttpRequest,
     store: web::Data<Arc<Store>>,
-    web::Query(filters): web::Query<Criteria>,
-) -> Result<String> {
+    cap: web::Data<ResponseCap>,
+    web::Query(criteria): web::Query<Criteria>,
+) -> Result<HttpResponse> {
     use finding_votes::{
-        find_votes_with_block_index, find_votes_with_full_scan, find_votes_with_signature,
+        find_votes_in_block_range, find_votes_in_timestamp_range, find_votes_with_account_pair,
+        find_votes_with_block_index, find_votes_with_full_scan, find_votes_with_program,
+        find_votes_with_signature,
     };
 
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    if criteria.lamports.is_some() {
+        return Err(Error::BadQueryParam {
+            param: "lamports".into(),
+            reason: "votes carry no lamports".to_owned(),
+        });
+    }
+
+    let plan = QueryPlan::of(&criteria, Kind::Vote)?;
+    let log = QueryLog::of(Kind::Vote, &plan);
+    if let Some(paging) = Paging::of(&criteria, &plan)? {
+        let filters = &plan.filters;
+        let after = paging.after_key(store.get_ref(), Kind::Vote).await?;
+        let page = store
+            .find_votes_page(
+                after.as_deref(),
+                filters.blocks(),
+                paging.offset,
+                paging.limit,
+                |x| filters.admit_vote(x) && paging.admit_program(&x.program_id),
+            )
+            .await?;
+        log.finish(page.records.len());
+        let total = match paging.with_total {
+            true => total(counting::count_votes(store.get_ref(), plan.clone()).await)?,
+            false => None,
+        };
+        let page = PageBody::<PrettyVote>::of(page, total);
+        return Ok(freshness.respond(serde_json::to_string(&page)?));
+    }
+    let QueryPlan { strategy, filters } = plan;
+    let Filters {
+        block, to, from, ..
+    } = filters;
     let store = store.get_ref();
-    let votes = match (
-        &filters.signature,
-        &filters.block,
-        &filters.to,
-        &filters.from,
-    ) {
-        (Some(signature), None, None, None) => find_votes_with_signature(store, signature).await,
-        (None, Some(block), None, None) => find_votes_with_block_index(store, *block).await,
-        _ => {
-            let block_index = filters.block;
-            let to = filters.to.as_deref().map(Pubkey::from_str).transpose()?;
-            let from = filters.from.as_deref().map(Pubkey::from_str).transpose()?;
-            find_votes_with_full_scan(store, block_index, to, from).await
+    let mut votes = match strategy {
+        Strategy::Signatures(signatures) => store.find_votes(&signatures).await?,
+        Strategy::Program(program) => {
+            find_votes_with_program(store, &program, block, to, from).await?
+        }
+        // Only ever planned for the transfers.
+        Strategy::Destination(_) => unreachable!("votes have no destination index"),
+        Strategy::Pair(from, to) => find_votes_with_account_pair(store, &from, &to).await?,
+        Strategy::Signature(signature) => find_votes_with_signature(store, &signature).await?,
+        Strategy::Block(block) => find_votes_with_block_index(store, block, to, from).await?,
+        Strategy::Blocks(first, last) => {
+            find_votes_in_block_range(store, first, last, to, from).await?
+        }
+        Strategy::Timestamps(first, last) => {
+            find_votes_in_timestamp_range(store, first, last, to, from).await?
+        }
+        Strategy::FullScan => {
+            find_votes_with_full_scan(store, block, to, from, filters.sort).await?
         }
     };
-    let votes = votes?.into_iter().map(PrettyVote::from).collect::<Vec<_>>();
-    Ok(serde_json::to_string(&votes)?)
+    if filters.block_from.is_some() || filters.block_to.is_some() {
+        let blocks = filters.blocks();
+        votes.retain(|x| blocks.contains(&x.block_index));
+    }
+    if filters.by_timestamp() {
+        let timestamps = filters.timestamps();
+        votes.retain(|x| timestamps.contains(&x.timestamp));
+    }
+    if let Some(since) = filters.indexed_since {
+        votes.retain(|x| x.indexed_at.is_some_and(|at| at >= since));
+    }
+    if let Some(sort) = filters.sort {
+        sort_votes(&mut votes, sort);
+    }
+    log.finish(votes.len());
+    let total = cap.apply(&mut votes);
+    if CsvResponseBody::wanted(&request, &criteria) {
+        let file_name = criteria.file_name("votes", "csv");
+        let response = CsvResponseBody::of(&votes).respond(&freshness, &file_name);
+        return Ok(label_truncated(response, total));
+    }
+    if streaming::wants_lines(&request) {
+        let body = streaming::lines(votes.into_iter().map(PrettyVote::from));
+        let file_name = criteria.file_name("votes", "ndjson");
+        let response = as_attachment(freshness.stream(NDJSON, body), &file_name);
+        return Ok(label_truncated(response, total));
+    }
+    let votes = votes.into_iter().map(PrettyVote::from).collect::<Vec<_>>();
+    let response = freshness.respond(serde_json::to_string(&votes)?);
+    Ok(label_truncated(response, total))
 }
 
 async fn get_transfers(
+    request: HttpRequest,
     store: web::Data<Arc<Store>>,
-    web::Query(filters): web::Query<Criteria>,
-) -> Result<String> {
+    cap: web::Data<ResponseCap>,
+    web::Query(criteria): web::Query<Criteria>,
+) -> Result<HttpResponse> {
     use finding_transfers::{
-        find_transfers_with_block_index, find_transfers_with_full_scan,
-        find_transfers_with_signature,
+        find_transfers_in_block_range, find_transfers_in_timestamp_range,
+        find_transfers_with_block_index, find_transfers_with_destination,
+        find_transfers_with_full_scan, find_transfers_with_program, find_transfers_with_signature,
     };
 
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    // The amount gets checked in memory, whatever the way.
+    let plan = QueryPlan::of(&criteria, Kind::Transfer)?;
+    let log = QueryLog::of(Kind::Transfer, &plan);
+    if let Some(paging) = Paging::of(&criteria, &plan)? {
+        let filters = &plan.filters;
+        let after = paging.after_key(store.get_ref(), Kind::Transfer).await?;
+        let page = store
+            .find_transfers_page(
+                after.as_deref(),
+                filters.blocks(),
+                paging.offset,
+                paging.limit,
+                |x| filters.admit_transfer(x) && paging.admit_program(&x.program_id),
+            )
+            .await?;
+        log.finish(page.records.len());
+        let total = match paging.with_total {
+            true => total(counting::count_transfers(store.get_ref(), plan.clone()).await)?,
+            false => None,
+        };
+        let page = PageBody::<PrettyTransfer>::of(page, total);
+        return Ok(freshness.respond(serde_json::to_string(&page)?));
+    }
+    let QueryPlan { strategy, filters } = plan;
+    let Filters {
+        block, to, from, ..
+    } = filters;
     let store = store.get_ref();
-    let transfers = match (
-        &filters.signature,
-        &filters.block,
-        &filters.to,
-        &filters.from,
-    ) {
-        (Some(signature), None, None, None) => {
-            find_transfers_with_signature(store, signature).await
-        }
-        (None, Some(block), None, None) => find_transfers_with_block_index(store, *block).await,
-        _ => {
-            let block_index = filters.block;
-            let to = filters.to.as_deref().map(Pubkey::from_str).transpose()?;
-            let from = filters.from.as_deref().map(Pubkey::from_str).transpose()?;
-            find_transfers_with_full_scan(store, block_index, to, from).await
+    let mut transfers = match strategy {
+        Strategy::Signatures(signatures) => store.find_transfers(&signatures).await?,
+        Strategy::Program(program) => {
+            find_transfers_with_program(store, &program, block, to, from).await?
+        }
+        Strategy::Destination(to) => {
+            find_transfers_with_destination(store, &to, block, from).await?
+        }
+        // Only ever planned for the votes.
+        Strategy::Pair(..) => unreachable!("transfers are not planned by the pair"),
+        Strategy::Signature(signature) => find_transfers_with_signature(store, &signature).await?,
+        Strategy::Block(block) => find_transfers_with_block_index(store, block, to, from).await?,
+        Strategy::Blocks(first, last) => {
+            find_transfers_in_block_range(store, first, last, to, from).await?
+        }
+        Strategy::Timestamps(first, last) => {
+            find_transfers_in_timestamp_range(store, first, last, to, from).await?
+        }
+        Strategy::FullScan => {
+            find_transfers_with_full_scan(store, block, to, from, filters.sort).await?
         }
     };
-    let transfers = transfers?
+    if let Some(lamports) = filters.lamports {
+        transfers.retain(|x| x.lamports == lamports);
+    }
+    if filters.block_from.is_some() || filters.block_to.is_some() {
+        let blocks = filters.blocks();
+        transfers.retain(|x| blocks.contains(&x.block_index));
+    }
+    if filters.by_timestamp() {
+        let timestamps = filters.timestamps();
+        transfers.retain(|x| timestamps.contains(&x.timestamp));
+    }
+    if let Some(since) = filters.indexed_since {
+        transfers.retain(|x| x.indexed_at.is_some_and(|at| at >= since));
+    }
+    if let Some(sort) = filters.sort {
+        sort_transfers(&mut transfers, sort);
+    }
+    log.finish(transfers.len());
+    let total = cap.apply(&mut transfers);
+    if CsvResponseBody::wanted(&request, &criteria) {
+        let file_name = criteria.file_name("transfers", "csv");
+        let response = CsvResponseBody::of(&transfers).respond(&freshness, &file_name);
+        return Ok(label_truncated(response, total));
+    }
+    if streaming::wants_lines(&request) {
+        let body = streaming::lines(transfers.into_iter().map(PrettyTransfer::from));
+        let file_name = criteria.file_name("transfers", "ndjson");
+        let response = as_attachment(freshness.stream(NDJSON, body), &file_name);
+        return Ok(label_truncated(response, total));
+    }
+    let transfers = transfers
         .into_iter()
         .map(PrettyTransfer::from)
         .collect::<Vec<_>>();
+    let response = freshness.respond(serde_json::to_string(&transfers)?);
+    Ok(label_truncated(response, total))
+}
+
+/// The votes with any of the signatures, in the order asked, leaving out the ones not found.
+async fn post_votes_lookup(
+    store: web::Data<Arc<Store>>,
+    cap: web::Data<LookupCap>,
+    web::Json(lookup): web::Json<Lookup>,
+) -> Result<String> {
+    let signatures = lookup.signatures(**cap)?;
+    let found = join_all(signatures.iter().map(|x| store.find_vote(x))).await;
+    let mut votes = Vec::new();
+    for each in found {
+        votes.extend(each?.into_iter().map(PrettyVote::from));
+    }
+    Ok(serde_json::to_string(&votes)?)
+}
+
+/// The transfers with any of the signatures, in the order asked, leaving out the ones not found.
+async fn post_transfers_lookup(
+    store: web::Data<Arc<Store>>,
+    cap: web::Data<LookupCap>,
+    web::Json(lookup): web::Json<Lookup>,
+) -> Result<String> {
+    let signatures = lookup.signatures(**cap)?;
+    let found = join_all(signatures.iter().map(|x| store.find_transfer(x))).await;
+    let mut transfers = Vec::new();
+    for each in found {
+        transfers.extend(each?.into_iter().map(PrettyTransfer::from));
+    }
     Ok(serde_json::to_string(&transfers)?)
 }
 
-/// Run the server.
+async fn get_votes_count(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    web::Query(criteria): web::Query<Criteria>,
+) -> Result<HttpResponse> {
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    if criteria.lamports.is_some() {
+        return Err(Error::BadQueryParam {
+            param: "lamports".into(),
+            reason: "votes carry no lamports".to_owned(),
+        });
+    }
+
+    let plan = QueryPlan::of(&criteria, Kind::Vote)?;
+    let log = QueryLog::of(Kind::Vote, &plan);
+    let count = counting::count_votes(store.get_ref(), plan).await?;
+    log.finish(count);
+    let body = serde_json::json!({ "count": count });
+    Ok(freshness.respond(body.to_string()))
+}
+
+async fn get_transfers_count(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    web::Query(criteria): web::Query<Criteria>,
+) -> Result<HttpResponse> {
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    let plan = QueryPlan::of(&criteria, Kind::Transfer)?;
+    let log = QueryLog::of(Kind::Transfer, &plan);
+    let count = counting::count_transfers(store.get_ref(), plan).await?;
+    log.finish(count);
+    let body = serde_json::json!({ "count": count });
+    Ok(freshness.respond(body.to_string()))
+}
+
+async fn get_largest_transfers(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    web::Query(criteria): web::Query<LargestCriteria>,
+) -> Result<HttpResponse> {
+    use finding_transfers::find_largest_transfers;
+
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    let n = criteria.n();
+    let transfers =
+        find_largest_transfers(store.get_ref(), criteria.from_block, criteria.to_block, n).await?;
+    let transfers = transfers
+        .into_iter()
+        .map(PrettyTransfer::from)
+        .collect::<Vec<_>>();
+    Ok(freshness.respond(serde_json::to_string(&transfers)?))
+}
+
+async fn get_flow(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    web::Query(criteria): web::Query<FlowCriteria>,
+) -> Result<HttpResponse> {
+    use finding_flows::find_flow;
+
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    let edges = criteria.edges.unwrap_or(20);
+    let flow = find_flow(
+        store.get_ref(),
+        &criteria.account,
+        criteria.from_block,
+        criteria.to_block,
+        edges,
+    )
+    .await?;
+    Ok(freshness.respond(serde_json::to_string(&flow)?))
+}
+
+async fn get_recent_records(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    web::Query(criteria): web::Query<RecentCriteria>,
+) -> Result<HttpResponse> {
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    let limit = criteria.limit.unwrap_or(20);
+    let records = store.recent_records(limit).await?;
+    let records = records
+        .into_iter()
+        .map(PrettyRecord::from)
+        .collect::<Vec<_>>();
+    Ok(freshness.respond(serde_json::to_string(&records)?))
+}
+
+async fn get_changes(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    web::Query(criteria): web::Query<ChangesCriteria>,
+) -> Result<HttpResponse> {
+    use finding_changes::find_changes;
+
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    let limit = criteria.limit.unwrap_or(1000);
+    let changes = find_changes(store.get_ref(), criteria.since_block, limit).await?;
+    Ok(freshness.respond(serde_json::to_string(&changes)?))
+}
+
+async fn get_rewards(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    web::Query(criteria): web::Query<RewardCriteria>,
+) -> Result<HttpResponse> {
+    use finding_rewards::find_rewards;
+
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    let pubkey = parse_pubkey("pubkey", criteria.pubkey.as_deref())?;
+    let rewards = find_rewards(store.get_ref(), criteria.block, pubkey).await?;
+    let rewards = rewards
+        .into_iter()
+        .map(PrettyReward::from)
+        .collect::<Vec<_>>();
+    Ok(freshness.respond(serde_json::to_string(&rewards)?))
+}
+
+async fn get_stake_delegations(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    cap: web::Data<ResponseCap>,
+    web::Query(criteria): web::Query<Criteria>,
+) -> Result<HttpResponse> {
+    use finding_stake_delegations::find_stake_delegations;
+
+    criteria.check_bounds()?;
+    // What only the votes and the transfers can be looked up by, or carry.
+    let priority_fees = matches!(criteria.sort, Some(Sort::PriorityFeeDesc));
+    for (param, given) in [
+        ("signatures", criteria.signatures.is_some()),
+        ("program", criteria.program.is_some()),
+        ("lamports", criteria.lamports.is_some()),
+        ("after", criteria.after.is_some()),
+        ("offset", criteria.offset.is_some()),
+        ("limit", criteria.limit.is_some()),
+        ("sort", priority_fees),
+        ("format", criteria.format.is_some()),
+    ] {
+        if given {
+            return Err(Error::BadQueryParam {
+                param: param.into(),
+                reason: "not for the stake delegations".to_owned(),
+            });
+        }
+    }
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    // The delegations are few, so whatever the plan, they only go by the account or by the blocks.
+    let QueryPlan { filters, .. } = QueryPlan::of(&criteria, Kind::StakeDelegation)?;
+    let mut delegations = find_stake_delegations(store.get_ref(), &filters).await?;
+    if let Some(signature) = &criteria.signature {
+        let signature = Signature::from_str(signature)?;
+        delegations.retain(|x| x.signature == signature);
+    }
+    if let Some(sort) = filters.sort {
+        sort_stake_delegations(&mut delegations, sort);
+    }
+    let total = cap.apply(&mut delegations);
+    let delegations = delegations
+        .into_iter()
+        .map(PrettyStakeDelegation::from)
+        .collect::<Vec<_>>();
+    let response = freshness.respond(serde_json::to_string(&delegations)?);
+    Ok(label_truncated(response, total))
+}
+
+async fn get_token_transfers(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    web::Query(criteria): web::Query<TokenTransferCriteria>,
+) -> Result<HttpResponse> {
+    use finding_token_transfers::find_token_transfers;
+
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    let account = parse_pubkey("account", criteria.account.as_deref())?;
+    let transfers = find_token_transfers(store.get_ref(), criteria.block, account).await?;
+    let transfers = transfers
+        .into_iter()
+        .map(PrettyTokenTransfer::from)
+        .collect::<Vec<_>>();
+    Ok(freshness.respond(serde_json::to_string(&transfers)?))
+}
+
+async fn get_gaps(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    web::Query(criteria): web::Query<GapsCriteria>,
+) -> Result<HttpResponse> {
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    let GapsCriteria { from, to } = criteria;
+    let gaps = store.find_block_gaps(from, to).await?;
+    let body = serde_json::json!({ "from": from, "to": to, "gaps": gaps });
+    Ok(freshness.respond(body.to_string()))
+}
+
+async fn get_account_balance(
+    request: HttpRequest,
+    store: web::Data<Arc<Store>>,
+    account: web::Path<String>,
+    web::Query(criteria): web::Query<BalanceCriteria>,
+) -> Result<HttpResponse> {
+    use finding_balances::find_balance_delta;
+
+    criteria.check_bounds()?;
+    let freshness = Freshness::of(&request, store.get_ref()).await?;
+    if let Some(response) = freshness.not_modified() {
+        return Ok(response);
+    }
+
+    let balance = find_balance_delta(store.get_ref(), &account, criteria.at_block).await?;
+    Ok(freshness.respond(serde_json::to_string(&balance)?))
+}
+
+async fn post_checkpoint(
+    store: web::Data<Arc<Store>>,
+    dir: web::Data<CheckpointDir>,
+    web::Query(criteria): web::Query<CheckpointCriteria>,
+) -> Result<String> {
+    let path = dir.resolve(&criteria.out)?;
+    tracing::info!("Writing a checkpoint to `{}`...", path.display());
+    store.checkpoint(&path).await?;
+    Ok(serde_json::to_string(&path)?)
+}
+
+async fn post_reindex(
+    store: web::Data<Arc<Store>>,
+    reindexer: web::Data<reindexing::Reindexer>,
+    block: web::Path<u64>,
+) -> Result<String> {
+    use reindexing::reindex_block;
+
+    let block = block.into_inner();
+    tracing::info!("Reindexing block #{block}...");
+    let reindexed = reindex_block(store.get_ref(), reindexer.get_ref(), block).await?;
+    Ok(serde_json::to_string(&reindexed)?)
+}
+
+async fn delete_vote(
+    store: web::Data<Arc<Store>>,
+    signature: web::Path<String>,
+) -> Result<HttpResponse> {
+    let signature = Signature::from_str(&signature)?;
+    tracing::info!("Deleting the votes of `{signature}`...");
+    store.delete_vote(&signature).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn delete_transfer(
+    store: web::Data<Arc<Store>>,
+    signature: web::Path<String>,
+) -> Result<HttpResponse> {
+    let signature = Signature::from_str(&signature)?;
+    tracing::info!("Deleting the transfers of `{signature}`...");
+    store.delete_transfer(&signature).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn get_cf_stats(store: web::Data<Arc<Store>>) -> Result<String> {
+    let stats = store.cf_stats()?;
+    Ok(serde_json::to_string(&stats)?)
+}
+
+async fn get_raw(
+    store: web::Data<Arc<Store>>,
+    web::Query(criteria): web::Query<RawCriteria>,
+) -> Result<String> {
+    use debugging::find_raw_entries;
+
+    let entries = find_raw_entries(store.get_ref(), &criteria.cf, &criteria.key).await?;
+    Ok(serde_json::to_string(&entries)?)
+}
+
+/// What every worker of the web interface is handed.
+#[derive(Clone)]
+struct Shared {
+    store: Arc<Store>,
+    health: Arc<Health>,
+    metrics: Arc<Metrics>,
+    reindexer: reindexing::Reindexer,
+}
+
+/// The web interface as each worker serves it, with the middleware and the routes.
+fn app(
+    shared: Shared,
+    settings: Settings,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    let Shared {
+        store,
+        health,
+        metrics,
+        reindexer,
+    } = shared;
+    let counted = metrics.clone();
+    let with_cors = !settings.cors_origins.is_empty();
+    App::new()
+        .wrap(Condition::new(settings.compress, Compress::default()))
+        .wrap(Condition::new(with_cors, cors(&settings.cors_origins)))
+        .wrap(Logger::default())
+        .wrap_fn(move |request, service| {
+            let metrics = counted.clone();
+            let response = service.call(request);
+            async move {
+                let response = response.await?;
+                count_request(&metrics, &response);
+                Ok(response)
+            }
+        })
+        .app_data(web::Data::new(store))
+        .app_data(web::Data::new(health))
+        .app_data(web::Data::new(metrics))
+        .app_data(web::Data::new(reindexer))
+        .app_data(web::Data::new(ResponseCap(settings.max_response_records)))
+        .app_data(web::Data::new(LookupCap(settings.max_lookup_signatures)))
+        .route("/", web::get().to(index))
+        .route("/readyz", web::get().to(get_readiness))
+        .route("/health", web::get().to(get_health))
+        .route("/metrics", web::get().to(get_metrics))
+        .route("/progress", web::get().to(get_progress))
+        .route("/stats", web::get().to(get_stats))
+        .route("/blockheight", web::get().to(get_last_known_block))
+        .route(
+            "/transactions/{signature}",
+            web::head().to(head_transaction),
+        )
+        .service(
+            web::resource("/votes")
+                .wrap(Etag)
+                .app_data(query_config::<Criteria>())
+                .route(web::get().to(get_votes)),
+        )
+        .service(
+            web::resource("/votes/lookup")
+                .app_data(json_config())
+                .route(web::post().to(post_votes_lookup)),
+        )
+        .service(
+            web::resource("/votes/count")
+                .app_data(query_config::<Criteria>())
+                .route(web::get().to(get_votes_count)),
+        )
+        .service(
+            web::resource("/transfers")
+                .wrap(Etag)
+                .app_data(query_config::<Criteria>())
+                .route(web::get().to(get_transfers)),
+        )
+        .service(
+            web::resource("/transfers/lookup")
+                .app_data(json_config())
+                .route(web::post().to(post_transfers_lookup)),
+        )
+        .service(
+            web::resource("/transfers/count")
+                .app_data(query_config::<Criteria>())
+                .route(web::get().to(get_transfers_count)),
+        )
+        .service(
+            web::resource("/transfers/largest")
+                .wrap(Etag)
+                .app_data(query_config::<LargestCriteria>())
+                .route(web::get().to(get_largest_transfers)),
+        )
+        .service(
+            web::resource("/transfers/flow")
+                .app_data(query_config::<FlowCriteria>())
+                .route(web::get().to(get_flow)),
+        )
+        .service(
+            web::resource("/recent")
+                .wrap(Etag)
+                .app_data(query_config::<RecentCriteria>())
+                .route(web::get().to(get_recent_records)),
+        )
+        .service(
+            web::resource("/changes")
+                .wrap(Etag)
+                .app_data(query_config::<ChangesCriteria>())
+                .route(web::get().to(get_changes)),
+        )
+        .service(
+            web::resource("/rewards")
+                .wrap(Etag)
+                .app_data(query_config::<RewardCriteria>())
+                .route(web::get().to(get_rewards)),
+        )
+        .service(
+            web::resource("/stake-delegations")
+                .wrap(Etag)
+                .app_data(query_config::<Criteria>())
+                .route(web::get().to(get_stake_delegations)),
+        )
+        .service(
+            web::resource("/token-transfers")
+                .wrap(Etag)
+                .app_data(query_config::<TokenTransferCriteria>())
+                .route(web::get().to(get_token_transfers)),
+        )
+        .service(
+            web::resource("/gaps")
+                .app_data(query_config::<GapsCriteria>())
+                .route(web::get().to(get_gaps)),
+        )
+        .service(
+            web::resource("/account/{pubkey}/balance")
+                .app_data(query_config::<BalanceCriteria>())
+                .route(web::get().to(get_account_balance)),
+        )
+        .configure(move |cfg| {
+            if let (true, Some(dir)) = (settings.admin, settings.checkpoint_dir) {
+                cfg.service(
+                    web::resource("/admin/checkpoint")
+                        .app_data(web::Data::new(CheckpointDir(dir)))
+                        .app_data(query_config::<CheckpointCriteria>())
+                        .route(web::post().to(post_checkpoint)),
+                );
+            }
+            if settings.admin {
+                cfg.route("/admin/reindex/{block}", web::post().to(post_reindex));
+                cfg.route("/votes/{signature}", web::delete().to(delete_vote));
+                cfg.route("/transfers/{signature}", web::delete().to(delete_transfer));
+            }
+            if settings.debug_endpoints {
+                cfg.service(
+                    web::resource("/debug/raw")
+                        .app_data(query_config::<RawCriteria>())
+                        .route(web::get().to(get_raw)),
+                );
+                cfg.route("/debug/stats", web::get().to(get_cf_stats));
+            }
+        })
+}
+
+/// Run the server until stopped.
 pub async fn serve_forever<Address>(
     address: Address,
     store: Arc<Store>,
-    _stop: CancellationToken,
+    health: Arc<Health>,
+    metrics: Arc<Metrics>,
+    connection: Connection,
+    settings: Settings,
+    stop: CancellationToken,
 ) -> Result<()>
 where
     Address: ToSocketAddrs + Debug,
 {
-    tracing::info!("Starting web server on {address:?}...");
-    HttpServer::new(move || {
-        App::new()
-            .wrap(Logger::default())
-            .app_data(web::Data::new(store.clone()))
-            .route("/", web::get().to(index))
-            .route("/blockheight", web::get().to(get_last_known_block))
-            .route("/votes", web::get().to(get_votes))
-            .route("/transfers", web::get().to(get_transfers))
-    })
-    .bind(address)?
-    .run()
-    .await?;
+    let workers = settings.workers.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
+    let backlog = settings.backlog.unwrap_or(2048);
+
+    tracing::info!(
+        "Starting web server on {address:?} with {workers} workers and a backlog of {backlog}..."
+    );
+    let ready_file = settings.ready_file.clone();
+    let reindexer = reindexing::Reindexer {
+        connection,
+        metrics: metrics.clone(),
+        kinds: settings.kinds.clone(),
+        clock_skew: settings.clock_skew,
+        runtime: tokio::runtime::Handle::current(),
+    };
+    let shared = Shared {
+        store,
+        health,
+        metrics,
+        reindexer,
+    };
+    let server = HttpServer::new(move || app(shared.clone(), settings.clone()))
+        .workers(workers)
+        .backlog(backlog)
+        // Leaving the signals to the caller, so that it can stop everything else along with the server:
+        .disable_signals()
+        .bind(address)?;
+
+    // Only now that the addresses are taken, so that a supervisor waiting for the file
+    // never finds it before the server can be reached:
+    if let Some(ref path) = ready_file {
+        let addresses = server.addrs().iter().map(ToString::to_string);
+        let contents = addresses.collect::<Vec<_>>().join("\n") + "\n";
+        std::fs::write(path, contents)?;
+        tracing::info!("Written the ready file `{}`", path.display());
+    }
+
+    let server = server.run();
+    let handle = server.handle();
+    tokio::spawn(async move {
+        stop.cancelled().await;
+        handle.stop(true).await;
+    });
+    let served = server.await;
+
+    if let Some(ref path) = ready_file {
+        if let Err(e) = std::fs::remove_file(path) {
+            tracing::warn!("Failed to remove the ready file `{}`: {e}", path.display());
+        }
+    }
+    served?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use actix_web::http::{header, Method, StatusCode};
+    use actix_web::test;
+
+    use crate::record::fixtures;
+    use crate::store::PrimaryKey;
+
+    /// What the workers are handed, with a node to reindex from that is never asked.
+    fn shared(store: Arc<Store>, metrics: Arc<Metrics>) -> Shared {
+        let connection = Connection {
+            urls: vec![],
+            quarantine: Duration::ZERO,
+            timeout: Duration::ZERO,
+            headers: vec![],
+            user_agent: "surf/test".to_owned(),
+            token: None,
+            ca_cert: None,
+            insecure: false,
+        };
+        let reindexer = reindexing::Reindexer {
+            connection,
+            metrics: metrics.clone(),
+            kinds: vec![],
+            clock_skew: ClockSkew::default(),
+            runtime: tokio::runtime::Handle::current(),
+        };
+        Shared {
+            store,
+            health: Arc::new(Health::default()),
+            metrics,
+            reindexer,
+        }
+    }
+
+    #[actix_web::test]
+    async fn large_responses_compressed() {
+        // Given a store with plenty of transfers, served with the compression on:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        for block_index in 0..100 {
+            let transfer = fixtures::transfer(block_index, 0);
+            store.save_transfer(&transfer).await.unwrap();
+        }
+        let settings = Settings {
+            compress: true,
+            max_response_records: 1000,
+            ..Settings::default()
+        };
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let app = test::init_service(app(shared(store, metrics), settings)).await;
+
+        // When a client accepting gzip asks for all of them:
+        let request = test::TestRequest::get()
+            .uri("/transfers")
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        // Then they should come gzipped:
+        assert!(response.status().is_success());
+        let encoding = response.headers().get(header::CONTENT_ENCODING).unwrap();
+        assert_eq!(encoding, "gzip");
+
+        // And they should be the same as for a client accepting nothing of the sort:
+        let gzipped = test::read_body(response).await;
+        let mut unzipped = Vec::new();
+        let mut decoder = flate2::read::GzDecoder::new(&gzipped[..]);
+        std::io::Read::read_to_end(&mut decoder, &mut unzipped).unwrap();
+        let request = test::TestRequest::get().uri("/transfers").to_request();
+        let plain = test::call_and_read_body(&app, request).await;
+        assert_eq!(unzipped, plain);
+    }
+
+    #[actix_web::test]
+    async fn records_streamed_one_per_line() {
+        // Given a store with a few transfers:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        for block_index in 0..3 {
+            let transfer = fixtures::transfer(block_index, 0);
+            store.save_transfer(&transfer).await.unwrap();
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(ResponseCap(1000)))
+                .route("/transfers", web::get().to(get_transfers)),
+        )
+        .await;
+
+        // When a client asks for them one per line:
+        let request = test::TestRequest::get()
+            .uri("/transfers?block_from=0&block_to=2")
+            .insert_header((header::ACCEPT, NDJSON))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        // Then they should come as such, to be saved under a name telling the blocks:
+        assert!(response.status().is_success());
+        let content_type = response.headers().get(header::CONTENT_TYPE).unwrap();
+        assert_eq!(content_type, NDJSON);
+        let disposition = response.headers().get(header::CONTENT_DISPOSITION).unwrap();
+        assert_eq!(disposition, "attachment; filename=\"transfers-0-2.ndjson\"");
+        let body = test::read_body(response).await;
+        let lines: Vec<serde_json::Value> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        // And they should be the same as those in the array given to everyone else:
+        let request = test::TestRequest::get().uri("/transfers").to_request();
+        let array: Vec<serde_json::Value> = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(lines, array);
+    }
+
+    #[test]
+    fn file_names_tell_the_filters() {
+        // Given the criteria narrowing the records down in a few ways:
+        let account = Pubkey::new_unique();
+        let criteria = |query: &str| web::Query::<Criteria>::from_query(query).unwrap();
+
+        // When we name the files to save them under:
+        let named = |query: &str| criteria(query).file_name("transfers", "csv");
+
+        // Then the names should tell the blocks and the accounts:
+        assert_eq!(named(""), "transfers.csv");
+        assert_eq!(named("block=777"), "transfers-777.csv");
+        assert_eq!(
+            named("block_from=777&block_to=790"),
+            "transfers-777-790.csv"
+        );
+        assert_eq!(named("block_from=777"), "transfers-777-latest.csv");
+        assert_eq!(
+            named(&format!("block_to=790&to={account}")),
+            format!("transfers-0-790-to-{account}.csv")
+        );
+    }
+
+    #[actix_web::test]
+    async fn votes_looked_up_in_one_go() {
+        // Given a store with a vote:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        let vote = fixtures::vote(777, 0);
+        store.save_vote(&vote).await.unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(LookupCap(2)))
+                .service(
+                    web::resource("/votes/lookup")
+                        .app_data(json_config())
+                        .route(web::post().to(post_votes_lookup)),
+                ),
+        )
+        .await;
+
+        // When looking it up along with a signature of nothing:
+        let missing = Signature::new_unique();
+        let body = serde_json::json!({
+            "signatures": [vote.signature.to_string(), missing.to_string()],
+        });
+        let request = test::TestRequest::post()
+            .uri("/votes/lookup")
+            .set_json(&body)
+            .to_request();
+        let found: Vec<PrettyVote> = test::call_and_read_body_json(&app, request).await;
+
+        // Then only the vote should come back:
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].signature, vote.signature.to_string());
+
+        // When looking up more than may be at once:
+        let body = serde_json::json!({
+            "signatures": [missing.to_string(), missing.to_string(), missing.to_string()],
+        });
+        let request = test::TestRequest::post()
+            .uri("/votes/lookup")
+            .set_json(&body)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        // Then the lookup should be refused:
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn totals_counted_when_asked() {
+        // Given a store with a couple of votes:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        for block_index in [777, 778] {
+            let vote = fixtures::vote(block_index, 0);
+            store.save_vote(&vote).await.unwrap();
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(ResponseCap(1000)))
+                .route("/votes", web::get().to(get_votes)),
+        )
+        .await;
+
+        // When a client asks for a page, with the total and without:
+        let request = test::TestRequest::get().uri("/votes?limit=1").to_request();
+        let without: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        let request = test::TestRequest::get()
+            .uri("/votes?limit=1&with_total=true")
+            .to_request();
+        let with: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+
+        // Then only the one asking should get it counted:
+        assert_eq!(without["total"], serde_json::Value::Null);
+        assert_eq!(with["total"], 2);
+        assert_eq!(with["records"], without["records"]);
+    }
+
+    #[actix_web::test]
+    async fn votes_saved_as_csv() {
+        // Given a store with a vote:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        let vote = fixtures::vote(777, 0);
+        store.save_vote(&vote).await.unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(ResponseCap(1000)))
+                .route("/votes", web::get().to(get_votes)),
+        )
+        .await;
+
+        // When a client asks for them as CSV:
+        let request = test::TestRequest::get()
+            .uri("/votes?format=csv")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        // Then they should come as a file to save:
+        assert!(response.status().is_success());
+        let headers = response.headers();
+        assert_eq!(
+            headers.get(header::CONTENT_TYPE).unwrap(),
+            CsvResponseBody::CONTENT_TYPE
+        );
+        let disposition = headers.get(header::CONTENT_DISPOSITION).unwrap();
+        assert_eq!(disposition, "attachment; filename=\"votes.csv\"");
+
+        // And the vote should be there under the header:
+        let body = test::read_body(response).await;
+        let expected = format!(
+            "signature,block,timestamp,author,target\r\n{},777,1234567890,{},{}\r\n",
+            vote.signature, vote.author, vote.target,
+        );
+        assert_eq!(body, expected);
+    }
+
+    #[actix_web::test]
+    async fn health_degraded_once_extraction_gives_up() {
+        // Given a store with some progress, and the extraction going:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        store.set_last_known_block(777).await.unwrap();
+        let health = Arc::new(Health::default());
+        health.beat();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(health.clone()))
+                .route("/health", web::get().to(get_health)),
+        )
+        .await;
+
+        // When asked about the health, then it should be fine:
+        let request = test::TestRequest::get().uri("/health").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(
+            body,
+            serde_json::json!({"status": "ok", "last_known_block": 777})
+        );
+
+        // And when the extraction gives up, then it should be degraded, telling why:
+        health.extraction_failed("extraction gave up".to_owned());
+        let request = test::TestRequest::get().uri("/health").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["status"], "degraded");
+        assert_eq!(body["reason"], "extraction gave up");
+    }
+
+    #[test]
+    fn checkpoints_kept_within_dir() {
+        // Given the directory the checkpoints go into:
+        let dir = CheckpointDir(PathBuf::from("/var/surf/checkpoints"));
+
+        // When given a plain name, then the checkpoint should go right under it:
+        let path = dir.resolve("2026-10-16").unwrap();
+        assert_eq!(path, PathBuf::from("/var/surf/checkpoints/2026-10-16"));
+
+        // When given anything reaching out of it, then it should be refused:
+        for name in ["", ".", "..", "../etc", "/tmp/x", "a/b", "a\\b", "x..y"] {
+            assert!(dir.resolve(name).is_err(), "{name:?} let through");
+        }
+    }
+
+    #[actix_web::test]
+    async fn cors_origins_let_in() {
+        // Given the web interface letting in one origin, and another one letting in none:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let settings = |cors_origins| Settings {
+            cors_origins,
+            ..Settings::default()
+        };
+        let origins = vec!["https://app.example.com".to_owned()];
+        let open = app(shared(store.clone(), metrics.clone()), settings(origins));
+        let open = test::init_service(open).await;
+        let strict = app(shared(store, metrics), settings(vec![]));
+        let strict = test::init_service(strict).await;
+
+        // When a browser calls in from that origin:
+        let request = || {
+            test::TestRequest::get()
+                .uri("/stats")
+                .insert_header((header::ORIGIN, "https://app.example.com"))
+                .to_request()
+        };
+        let response = test::call_service(&open, request()).await;
+
+        // Then it should be let in:
+        let allowed = response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN);
+        assert_eq!(allowed.unwrap(), "https://app.example.com");
+
+        // And without any origins given, there should be no word of CORS:
+        let response = test::call_service(&strict, request()).await;
+        assert!(response.status().is_success());
+        let allowed = response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN);
+        assert!(allowed.is_none());
+    }
+
+    #[actix_web::test]
+    async fn unservable_formats_refused() {
+        // Given the web interface:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let app = app(shared(store, metrics), Settings::default());
+        let app = test::init_service(app).await;
+
+        // When asked for CSV by the page, or for the stake delegations in any format:
+        for uri in [
+            "/transfers?format=csv&limit=10",
+            "/votes?format=csv&offset=0",
+            "/stake-delegations?format=csv",
+            "/stake-delegations?format=json",
+        ] {
+            let request = test::TestRequest::get().uri(uri).to_request();
+            let response = test::call_service(&app, request).await;
+
+            // Then it should be refused rather than answered in JSON all the same:
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn requests_counted_by_route() {
+        // Given the web interface, with the requests counted:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let app = app(shared(store, metrics.clone()), Settings::default());
+        let app = test::init_service(app).await;
+
+        // When asked for them twice, and for something else once:
+        for uri in ["/stats", "/stats", "/nothing"] {
+            let request = test::TestRequest::get().uri(uri).to_request();
+            test::call_service(&app, request).await;
+        }
+
+        // Then each should be counted by its route and status:
+        let rendered = metrics.render().unwrap();
+        let stats = r#"surf_api_requests_total{method="GET",path="/stats",status="200"} 2"#;
+        let nothing = r#"surf_api_requests_total{method="GET",path="<unmatched>",status="404"} 1"#;
+        assert!(rendered.contains(stats), "{rendered}");
+        assert!(rendered.contains(nothing), "{rendered}");
+    }
+
+    #[actix_web::test]
+    async fn skipped_blocks_counted() {
+        // Given the extraction through a few blocks, two of them skipped by the cluster:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        store.set_last_known_block(779).await.unwrap();
+        let health = Arc::new(Health::default());
+        for skipped in [false, true, true, false] {
+            health.block_processed(skipped);
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(health))
+                .route("/stats", web::get().to(get_stats)),
+        )
+        .await;
+
+        // When asked for the stats:
+        let request = test::TestRequest::get().uri("/stats").to_request();
+        let stats: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+
+        // Then they should tell them apart:
+        let expected = serde_json::json!({
+            "blocks_processed": 4,
+            "blocks_skipped": 2,
+            "votes": 0,
+            "transfers": 0,
+            "first_block": null,
+            "last_block": 779,
+            "db_size_bytes": 0,
+        });
+        assert_eq!(stats, expected);
+    }
+
+    #[actix_web::test]
+    async fn indexed_signatures_told() {
+        // Given a store with a transfer, keyed by the occurrence:
+        let store = Store::disposable().await.unwrap();
+        let store = Arc::new(store.with_primary_key(PrimaryKey::Occurrence).unwrap());
+        let transfer = fixtures::transfer(777, 0);
+        store.save_transfer(&transfer).await.unwrap();
+        let app = test::init_service(App::new().app_data(web::Data::new(store)).route(
+            "/transactions/{signature}",
+            web::head().to(head_transaction),
+        ))
+        .await;
+
+        // When asked about its signature, another one, and something else altogether,
+        // then it should be found, not found, and refused, in that order:
+        let cases = [
+            (transfer.signature.to_string(), StatusCode::OK),
+            (Signature::new_unique().to_string(), StatusCode::NOT_FOUND),
+            ("x".to_owned(), StatusCode::BAD_REQUEST),
+        ];
+        for (signature, expected) in cases {
+            let request = test::TestRequest::default()
+                .method(Method::HEAD)
+                .uri(&format!("/transactions/{signature}"))
+                .to_request();
+            let response = test::call_service(&app, request).await;
+            assert_eq!(response.status(), expected, "{signature}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn oversized_responses_truncated() {
+        // Given a store with more transfers than a response may carry:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        for block_index in 0..10 {
+            let transfer = fixtures::transfer(block_index, 0);
+            store.save_transfer(&transfer).await.unwrap();
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(ResponseCap(3)))
+                .route("/transfers", web::get().to(get_transfers)),
+        )
+        .await;
+
+        // When a client asks for all of them:
+        let request = test::TestRequest::get().uri("/transfers").to_request();
+        let response = test::call_service(&app, request).await;
+
+        // Then only as many as allowed should come, along with how many there were:
+        assert_eq!(response.headers().get(TRUNCATED).unwrap(), "true");
+        assert_eq!(response.headers().get(TOTAL_MATCHED).unwrap(), "10");
+        let transfers: Vec<serde_json::Value> = test::read_body_json(response).await;
+        assert_eq!(transfers.len(), 3);
+
+        // And a request within the cap should not be labeled:
+        let request = test::TestRequest::get()
+            .uri("/transfers?block=7")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.headers().get(TRUNCATED).is_none());
+    }
+
+    #[actix_web::test]
+    async fn sorted_as_asked() {
+        // Given a store with transfers of different amounts at different times:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        for (block_index, lamports) in [(1, 300), (2, 100), (3, 200)] {
+            let transfer = Transfer {
+                timestamp: 1234567890 + block_index,
+                lamports,
+                ..fixtures::transfer(block_index, 0)
+            };
+            store.save_transfer(&transfer).await.unwrap();
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(ResponseCap(100)))
+                .route("/votes", web::get().to(get_votes))
+                .route("/transfers", web::get().to(get_transfers)),
+        )
+        .await;
+
+        // When a client asks for them in either order, then they should come that way:
+        for (sort, expected) in [
+            ("lamports_asc", [100, 200, 300]),
+            ("lamports_desc", [300, 200, 100]),
+            ("timestamp_desc", [200, 100, 300]),
+            ("block_desc", [200, 100, 300]),
+        ] {
+            let uri = format!("/transfers?sort={sort}");
+            let request = test::TestRequest::get().uri(&uri).to_request();
+            let transfers: Vec<PrettyTransfer> = test::call_and_read_body_json(&app, request).await;
+            let lamports: Vec<_> = transfers.iter().map(|x| x.lamports).collect();
+            assert_eq!(lamports, expected, "{sort}");
+        }
+
+        // And the votes should not be sorted by the amount they lack:
+        let request = test::TestRequest::get()
+            .uri("/votes?sort=lamports_asc")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn stake_delegations_filtered() {
+        // Given delegations to two validators:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        let (validator, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        for (block_index, vote_account) in [(777, validator), (778, other), (779, validator)] {
+            let delegation = StakeDelegation {
+                signature: Signature::new_unique(),
+                block_index,
+                instruction_index: 0,
+                timestamp: 1234567890,
+                stake_account: Pubkey::new_unique(),
+                vote_account,
+                authority: Pubkey::new_unique(),
+                indexed_at: None,
+            };
+            let records = [crate::record::Record::StakeDelegation(delegation)];
+            store.save_block(&records).await.unwrap();
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(ResponseCap(100)))
+                .route("/stake-delegations", web::get().to(get_stake_delegations)),
+        )
+        .await;
+
+        // When a client asks for the ones to a validator, the latest first:
+        let uri = format!("/stake-delegations?to={validator}&sort=block_desc");
+        let request = test::TestRequest::get().uri(&uri).to_request();
+        let delegations: Vec<PrettyStakeDelegation> =
+            test::call_and_read_body_json(&app, request).await;
+
+        // Then only those should come, in that order:
+        let blocks: Vec<_> = delegations.iter().map(|x| x.block).collect();
+        assert_eq!(blocks, [779, 777]);
+
+        // And the filters only the transfers have should be turned away:
+        let request = test::TestRequest::get()
+            .uri("/stake-delegations?lamports=100")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,278 @@
+//! How far the numbers in a query may go, checked before any store work,
+//! so that no query string can make the service allocate or scan without end.
+
+use std::fmt::Display;
+
+use super::{
+    BalanceCriteria, ChangesCriteria, Criteria, FlowCriteria, GapsCriteria, LargestCriteria,
+    RecentCriteria, RewardCriteria, TokenTransferCriteria,
+};
+use crate::result::Error;
+use crate::Result;
+
+/// The greatest block one may ask about; far past any there is, yet exact as a JSON number anywhere.
+pub const MAX_BLOCK: u64 = 1 << 53;
+
+/// The greatest amount one may ask about; a billion SOL, more than there is.
+pub const MAX_LAMPORTS: u64 = 1_000_000_000 * 1_000_000_000;
+
+/// The greatest time of writing down one may ask about; the end of the year 9999.
+pub const MAX_TIMESTAMP: u64 = 253_402_300_799;
+
+/// How many of the newest records one request may ask for at most.
+pub const MAX_RECENT: usize = 1000;
+
+/// How many records one pull of the changes may ask for at most, give or take a block.
+pub const MAX_CHANGES: usize = 10_000;
+
+/// How many records one page may carry at most.
+pub const MAX_PAGE: usize = 10_000;
+
+/// How many records a page may pass over; each of them gets read all the same.
+pub const MAX_OFFSET: usize = 1_000_000;
+
+/// How many records a page carries unless told otherwise.
+pub const DEFAULT_PAGE: usize = 100;
+
+/// How many blocks one request may look for the gaps in, about an epoch.
+pub const MAX_GAPS_SPAN: u64 = 432_000;
+
+/// How many transfers a top-N query may return.
+pub const MAX_LARGEST: usize = 1000;
+
+/// How many blocks a top-N query may span; about an epoch.
+pub const MAX_LARGEST_SPAN: u64 = 432_000;
+
+/// How many counterparties a flow query may return each way.
+pub const MAX_EDGES: usize = 1000;
+
+/// Criteria to be checked as soon as parsed.
+pub trait Bounded {
+    /// Reject the first number out of bounds, naming its parameter.
+    fn check_bounds(&self) -> Result<()>;
+}
+
+/// Reject the value if it is past the greatest one allowed.
+fn at_most<T: PartialOrd + Display>(param: &'static str, value: Option<T>, max: T) -> Result<()> {
+    match value {
+        Some(value) if value > max => Err(Error::BadQueryParam {
+            param: param.into(),
+            reason: format!("at most {max}"),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Reject a range going backwards, or spanning `max_span` blocks or more.
+fn block_range(
+    names: (&'static str, &'static str),
+    from: u64,
+    to: u64,
+    max_span: u64,
+) -> Result<()> {
+    let (from_param, to_param) = names;
+    match to.checked_sub(from) {
+        Some(span) if span < max_span => Ok(()),
+        _ => Err(Error::BadQueryParam {
+            param: to_param.into(),
+            reason: format!("must not precede `{from_param}`, nor be {max_span} blocks past it"),
+        }),
+    }
+}
+
+impl Bounded for Criteria {
+    fn check_bounds(&self) -> Result<()> {
+        at_most("block", self.block, MAX_BLOCK)?;
+        at_most("block_from", self.block_from, MAX_BLOCK)?;
+        at_most("block_to", self.block_to, MAX_BLOCK)?;
+        if let (Some(from), Some(to)) = (self.block_from, self.block_to) {
+            // Any span, as the index only yields the blocks there are.
+            if to < from {
+                return Err(Error::BadQueryParam {
+                    param: "block_to".into(),
+                    reason: "must not precede `block_from`".to_owned(),
+                });
+            }
+        }
+        at_most("ts_from", self.ts_from, MAX_TIMESTAMP)?;
+        at_most("ts_to", self.ts_to, MAX_TIMESTAMP)?;
+        if let (Some(from), Some(to)) = (self.ts_from, self.ts_to) {
+            if to < from {
+                return Err(Error::BadQueryParam {
+                    param: "ts_to".into(),
+                    reason: "must not precede `ts_from`".to_owned(),
+                });
+            }
+        }
+        at_most("lamports", self.lamports, MAX_LAMPORTS)?;
+        at_most("indexed_since", self.indexed_since, MAX_TIMESTAMP)?;
+        at_most("offset", self.offset, MAX_OFFSET)?;
+        at_most("limit", self.limit, MAX_PAGE)
+    }
+}
+
+impl Bounded for LargestCriteria {
+    fn check_bounds(&self) -> Result<()> {
+        at_most("from_block", Some(self.from_block), MAX_BLOCK)?;
+        at_most("to_block", Some(self.to_block), MAX_BLOCK)?;
+        let names = ("from_block", "to_block");
+        block_range(names, self.from_block, self.to_block, MAX_LARGEST_SPAN)
+    }
+}
+
+impl LargestCriteria {
+    /// How many transfers to rank, capped rather than rejected.
+    pub fn n(&self) -> usize {
+        self.n.unwrap_or(10).min(MAX_LARGEST)
+    }
+}
+
+impl Bounded for GapsCriteria {
+    fn check_bounds(&self) -> Result<()> {
+        at_most("from", Some(self.from), MAX_BLOCK)?;
+        at_most("to", Some(self.to), MAX_BLOCK)?;
+        block_range(("from", "to"), self.from, self.to, MAX_GAPS_SPAN)
+    }
+}
+
+impl Bounded for FlowCriteria {
+    fn check_bounds(&self) -> Result<()> {
+        at_most("from_block", self.from_block, MAX_BLOCK)?;
+        at_most("to_block", self.to_block, MAX_BLOCK)?;
+        at_most("edges", self.edges, MAX_EDGES)?;
+        // Any span, as the transfers of the account get capped anyway.
+        match (self.from_block, self.to_block) {
+            (Some(from), Some(to)) if to < from => Err(Error::BadQueryParam {
+                param: "to_block".into(),
+                reason: "must not precede `from_block`".to_owned(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Bounded for RecentCriteria {
+    fn check_bounds(&self) -> Result<()> {
+        at_most("limit", self.limit, MAX_RECENT)
+    }
+}
+
+impl Bounded for ChangesCriteria {
+    fn check_bounds(&self) -> Result<()> {
+        at_most("since_block", Some(self.since_block), MAX_BLOCK)?;
+        at_most("limit", self.limit, MAX_CHANGES)
+    }
+}
+
+impl Bounded for RewardCriteria {
+    fn check_bounds(&self) -> Result<()> {
+        at_most("block", self.block, MAX_BLOCK)
+    }
+}
+
+impl Bounded for TokenTransferCriteria {
+    fn check_bounds(&self) -> Result<()> {
+        at_most("block", self.block, MAX_BLOCK)
+    }
+}
+
+impl Bounded for BalanceCriteria {
+    fn check_bounds(&self) -> Result<()> {
+        at_most("at_block", self.at_block, MAX_BLOCK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::web;
+    use serde::de::DeserializeOwned;
+
+    /// Parse the query string as actix would, then check it.
+    fn check<T: DeserializeOwned + Bounded>(query: &str) -> Result<()> {
+        let web::Query(criteria) = web::Query::<T>::from_query(query).unwrap();
+        criteria.check_bounds()
+    }
+
+    /// The parameter a query got rejected for.
+    fn rejected<T: DeserializeOwned + Bounded>(query: &str) -> String {
+        match check::<T>(query) {
+            Err(Error::BadQueryParam { param, .. }) => param.into_owned(),
+            other => panic!("`{query}` should have been rejected, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn adversarial_numbers_rejected() {
+        // Given the greatest numbers the query strings may carry:
+        let max = u64::MAX;
+
+        // When they get checked, then each should be rejected by its own name:
+        assert_eq!(rejected::<Criteria>(&format!("block={max}")), "block");
+        assert_eq!(rejected::<Criteria>(&format!("lamports={max}")), "lamports");
+        assert_eq!(
+            rejected::<Criteria>(&format!("indexed_since={max}")),
+            "indexed_since"
+        );
+        assert_eq!(rejected::<Criteria>(&format!("limit={max}")), "limit");
+        assert_eq!(rejected::<Criteria>("block_from=10&block_to=0"), "block_to");
+        assert_eq!(rejected::<Criteria>("ts_from=10&ts_to=0"), "ts_to");
+        assert_eq!(rejected::<Criteria>(&format!("offset={max}")), "offset");
+        assert_eq!(rejected::<RecentCriteria>(&format!("limit={max}")), "limit");
+        assert_eq!(
+            rejected::<FlowCriteria>(&format!("account=x&edges={max}")),
+            "edges"
+        );
+        assert_eq!(rejected::<RewardCriteria>(&format!("block={max}")), "block");
+        assert_eq!(
+            rejected::<TokenTransferCriteria>(&format!("block={max}")),
+            "block"
+        );
+        assert_eq!(
+            rejected::<ChangesCriteria>(&format!("since_block=0&limit={max}")),
+            "limit"
+        );
+        assert_eq!(
+            rejected::<BalanceCriteria>(&format!("at_block={max}")),
+            "at_block"
+        );
+
+        // And the ranges spanning everything, or going backwards, should be rejected too,
+        // without overflowing on the way:
+        assert_eq!(rejected::<GapsCriteria>(&format!("from=0&to={max}")), "to");
+        assert_eq!(
+            rejected::<GapsCriteria>(&format!("from={max}&to=0")),
+            "from"
+        );
+        assert_eq!(rejected::<GapsCriteria>("from=10&to=0"), "to");
+        assert_eq!(
+            rejected::<LargestCriteria>("from_block=0&to_block=432000"),
+            "to_block"
+        );
+        assert_eq!(
+            rejected::<FlowCriteria>("account=x&from_block=10&to_block=0"),
+            "to_block"
+        );
+    }
+
+    #[test]
+    fn sane_numbers_admitted() {
+        // Given the queries within bounds, when they get checked, then they should pass:
+        check::<Criteria>("block=777&lamports=5000&indexed_since=1700000000").unwrap();
+        check::<Criteria>("").unwrap();
+        check::<RecentCriteria>("limit=1000").unwrap();
+        check::<GapsCriteria>("from=777&to=777").unwrap();
+        check::<LargestCriteria>("from_block=0&to_block=431999").unwrap();
+        check::<FlowCriteria>("account=x&from_block=0&to_block=9007199254740992").unwrap();
+
+        // And an outsized count of the largest transfers should be capped instead:
+        let web::Query(criteria) = web::Query::<LargestCriteria>::from_query(&format!(
+            "from_block=0&to_block=0&n={}",
+            usize::MAX
+        ))
+        .unwrap();
+        criteria.check_bounds().unwrap();
+        assert_eq!(criteria.n(), MAX_LARGEST);
+    }
+}
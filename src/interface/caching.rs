@@ -0,0 +1,94 @@
+//! Letting the polling clients skip the answers they have already seen.
+//! Every answer only changes when a new block gets indexed,
+//! so the last known block is as good a validator as any.
+
+use actix_web::http::header::{self, ContentType, HeaderValue};
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+use futures::Stream;
+
+use crate::result::Error;
+use crate::store::Store;
+use crate::Result;
+
+/// The block the answer is fresh as of.
+const LAST_KNOWN_BLOCK: &str = "X-Last-Known-Block";
+
+/// The block the client has already seen, so it only wants the answer if there is a newer one.
+const IF_GREATER_THAN: &str = "If-Last-Known-Block-Greater-Than";
+
+/// What the client has seen, against what there is.
+#[derive(Clone, Copy, Debug)]
+pub struct Freshness {
+    last_known_block: Option<u64>,
+    seen: bool,
+}
+
+impl Freshness {
+    /// Take the last known block before looking anything up,
+    /// so that the answer is at least as fresh as the block it is labeled with.
+    pub async fn of(request: &HttpRequest, store: &Store) -> Result<Self> {
+        let last_known_block = store.last_known_block().await;
+        let seen = match request.headers().get(IF_GREATER_THAN) {
+            Some(value) => {
+                let seen_block = parse_block(value)?;
+                last_known_block.is_some_and(|block| block <= seen_block)
+            }
+            None => false,
+        };
+        Ok(Self {
+            last_known_block,
+            seen,
+        })
+    }
+
+    /// The block the answer is going to be labeled with.
+    pub fn last_known_block(&self) -> Option<u64> {
+        self.last_known_block
+    }
+
+    /// An empty answer if the client has seen everything there is.
+    pub fn not_modified(&self) -> Option<HttpResponse> {
+        self.seen
+            .then(|| self.labeled(HttpResponse::NotModified()).finish())
+    }
+
+    /// The answer, labeled with the block it is fresh as of.
+    pub fn respond(&self, body: String) -> HttpResponse {
+        self.labeled(HttpResponse::Ok())
+            .insert_header(ContentType::plaintext())
+            .body(body)
+    }
+
+    /// The answer as it comes, labeled with the block it is fresh as of.
+    pub fn stream<S, E>(&self, content_type: &'static str, body: S) -> HttpResponse
+    where
+        S: Stream<Item = Result<Bytes, E>> + 'static,
+        E: Into<Box<dyn std::error::Error>> + 'static,
+    {
+        self.labeled(HttpResponse::Ok())
+            .insert_header((header::CONTENT_TYPE, content_type))
+            .streaming(body)
+    }
+
+    fn labeled(&self, mut builder: HttpResponseBuilder) -> HttpResponseBuilder {
+        // Caches may keep the answer, but should ask whether it is still good.
+        builder.insert_header((header::CACHE_CONTROL, "no-cache"));
+        if let Some(block) = self.last_known_block {
+            builder.insert_header((LAST_KNOWN_BLOCK, block));
+        }
+        builder
+    }
+}
+
+fn parse_block(value: &HeaderValue) -> Result<u64> {
+    let bad = |reason: String| Error::BadHeader {
+        header: IF_GREATER_THAN,
+        reason,
+    };
+    let value = value.to_str().map_err(|e| bad(e.to_string()))?;
+    value
+        .trim()
+        .parse()
+        .map_err(|e: std::num::ParseIntError| bad(e.to_string()))
+}
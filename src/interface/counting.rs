@@ -0,0 +1,86 @@
+//! Counting the records a query would yield, without as much as decoding them where possible.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::planning::{QueryPlan, Strategy};
+use crate::store::Store;
+use crate::Result;
+
+/// How long a count may go through the records before giving up.
+const MAX_COUNTING: Duration = Duration::from_secs(5);
+
+/// How many votes the plan would yield.
+pub async fn count_votes(store: &Arc<Store>, plan: QueryPlan) -> Result<usize> {
+    let QueryPlan { strategy, filters } = plan;
+    let admit = |vote: &_| filters.admit_vote(vote);
+    let count = match strategy {
+        Strategy::Block(block) if !filters.beyond_block() => {
+            store.count_votes_by_block_index(block).await?
+        }
+        Strategy::Program(program) if filters.block.is_none() && !filters.beyond_block() => {
+            store.count_votes_by_program(&program).await?
+        }
+        Strategy::Signatures(signatures) => tally(store.find_votes(&signatures).await?, admit),
+        Strategy::Signature(signature) => tally(store.find_votes(&[signature]).await?, admit),
+        Strategy::Block(block) => tally(store.find_votes_by_block_index(block).await?, admit),
+        Strategy::Blocks(first, last) => {
+            tally(store.find_votes_by_block_range(first, last).await?, admit)
+        }
+        Strategy::Timestamps(first, last) => tally(
+            store.find_votes_by_timestamp_range(first, last).await?,
+            admit,
+        ),
+        Strategy::Program(program) => tally(store.find_votes_by_program(&program).await?, admit),
+        Strategy::Pair(from, to) => {
+            tally(store.find_votes_by_account_pair(&from, &to).await?, admit)
+        }
+        // Only ever planned for the transfers; a full scan gets it right all the same.
+        Strategy::Destination(_) | Strategy::FullScan => {
+            store
+                .count_votes(Instant::now() + MAX_COUNTING, admit)
+                .await?
+        }
+    };
+    Ok(count)
+}
+
+/// How many transfers the plan would yield.
+pub async fn count_transfers(store: &Arc<Store>, plan: QueryPlan) -> Result<usize> {
+    let QueryPlan { strategy, filters } = plan;
+    let admit = |transfer: &_| filters.admit_transfer(transfer);
+    let count = match strategy {
+        Strategy::Block(block) if !filters.beyond_block() => {
+            store.count_transfers_by_block_index(block).await?
+        }
+        Strategy::Program(program) if filters.block.is_none() && !filters.beyond_block() => {
+            store.count_transfers_by_program(&program).await?
+        }
+        Strategy::Signatures(signatures) => tally(store.find_transfers(&signatures).await?, admit),
+        Strategy::Signature(signature) => tally(store.find_transfers(&[signature]).await?, admit),
+        Strategy::Block(block) => tally(store.find_transfers_by_block_index(block).await?, admit),
+        Strategy::Blocks(first, last) => tally(
+            store.find_transfers_by_block_range(first, last).await?,
+            admit,
+        ),
+        Strategy::Timestamps(first, last) => tally(
+            store.find_transfers_by_timestamp_range(first, last).await?,
+            admit,
+        ),
+        Strategy::Program(program) => {
+            tally(store.find_transfers_by_program(&program).await?, admit)
+        }
+        Strategy::Destination(to) => tally(store.find_transfers_by_account(&to).await?, admit),
+        // Only ever planned for the votes; a full scan gets it right all the same.
+        Strategy::Pair(..) | Strategy::FullScan => {
+            store
+                .count_transfers(Instant::now() + MAX_COUNTING, admit)
+                .await?
+        }
+    };
+    Ok(count)
+}
+
+fn tally<T>(records: Vec<T>, admit: impl Fn(&T) -> bool) -> usize {
+    records.iter().filter(|record| admit(record)).count()
+}
@@ -0,0 +1,330 @@
+//! Peeking into the database as it is, for diagnosing encoding issues.
+
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
+use crate::record::{Reward, StakeDelegation, TokenTransfer, Transfer, Vote};
+use crate::result::Error;
+use crate::store::{
+    IndexField, Store, EMPTY_NS, LAST_KNOWN_BLOCK_KEY, REWARDS_INDEX_NS, REWARDS_NS, SKIPPED_NS,
+    STAGED_NS, STAKE_DELEGATIONS_INDEX_NS, STAKE_DELEGATIONS_NS, TOKEN_TRANSFERS_INDEX_NS,
+    TOKEN_TRANSFERS_NS, TRANSFERS_CHRONO_NS, TRANSFERS_INDEX_NS, TRANSFERS_NS, VOTES_CHRONO_NS,
+    VOTES_INDEX_NS, VOTES_NS,
+};
+use crate::Result;
+
+/// How many entries a single prefix scan may show.
+const MAX_ENTRIES: usize = 100;
+
+/// A single row, both as stored and as understood.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct RawEntry {
+    pub key: String,
+    pub value: String,
+    pub decoded: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn describe<T: DeserializeOwned + Debug>(bytes: &[u8]) -> String {
+    match postcard::from_bytes::<T>(bytes) {
+        Ok(decoded) => format!("{decoded:?}"),
+        Err(e) => format!("<undecodable: {e}>"),
+    }
+}
+
+/// Describe an index entry of `{field}:{value}:{primary_key} -> {primary_key}`.
+fn describe_indexed(key: &[u8]) -> String {
+    let Some((&tag, rest)) = key.split_first() else {
+        return "<too short>".to_owned();
+    };
+    let Some(field) = IndexField::from_tag(tag) else {
+        return format!("<unknown field {tag:#04x}>");
+    };
+    let width = match field {
+        IndexField::Account | IndexField::Program => 32,
+        IndexField::Block | IndexField::Lamports | IndexField::Timestamp => 8,
+    };
+    if rest.len() < width {
+        return "<too short>".to_owned();
+    }
+    let (value, primary_key) = rest.split_at(width);
+    let value = match field {
+        IndexField::Account | IndexField::Program => Pubkey::try_from(value).unwrap().to_string(),
+        IndexField::Block | IndexField::Lamports | IndexField::Timestamp => {
+            u64::from_be_bytes(value.try_into().unwrap()).to_string()
+        }
+    };
+    format!(
+        "{field:?} {value} -> {}",
+        describe::<Signature>(primary_key)
+    )
+}
+
+/// Describe a chronological index entry of `{block_index}:{instruction_index}:{signature}`.
+fn describe_chronological(key: &[u8]) -> String {
+    if key.len() < 12 {
+        return "<too short>".to_owned();
+    }
+    let (block_index, rest) = key.split_at(8);
+    let (instruction_index, signature) = rest.split_at(4);
+    let block_index = u64::from_be_bytes(block_index.try_into().unwrap());
+    let instruction_index = u32::from_be_bytes(instruction_index.try_into().unwrap());
+    match Signature::try_from(signature) {
+        Ok(signature) => format!("{block_index}:{instruction_index}:{signature}"),
+        Err(e) => format!("{block_index}:{instruction_index}:<undecodable: {e}>"),
+    }
+}
+
+/// Describe a reward index entry of `{pubkey}:{block_index}:{reward_index}`.
+fn describe_reward_indexed(key: &[u8]) -> String {
+    if key.len() < 32 + 12 {
+        return "<too short>".to_owned();
+    }
+    let (pubkey, rest) = key.split_at(32);
+    let (block_index, reward_index) = rest.split_at(8);
+    let pubkey = Pubkey::try_from(pubkey).unwrap();
+    let block_index = u64::from_be_bytes(block_index.try_into().unwrap());
+    let reward_index = u32::from_be_bytes(reward_index.try_into().unwrap());
+    format!("{pubkey} -> {block_index}:{reward_index}")
+}
+
+/// Describe a token transfer or stake delegation index entry
+/// of `{pubkey}:{block_index}:{instruction_index}:{signature}`.
+fn describe_account_indexed(key: &[u8]) -> String {
+    if key.len() < 32 {
+        return "<too short>".to_owned();
+    }
+    let (pubkey, rest) = key.split_at(32);
+    let pubkey = Pubkey::try_from(pubkey).unwrap();
+    format!("{pubkey} -> {}", describe_chronological(rest))
+}
+
+fn entries_under_prefix(
+    store: &Store,
+    cf: &str,
+    prefix: &[u8],
+    describe_key: impl Fn(&[u8]) -> String,
+) -> Result<Vec<RawEntry>> {
+    let rows = store.raw_scan(cf, prefix, MAX_ENTRIES)?;
+    Ok(rows
+        .into_iter()
+        .map(|(key, value)| RawEntry {
+            decoded: describe_key(&key),
+            key: hex(&key),
+            value: hex(&value),
+        })
+        .collect())
+}
+
+/// The same as [entries_under_prefix], but describing the values rather than the keys.
+fn records_under_prefix(
+    store: &Store,
+    cf: &str,
+    prefix: &[u8],
+    describe_value: impl Fn(&[u8]) -> String,
+) -> Result<Vec<RawEntry>> {
+    let rows = store.raw_scan(cf, prefix, MAX_ENTRIES)?;
+    Ok(rows
+        .into_iter()
+        .map(|(key, value)| RawEntry {
+            decoded: describe_value(&value),
+            key: hex(&key),
+            value: hex(&value),
+        })
+        .collect())
+}
+
+fn entry_under_key(
+    store: &Store,
+    cf: &str,
+    key: &[u8],
+    describe_value: impl Fn(&[u8]) -> String,
+) -> Result<Vec<RawEntry>> {
+    let Some(value) = store.raw_get(cf, key)? else {
+        return Err(Error::NotFound);
+    };
+    Ok(vec![RawEntry {
+        key: hex(key),
+        value: hex(&value),
+        decoded: describe_value(&value),
+    }])
+}
+
+/// Find the rows of the given column family, interpreting the key the way the family does:
+///   - `votes`, `transfers`: the signature, yielding every occurrence if keyed by those;
+///   - `votes-index`, `transfers-index`: the block index, the lamports, the account, or the program;
+///   - `votes-chrono`, `transfers-chrono`: the block index;
+///   - `rewards`: the block index;
+///   - `rewards-index`: the account;
+///   - `token-transfers`: the block index;
+///   - `token-transfers-index`: the account;
+///   - `stake-delegations`: the block index;
+///   - `stake-delegations-index`: the account;
+///   - `skipped`: the block index;
+///   - `staged`: the place in the line of the committer;
+///   - `meta`: `last_known_block`.
+pub async fn find_raw_entries(store: &Arc<Store>, cf: &str, key: &str) -> Result<Vec<RawEntry>> {
+    match cf {
+        "votes" | "transfers" => {
+            let signature = Signature::from_str(key)?;
+            // The signature leads the key, whatever else follows it:
+            let prefix = postcard::to_stdvec(&signature).unwrap();
+            let entries = if cf == "votes" {
+                records_under_prefix(store, VOTES_NS, &prefix, describe::<Vote>)?
+            } else {
+                records_under_prefix(store, TRANSFERS_NS, &prefix, describe::<Transfer>)?
+            };
+            if entries.is_empty() {
+                return Err(Error::NotFound);
+            }
+            Ok(entries)
+        }
+        "votes-index" | "transfers-index" => {
+            let cf = if cf == "votes-index" {
+                VOTES_INDEX_NS
+            } else {
+                TRANSFERS_INDEX_NS
+            };
+            let (value, fields) = if let Ok(number) = u64::from_str(key) {
+                let fields = [
+                    IndexField::Block,
+                    IndexField::Lamports,
+                    IndexField::Timestamp,
+                ];
+                (number.to_be_bytes().to_vec(), fields)
+            } else {
+                let address = Pubkey::from_str(key)?;
+                let fields = [IndexField::Account, IndexField::Program];
+                (address.to_bytes().to_vec(), fields)
+            };
+            let mut entries = Vec::new();
+            for field in fields {
+                let prefix = field.prefix(&value);
+                entries.extend(entries_under_prefix(store, cf, &prefix, describe_indexed)?);
+            }
+            Ok(entries)
+        }
+        "votes-chrono" | "transfers-chrono" => {
+            let cf = if cf == "votes-chrono" {
+                VOTES_CHRONO_NS
+            } else {
+                TRANSFERS_CHRONO_NS
+            };
+            let block_index = u64::from_str(key).map_err(|e| Error::BadQueryParam {
+                param: "key".into(),
+                reason: e.to_string(),
+            })?;
+            entries_under_prefix(
+                store,
+                cf,
+                &block_index.to_be_bytes(),
+                describe_chronological,
+            )
+        }
+        "rewards" => {
+            let block_index = u64::from_str(key).map_err(|e| Error::BadQueryParam {
+                param: "key".into(),
+                reason: e.to_string(),
+            })?;
+            records_under_prefix(
+                store,
+                REWARDS_NS,
+                &block_index.to_be_bytes(),
+                describe::<Reward>,
+            )
+        }
+        "rewards-index" => {
+            let address = Pubkey::from_str(key)?;
+            entries_under_prefix(
+                store,
+                REWARDS_INDEX_NS,
+                address.as_ref(),
+                describe_reward_indexed,
+            )
+        }
+        "token-transfers" => {
+            let block_index = u64::from_str(key).map_err(|e| Error::BadQueryParam {
+                param: "key".into(),
+                reason: e.to_string(),
+            })?;
+            records_under_prefix(
+                store,
+                TOKEN_TRANSFERS_NS,
+                &block_index.to_be_bytes(),
+                describe::<TokenTransfer>,
+            )
+        }
+        "token-transfers-index" => {
+            let address = Pubkey::from_str(key)?;
+            entries_under_prefix(
+                store,
+                TOKEN_TRANSFERS_INDEX_NS,
+                address.as_ref(),
+                describe_account_indexed,
+            )
+        }
+        "stake-delegations" => {
+            let block_index = u64::from_str(key).map_err(|e| Error::BadQueryParam {
+                param: "key".into(),
+                reason: e.to_string(),
+            })?;
+            records_under_prefix(
+                store,
+                STAKE_DELEGATIONS_NS,
+                &block_index.to_be_bytes(),
+                describe::<StakeDelegation>,
+            )
+        }
+        "stake-delegations-index" => {
+            let address = Pubkey::from_str(key)?;
+            entries_under_prefix(
+                store,
+                STAKE_DELEGATIONS_INDEX_NS,
+                address.as_ref(),
+                describe_account_indexed,
+            )
+        }
+        "skipped" => {
+            let block_index = u64::from_str(key).map_err(|e| Error::BadQueryParam {
+                param: "key".into(),
+                reason: e.to_string(),
+            })?;
+            entry_under_key(store, SKIPPED_NS, &block_index.to_be_bytes(), |_| {
+                "skipped by the cluster".to_owned()
+            })
+        }
+        "empty" => {
+            let block_index = u64::from_str(key).map_err(|e| Error::BadQueryParam {
+                param: "key".into(),
+                reason: e.to_string(),
+            })?;
+            entry_under_key(store, EMPTY_NS, &block_index.to_be_bytes(), |_| {
+                "processed with nothing to index".to_owned()
+            })
+        }
+        "staged" => {
+            let seq = u64::from_str(key).map_err(|e| Error::BadQueryParam {
+                param: "key".into(),
+                reason: e.to_string(),
+            })?;
+            entry_under_key(store, STAGED_NS, &seq.to_be_bytes(), |value| {
+                String::from_utf8_lossy(value).into_owned()
+            })
+        }
+        "meta" if key == "last_known_block" => entry_under_key(
+            store,
+            rocksdb::DEFAULT_COLUMN_FAMILY_NAME,
+            LAST_KNOWN_BLOCK_KEY,
+            describe::<u64>,
+        ),
+        _ => Err(Error::NotFound),
+    }
+}
@@ -0,0 +1,149 @@
+//! Letting the polling clients skip the answers they already have, judging by what they hold.
+//! Unlike the last known block, the tag stays the same for as long as the very answer does,
+//! so a new block having nothing to do with the query does not make the client fetch it anew.
+
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+
+use actix_web::body::{self, BodySize, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{error, Error};
+
+/// Tags the successful answers to `GET` with the hash of their body,
+/// and answers with an empty `304 Not Modified` instead should the client already have the same.
+pub struct Etag;
+
+impl<S, B> Transform<S, ServiceRequest> for Etag
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = EtagMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(EtagMiddleware { service }))
+    }
+}
+
+pub struct EtagMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for EtagMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let taggable = request.method() == Method::GET;
+        let seen = request.headers().get(header::IF_NONE_MATCH).cloned();
+        let response = self.service.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            // The streams are left to go out as they come, rather than be held up for the hash.
+            let streamed = matches!(response.response().body().size(), BodySize::Stream);
+            if !taggable || streamed || response.status() != StatusCode::OK {
+                return Ok(response.map_into_boxed_body());
+            }
+
+            let (request, response) = response.into_parts();
+            let (response, body) = response.into_parts();
+            let body = body::to_bytes(body).await.map_err(|e| {
+                let e: Box<dyn std::error::Error> = e.into();
+                error::ErrorInternalServerError(e)
+            })?;
+
+            let tag = tag_of(&body);
+            let mut response = if seen.is_some_and(|seen| matches(&seen, &tag)) {
+                let mut response = response.set_body(BoxBody::new(()));
+                *response.status_mut() = StatusCode::NOT_MODIFIED;
+                response
+            } else {
+                response.set_body(BoxBody::new(body))
+            };
+            if let Ok(tag) = HeaderValue::from_str(&tag) {
+                response.headers_mut().insert(header::ETAG, tag);
+            }
+            Ok(ServiceResponse::new(request, response))
+        })
+    }
+}
+
+/// The hex of the Blake3 hash of the body, quoted as a strong tag.
+fn tag_of(body: &[u8]) -> String {
+    format!("\"{}\"", blake3::hash(body).to_hex())
+}
+
+/// Whether any of the tags in `If-None-Match` is the one of the answer.
+fn matches(seen: &HeaderValue, tag: &str) -> bool {
+    let Ok(seen) = seen.to_str() else {
+        return false;
+    };
+    seen.split(',')
+        .map(str::trim)
+        .any(|seen| seen == "*" || seen.trim_start_matches("W/") == tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn same_answer_not_sent_twice() {
+        // Given an endpoint answering the same every time:
+        let app = test::init_service(
+            App::new().service(
+                web::resource("/votes")
+                    .wrap(Etag)
+                    .route(web::get().to(|| async { HttpResponse::Ok().body("[]") })),
+            ),
+        )
+        .await;
+
+        // When asked for the first time:
+        let request = test::TestRequest::get().uri("/votes").to_request();
+        let response = test::call_service(&app, request).await;
+
+        // Then the answer should come tagged with the hash of its body:
+        assert_eq!(response.status(), StatusCode::OK);
+        let tag = response.headers().get(header::ETAG).unwrap().clone();
+        assert_eq!(tag.to_str().unwrap(), tag_of(b"[]"));
+
+        // When asked again with that tag:
+        let request = test::TestRequest::get()
+            .uri("/votes")
+            .insert_header((header::IF_NONE_MATCH, tag.clone()))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        // Then the answer should be empty, keeping the tag:
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(header::ETAG), Some(&tag));
+        assert!(test::read_body(response).await.is_empty());
+
+        // When asked with some other tag:
+        let request = test::TestRequest::get()
+            .uri("/votes")
+            .insert_header((header::IF_NONE_MATCH, "\"stale\""))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        // Then the whole answer should come again:
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "[]");
+    }
+}
@@ -0,0 +1,56 @@
+//! Bridge between the db and the web interface.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::store::Store;
+use crate::Result;
+
+/// The net effect of the indexed transfers on an account.
+///
+/// This is not the on-chain balance: only the System transfers
+/// that surf has seen are accounted for, so fees, rent, rewards, program-owned
+/// lamport movements, and anything before the first indexed block are missing.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct BalanceDelta {
+    pub account: String,
+    pub at_block: Option<u64>,
+    pub lamports_delta: i128,
+    pub transfers: u64,
+}
+
+pub async fn find_balance_delta(
+    store: &Arc<Store>,
+    account: &str,
+    at_block: Option<u64>,
+) -> Result<BalanceDelta> {
+    let account = Pubkey::from_str(account)?;
+
+    let mut lamports_delta = 0i128;
+    let mut transfers = 0;
+    for transfer in store.find_transfers_by_account(&account).await? {
+        if let Some(at_block) = at_block {
+            if transfer.block_index > at_block {
+                continue;
+            }
+        }
+        if transfer.destination == account {
+            lamports_delta += i128::from(transfer.lamports);
+        }
+        if transfer.source == account {
+            lamports_delta -= i128::from(transfer.lamports);
+        }
+        if transfer.destination == account || transfer.source == account {
+            transfers += 1;
+        }
+    }
+
+    Ok(BalanceDelta {
+        account: account.to_string(),
+        at_block,
+        lamports_delta,
+        transfers,
+    })
+}
@@ -0,0 +1,123 @@
+//! Bridge between the db and the web interface.
+
+use std::iter::Peekable;
+use std::sync::Arc;
+
+use crate::record::{Kind, PrettyRecord};
+use crate::store::{Cursor, Store};
+use crate::Result;
+
+/// Whatever got indexed past a block, a block at a time.
+#[derive(Debug, serde::Serialize)]
+pub struct Changes {
+    pub since_block: u64,
+    /// The last block whose records have all been sent, to pass as `since_block` next time.
+    pub until_block: u64,
+    /// Whether there are blocks left past `until_block`.
+    pub more: bool,
+    pub records: Vec<PrettyRecord>,
+}
+
+/// The records of every kind from the blocks past `since_block`, by the block,
+/// then by the kind, then in their on-chain order. Whole blocks only,
+/// so that no block goes without all of its records once there are `limit` of them.
+pub async fn find_changes(store: &Arc<Store>, since_block: u64, limit: usize) -> Result<Changes> {
+    let kinds = [
+        Kind::Vote,
+        Kind::Transfer,
+        Kind::Reward,
+        Kind::TokenTransfer,
+        Kind::StakeDelegation,
+    ];
+    let mut cursors: Vec<Peekable<Cursor>> = kinds
+        .into_iter()
+        .map(|kind| store.iter_since(kind, since_block).peekable())
+        .collect();
+
+    let mut records = Vec::new();
+    let mut until_block = since_block;
+    let more = loop {
+        let Some(block) = earliest_block(&mut cursors)? else {
+            break false;
+        };
+        if records.len() >= limit {
+            break true;
+        }
+        for cursor in &mut cursors {
+            while let Some(record) =
+                cursor.next_if(|x| matches!(x, Ok(record) if record.block_index() == block))
+            {
+                records.push(PrettyRecord::from(record?));
+            }
+        }
+        until_block = block;
+    };
+    Ok(Changes {
+        since_block,
+        until_block,
+        more,
+        records,
+    })
+}
+
+/// The earliest block any of the cursors has left, failing on the first unreadable record.
+fn earliest_block(cursors: &mut [Peekable<Cursor<'_>>]) -> Result<Option<u64>> {
+    let mut earliest: Option<u64> = None;
+    for cursor in cursors {
+        if let Some(Err(_)) = cursor.peek() {
+            if let Some(Err(e)) = cursor.next() {
+                return Err(e);
+            }
+        }
+        if let Some(Ok(record)) = cursor.peek() {
+            let block = record.block_index();
+            earliest = Some(earliest.map_or(block, |earliest| earliest.min(block)));
+        }
+    }
+    Ok(earliest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::record::{fixtures, Record};
+
+    #[tokio::test]
+    async fn changes_come_in_whole_blocks() {
+        // Given a vote and a transfer in one block, and a record in each of the next two:
+        let store = Arc::new(Store::disposable().await.unwrap());
+        let records = [
+            Record::Vote(fixtures::vote(777, 0)),
+            Record::Transfer(fixtures::transfer(777, 1)),
+            Record::Vote(fixtures::vote(778, 0)),
+            Record::Transfer(fixtures::transfer(779, 0)),
+        ];
+        for record in &records {
+            store
+                .save_block(std::slice::from_ref(record))
+                .await
+                .unwrap();
+        }
+        let pretty: Vec<_> = records.into_iter().map(PrettyRecord::from).collect();
+
+        // When we pull the changes three records at a time:
+        let first = find_changes(&store, 776, 3).await.unwrap();
+
+        // Then the first two blocks should come, and there should be more:
+        assert_eq!(first.records, pretty[..3]);
+        assert_eq!(first.until_block, 778);
+        assert!(first.more);
+
+        // And when we pull on from there, the last block should come, and nothing more:
+        let second = find_changes(&store, first.until_block, 3).await.unwrap();
+        assert_eq!(second.records, pretty[3..]);
+        assert_eq!(second.until_block, 779);
+        assert!(!second.more);
+
+        // And with the limit below the records of a block, the block should still come whole:
+        let whole = find_changes(&store, 776, 1).await.unwrap();
+        assert_eq!(whole.records, pretty[..2]);
+        assert_eq!(whole.until_block, 777);
+    }
+}
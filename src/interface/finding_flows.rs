@@ -0,0 +1,113 @@
+//! Bridge between the db and the web interface.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::store::Store;
+use crate::Result;
+
+/// How many transfers of the account a flow query looks at, at most.
+pub const MAX_SCANNED: usize = 100_000;
+
+/// Where the lamports of an account went, and where they came from.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Flow {
+    pub account: String,
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    /// Whether the account has more transfers than have been looked at.
+    pub truncated: bool,
+    pub outgoing: Vec<Outgoing>,
+    pub incoming: Vec<Incoming>,
+}
+
+/// The transfers from the account to one counterparty, summed up.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Outgoing {
+    pub to: String,
+    pub lamports: u128,
+    pub count: u64,
+}
+
+/// The transfers from one counterparty to the account, summed up.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Incoming {
+    pub from: String,
+    pub lamports: u128,
+    pub count: u64,
+}
+
+/// Sum up the transfers of the account within the blocks from `from_block` to `to_block`
+/// inclusive, by the counterparty, keeping the `edges` largest ones each way.
+pub async fn find_flow(
+    store: &Arc<Store>,
+    account: &str,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+    edges: usize,
+) -> Result<Flow> {
+    let account = Pubkey::from_str(account)?;
+
+    // One past the cap, to tell whether there is more:
+    let mut transfers = store
+        .find_transfers_by_account_up_to(&account, MAX_SCANNED + 1)
+        .await?;
+    let truncated = transfers.len() > MAX_SCANNED;
+    transfers.truncate(MAX_SCANNED);
+
+    let mut outgoing = HashMap::<Pubkey, (u128, u64)>::new();
+    let mut incoming = HashMap::<Pubkey, (u128, u64)>::new();
+    for transfer in transfers {
+        if from_block.is_some_and(|from_block| transfer.block_index < from_block)
+            || to_block.is_some_and(|to_block| transfer.block_index > to_block)
+        {
+            continue;
+        }
+        if transfer.source == account {
+            let edge = outgoing.entry(transfer.destination).or_default();
+            edge.0 += u128::from(transfer.lamports);
+            edge.1 += 1;
+        }
+        if transfer.destination == account {
+            let edge = incoming.entry(transfer.source).or_default();
+            edge.0 += u128::from(transfer.lamports);
+            edge.1 += 1;
+        }
+    }
+
+    let outgoing = largest(outgoing, edges)
+        .map(|(to, (lamports, count))| Outgoing {
+            to: to.to_string(),
+            lamports,
+            count,
+        })
+        .collect();
+    let incoming = largest(incoming, edges)
+        .map(|(from, (lamports, count))| Incoming {
+            from: from.to_string(),
+            lamports,
+            count,
+        })
+        .collect();
+    Ok(Flow {
+        account: account.to_string(),
+        from_block,
+        to_block,
+        truncated,
+        outgoing,
+        incoming,
+    })
+}
+
+/// The `n` counterparties with the most lamports, the most first.
+fn largest(
+    edges: HashMap<Pubkey, (u128, u64)>,
+    n: usize,
+) -> impl Iterator<Item = (Pubkey, (u128, u64))> {
+    let mut edges: Vec<_> = edges.into_iter().collect();
+    edges.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(&b.0)));
+    edges.into_iter().take(n)
+}
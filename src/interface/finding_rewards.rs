@@ -0,0 +1,34 @@
+//! Bridge between the db and the web interface.
+
+use std::sync::Arc;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::record::Reward;
+use crate::result::Error;
+use crate::store::Store;
+use crate::Result;
+
+/// The rewards of the block, or of the account, or of the account within the block.
+/// There are far too many of them to list them all, so either one has to be given.
+pub async fn find_rewards(
+    store: &Arc<Store>,
+    block: Option<u64>,
+    pubkey: Option<Pubkey>,
+) -> Result<Vec<Reward>> {
+    match (block, pubkey) {
+        (Some(block), pubkey) => store
+            .find_rewards_by_block_index(block)
+            .await
+            .map(|results| {
+                results
+                    .into_iter()
+                    .filter(|x| pubkey.is_none() || pubkey == Some(x.pubkey))
+                    .collect()
+            }),
+        (None, Some(pubkey)) => store.find_rewards_by_pubkey(&pubkey).await,
+        (None, None) => Err(Error::BadQuery(
+            "either `block` or `pubkey` is required".to_owned(),
+        )),
+    }
+}
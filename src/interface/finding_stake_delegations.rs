@@ -0,0 +1,30 @@
+//! Bridge between the db and the web interface.
+
+use std::sync::Arc;
+
+use super::planning::Filters;
+use crate::record::StakeDelegation;
+use crate::store::Store;
+use crate::Result;
+
+/// The stake delegations passing the filters, the vote account standing for `to`,
+/// and the stake account for `from`. By the account index if either is given,
+/// or by the blocks otherwise, as there are few enough delegations to go through them all.
+pub async fn find_stake_delegations(
+    store: &Arc<Store>,
+    filters: &Filters,
+) -> Result<Vec<StakeDelegation>> {
+    let found = match filters.to.or(filters.from) {
+        Some(account) => store.find_stake_delegations_by_account(&account).await?,
+        None => {
+            let blocks = filters.blocks();
+            store
+                .find_stake_delegations_by_block_range(*blocks.start(), *blocks.end())
+                .await?
+        }
+    };
+    Ok(found
+        .into_iter()
+        .filter(|x| filters.admit_stake_delegation(x))
+        .collect())
+}
@@ -0,0 +1,39 @@
+//! Bridge between the db and the web interface.
+
+use std::sync::Arc;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::record::TokenTransfer;
+use crate::result::Error;
+use crate::store::Store;
+use crate::Result;
+
+/// The token transfers of the block, or of the account, or of the account within the block.
+/// There are far too many of them to list them all, so either one has to be given.
+pub async fn find_token_transfers(
+    store: &Arc<Store>,
+    block: Option<u64>,
+    account: Option<Pubkey>,
+) -> Result<Vec<TokenTransfer>> {
+    match (block, account) {
+        (Some(block), account) => {
+            store
+                .find_token_transfers_by_block_index(block)
+                .await
+                .map(|results| {
+                    results
+                        .into_iter()
+                        .filter(|x| match account {
+                            Some(account) => x.accounts().contains(&&account),
+                            None => true,
+                        })
+                        .collect()
+                })
+        }
+        (None, Some(account)) => store.find_token_transfers_by_account(&account).await,
+        (None, None) => Err(Error::BadQuery(
+            "either `block` or `account` is required".to_owned(),
+        )),
+    }
+}
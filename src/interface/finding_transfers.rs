@@ -1,11 +1,13 @@
 //! Bridge between the db and the web interface.
 
-use std::str::FromStr;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
 use std::sync::Arc;
 
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
 
+use super::Sort;
 use crate::record::Transfer;
 use crate::result::Error;
 use crate::store::Store;
@@ -14,28 +16,120 @@ use crate::Result;
 pub async fn find_transfers_with_block_index(
     store: &Arc<Store>,
     block_index: u64,
+    to: Option<Pubkey>,
+    from: Option<Pubkey>,
 ) -> Result<Vec<Transfer>> {
     store
         .find_transfers_by_block_index(block_index)
         .await
         .map(|results| {
-            results
+            let mut results: Vec<_> = results
                 .into_iter()
                 .filter(|x| x.block_index == block_index)
-                .collect()
+                .filter(|x| to.is_none() || to == Some(x.destination))
+                .filter(|x| from.is_none() || from == Some(x.source))
+                .collect();
+            // The index yields them by signature, which is no order at all.
+            results.sort_by_key(|x| x.instruction_index);
+            results
         })
 }
 
-pub async fn find_transfers_with_signature(
+pub async fn find_transfers_in_block_range(
+    store: &Arc<Store>,
+    block_from: u64,
+    block_to: u64,
+    to: Option<Pubkey>,
+    from: Option<Pubkey>,
+) -> Result<Vec<Transfer>> {
+    store
+        .find_transfers_by_block_range(block_from, block_to)
+        .await
+        .map(|results| {
+            let mut results: Vec<_> = results
+                .into_iter()
+                .filter(|x| to.is_none() || to == Some(x.destination))
+                .filter(|x| from.is_none() || from == Some(x.source))
+                .collect();
+            // The index yields them by the block, then by signature.
+            results.sort_by_key(|x| (x.block_index, x.instruction_index));
+            results
+        })
+}
+
+pub async fn find_transfers_in_timestamp_range(
+    store: &Arc<Store>,
+    ts_from: u64,
+    ts_to: u64,
+    to: Option<Pubkey>,
+    from: Option<Pubkey>,
+) -> Result<Vec<Transfer>> {
+    store
+        .find_transfers_by_timestamp_range(ts_from, ts_to)
+        .await
+        .map(|results| {
+            let mut results: Vec<_> = results
+                .into_iter()
+                .filter(|x| to.is_none() || to == Some(x.destination))
+                .filter(|x| from.is_none() || from == Some(x.source))
+                .collect();
+            // The index yields them by the time, which may be shared by several blocks.
+            results.sort_by_key(|x| (x.block_index, x.instruction_index));
+            results
+        })
+}
+
+pub async fn find_transfers_with_program(
+    store: &Arc<Store>,
+    program_id: &Pubkey,
+    block: Option<u64>,
+    to: Option<Pubkey>,
+    from: Option<Pubkey>,
+) -> Result<Vec<Transfer>> {
+    store
+        .find_transfers_by_program(program_id)
+        .await
+        .map(|results| {
+            let mut results: Vec<_> = results
+                .into_iter()
+                .filter(|x| block.is_none() || block == Some(x.block_index))
+                .filter(|x| to.is_none() || to == Some(x.destination))
+                .filter(|x| from.is_none() || from == Some(x.source))
+                .collect();
+            results.sort_by_key(|x| (x.block_index, x.instruction_index));
+            results
+        })
+}
+
+pub async fn find_transfers_with_destination(
     store: &Arc<Store>,
-    signature: &str,
+    to: &Pubkey,
+    block: Option<u64>,
+    from: Option<Pubkey>,
 ) -> Result<Vec<Transfer>> {
-    let signature = Signature::from_str(signature)?;
+    store.find_transfers_by_account(to).await.map(|results| {
+        let mut results: Vec<_> = results
+            .into_iter()
+            .filter(|x| x.destination == *to)
+            .filter(|x| block.is_none() || block == Some(x.block_index))
+            .filter(|x| from.is_none() || from == Some(x.source))
+            .collect();
+        results.sort_by_key(|x| (x.block_index, x.instruction_index));
+        results
+    })
+}
 
-    let Some(transfer) = store.find_transfer(&signature).await else {
+pub async fn find_transfers_with_signature(
+    store: &Arc<Store>,
+    signature: &Signature,
+) -> Result<Vec<Transfer>> {
+    let found = store
+        .find_transfers(std::slice::from_ref(signature))
+        .await?;
+    if found.is_empty() {
         return Err(Error::NotFound);
-    };
-    Ok(vec![transfer])
+    }
+    Ok(found)
 }
 
 pub async fn find_transfers_with_full_scan(
@@ -43,8 +137,12 @@ pub async fn find_transfers_with_full_scan(
     block: Option<u64>,
     to: Option<Pubkey>,
     from: Option<Pubkey>,
+    sort: Option<Sort>,
 ) -> Result<Vec<Transfer>> {
-    let all_transfers = store.find_all_transfers().await?;
+    let all_transfers = match sort {
+        Some(Sort::BlockAsc) => store.find_all_transfers_chronological().await?,
+        _ => store.find_all_transfers().await?,
+    };
 
     let mut transfers = Vec::with_capacity(all_transfers.len());
     for transfer in all_transfers {
@@ -67,3 +165,53 @@ pub async fn find_transfers_with_full_scan(
     }
     Ok(transfers)
 }
+
+/// Orders the transfers by the amount alone.
+struct ByLamports(Transfer);
+
+impl PartialEq for ByLamports {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.lamports == other.0.lamports
+    }
+}
+
+impl Eq for ByLamports {}
+
+impl PartialOrd for ByLamports {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ByLamports {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.lamports.cmp(&other.0.lamports)
+    }
+}
+
+/// The `n` largest transfers within the blocks from `from_block` to `to_block` inclusive,
+/// the largest first, with the bounds already checked.
+pub async fn find_largest_transfers(
+    store: &Arc<Store>,
+    from_block: u64,
+    to_block: u64,
+    n: usize,
+) -> Result<Vec<Transfer>> {
+    // A min-heap of the largest ones seen so far, so that the smallest of those
+    // is the one to go whenever there are too many.
+    let mut largest = BinaryHeap::with_capacity(n.saturating_add(1));
+    for transfer in store
+        .find_transfers_by_block_range(from_block, to_block)
+        .await?
+    {
+        largest.push(Reverse(ByLamports(transfer)));
+        if largest.len() > n {
+            largest.pop();
+        }
+    }
+    Ok(largest
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(ByLamports(transfer))| transfer)
+        .collect())
+}
@@ -1,11 +1,11 @@
 //! Bridge between the db and the web interface.
 
-use std::str::FromStr;
 use std::sync::Arc;
 
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
 
+use super::Sort;
 use crate::record::Vote;
 use crate::result::Error;
 use crate::store::Store;
@@ -14,25 +14,119 @@ use crate::Result;
 pub async fn find_votes_with_block_index(
     store: &Arc<Store>,
     block_index: u64,
+    to: Option<Pubkey>,
+    from: Option<Pubkey>,
 ) -> Result<Vec<Vote>> {
     store
         .find_votes_by_block_index(block_index)
         .await
         .map(|results| {
-            results
+            let mut results: Vec<_> = results
                 .into_iter()
                 .filter(|x| x.block_index == block_index)
-                .collect()
+                .filter(|x| to.is_none() || to == Some(x.target))
+                .filter(|x| from.is_none() || from == Some(x.author))
+                .collect();
+            // The index yields them by signature, which is no order at all.
+            results.sort_by_key(|x| x.instruction_index);
+            results
+        })
+}
+
+pub async fn find_votes_in_block_range(
+    store: &Arc<Store>,
+    block_from: u64,
+    block_to: u64,
+    to: Option<Pubkey>,
+    from: Option<Pubkey>,
+) -> Result<Vec<Vote>> {
+    store
+        .find_votes_by_block_range(block_from, block_to)
+        .await
+        .map(|results| {
+            let mut results: Vec<_> = results
+                .into_iter()
+                .filter(|x| to.is_none() || to == Some(x.target))
+                .filter(|x| from.is_none() || from == Some(x.author))
+                .collect();
+            // The index yields them by the block, then by signature.
+            results.sort_by_key(|x| (x.block_index, x.instruction_index));
+            results
         })
 }
 
-pub async fn find_votes_with_signature(store: &Arc<Store>, signature: &str) -> Result<Vec<Vote>> {
-    let signature = Signature::from_str(signature)?;
+pub async fn find_votes_in_timestamp_range(
+    store: &Arc<Store>,
+    ts_from: u64,
+    ts_to: u64,
+    to: Option<Pubkey>,
+    from: Option<Pubkey>,
+) -> Result<Vec<Vote>> {
+    store
+        .find_votes_by_timestamp_range(ts_from, ts_to)
+        .await
+        .map(|results| {
+            let mut results: Vec<_> = results
+                .into_iter()
+                .filter(|x| to.is_none() || to == Some(x.target))
+                .filter(|x| from.is_none() || from == Some(x.author))
+                .collect();
+            // The index yields them by the time, which may be shared by several blocks.
+            results.sort_by_key(|x| (x.block_index, x.instruction_index));
+            results
+        })
+}
 
-    let Some(vote) = store.find_vote(&signature).await else {
+pub async fn find_votes_with_program(
+    store: &Arc<Store>,
+    program_id: &Pubkey,
+    block: Option<u64>,
+    to: Option<Pubkey>,
+    from: Option<Pubkey>,
+) -> Result<Vec<Vote>> {
+    store
+        .find_votes_by_program(program_id)
+        .await
+        .map(|results| {
+            let mut results: Vec<_> = results
+                .into_iter()
+                .filter(|x| block.is_none() || block == Some(x.block_index))
+                .filter(|x| to.is_none() || to == Some(x.target))
+                .filter(|x| from.is_none() || from == Some(x.author))
+                .collect();
+            results.sort_by_key(|x| (x.block_index, x.instruction_index));
+            results
+        })
+}
+
+pub async fn find_votes_with_account_pair(
+    store: &Arc<Store>,
+    from: &Pubkey,
+    to: &Pubkey,
+) -> Result<Vec<Vote>> {
+    store
+        .find_votes_by_account_pair(from, to)
+        .await
+        .map(|results| {
+            // Either may be the author or the target, as far as the index goes.
+            let mut results: Vec<_> = results
+                .into_iter()
+                .filter(|x| x.author == *from && x.target == *to)
+                .collect();
+            results.sort_by_key(|x| (x.block_index, x.instruction_index));
+            results
+        })
+}
+
+pub async fn find_votes_with_signature(
+    store: &Arc<Store>,
+    signature: &Signature,
+) -> Result<Vec<Vote>> {
+    let found = store.find_votes(std::slice::from_ref(signature)).await?;
+    if found.is_empty() {
         return Err(Error::NotFound);
-    };
-    Ok(vec![vote])
+    }
+    Ok(found)
 }
 
 pub async fn find_votes_with_full_scan(
@@ -40,8 +134,12 @@ pub async fn find_votes_with_full_scan(
     block: Option<u64>,
     to: Option<Pubkey>,
     from: Option<Pubkey>,
+    sort: Option<Sort>,
 ) -> Result<Vec<Vote>> {
-    let all_votes = store.find_all_votes().await?;
+    let all_votes = match sort {
+        Some(Sort::BlockAsc) => store.find_all_votes_chronological().await?,
+        _ => store.find_all_votes().await?,
+    };
 
     let mut votes = Vec::with_capacity(all_votes.len());
     for vote in all_votes {
@@ -0,0 +1,178 @@
+//! Going through the records a page at a time, each page telling where the next one starts.
+
+use std::str::FromStr;
+
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
+use super::bounding::DEFAULT_PAGE;
+use super::planning::{QueryPlan, Strategy};
+use super::{Criteria, Format, Sort};
+use crate::record::Kind;
+use crate::result::Error;
+use crate::store::{Page, Store};
+use crate::Result;
+
+/// How short a cursor may be and still be a key: the block and the position in it.
+const MIN_CURSOR_LEN: usize = 8 + 4;
+
+/// Where the page before ended.
+#[derive(Debug, PartialEq, Eq)]
+pub enum After {
+    /// At the chronological key, as given by the cursor.
+    Key(Vec<u8>),
+    /// At the record with the signature, as the client last saw it.
+    Signature(Signature),
+}
+
+/// Where the page starts, and how long it is.
+#[derive(Debug)]
+pub struct Paging {
+    after: Option<After>,
+    /// How many of the records to pass over first.
+    pub offset: usize,
+    pub limit: usize,
+    /// The program the records should be of, as the pages go by the chronological index instead.
+    pub program: Option<Pubkey>,
+    /// Whether to count the records across all the pages, as it may take as long as a full scan.
+    pub with_total: bool,
+}
+
+impl Paging {
+    /// The paging asked for, if any; pages only go in the on-chain order,
+    /// and only through the records that would be looked up by going through more than one.
+    pub fn of(criteria: &Criteria, plan: &QueryPlan) -> Result<Option<Self>> {
+        if criteria.after.is_none() && criteria.offset.is_none() && criteria.limit.is_none() {
+            return Ok(None);
+        }
+        if criteria.format == Some(Format::Csv) {
+            return Err(Error::BadQueryParam {
+                param: "format".into(),
+                reason: "pages only come as JSON".to_owned(),
+            });
+        }
+        if let Strategy::Signature(_) | Strategy::Signatures(_) = plan.strategy {
+            return Err(Error::BadQueryParam {
+                param: "after".into(),
+                reason: "no pages when looking up by the signature".to_owned(),
+            });
+        }
+        if let Some(sort) = plan.filters.sort.filter(|&sort| sort != Sort::BlockAsc) {
+            return Err(Error::BadQueryParam {
+                param: "sort".into(),
+                reason: format!("no pages when sorting by {sort:?}, only by the block"),
+            });
+        }
+        let program = match plan.strategy {
+            Strategy::Program(program) => Some(program),
+            _ => None,
+        };
+        Ok(Some(Self {
+            after: criteria.after.as_deref().map(decode_after).transpose()?,
+            offset: criteria.offset.unwrap_or(0),
+            limit: criteria.limit.unwrap_or(DEFAULT_PAGE),
+            program,
+            with_total: criteria.with_total.unwrap_or(false),
+        }))
+    }
+
+    /// The chronological key to go on from, looking the record up if given by its signature.
+    pub async fn after_key(&self, store: &Store, kind: Kind) -> Result<Option<Vec<u8>>> {
+        let signature = match &self.after {
+            None => return Ok(None),
+            Some(After::Key(key)) => return Ok(Some(key.clone())),
+            Some(After::Signature(signature)) => signature,
+        };
+        let position = match kind {
+            Kind::Transfer => store.position_of_transfer(signature).await?,
+            _ => store.position_of_vote(signature).await?,
+        };
+        match position {
+            Some(key) => Ok(Some(key)),
+            None => Err(Error::BadQueryParam {
+                param: "after".into(),
+                reason: "no such record to go on from".to_owned(),
+            }),
+        }
+    }
+
+    /// Whether the record is of the program asked for, if any.
+    pub fn admit_program(&self, program_id: &Pubkey) -> bool {
+        self.program.is_none() || self.program.as_ref() == Some(program_id)
+    }
+}
+
+/// A page of records as the clients see it.
+#[derive(Debug, serde::Serialize)]
+pub struct PageBody<T> {
+    pub records: Vec<T>,
+    /// What to pass as `after` for the next page, if there may be one.
+    pub next_cursor: Option<String>,
+    /// How many records there are across all the pages, if asked, and not too many to count in time.
+    pub total: Option<usize>,
+}
+
+impl<T> PageBody<T> {
+    /// Dress up the records of the page, and turn where it ends into a cursor.
+    pub fn of<R>(page: Page<R>, total: Option<usize>) -> Self
+    where
+        T: From<R>,
+    {
+        Self {
+            records: page.records.into_iter().map(T::from).collect(),
+            next_cursor: page.next.as_deref().map(encode_cursor),
+            total,
+        }
+    }
+}
+
+/// The count, or none if it took too long, so that the page goes out without it.
+pub fn total(counted: Result<usize>) -> Result<Option<usize>> {
+    match counted {
+        Ok(count) => Ok(Some(count)),
+        Err(Error::TimedOut(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// The opaque cursor for the chronological key.
+fn encode_cursor(key: &[u8]) -> String {
+    bs58::encode(key).into_string()
+}
+
+/// Where the page before ended, from either the cursor or the signature of its last record,
+/// rejecting what could have come from neither.
+fn decode_after(after: &str) -> Result<After> {
+    match bs58::decode(after).into_vec() {
+        // The keys carry the signature along with the block and the position, so are longer.
+        Ok(bytes) if bytes.len() == 64 => Ok(After::Signature(Signature::from_str(after)?)),
+        Ok(key) if key.len() >= MIN_CURSOR_LEN => Ok(After::Key(key)),
+        _ => Err(Error::BadQueryParam {
+            param: "after".into(),
+            reason: "not a cursor of any page".to_owned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        // Given the key of a record:
+        let key = [&777u64.to_be_bytes()[..], &3u32.to_be_bytes(), &[7; 64]].concat();
+
+        // When it gets turned into a cursor and back:
+        let cursor = encode_cursor(&key);
+
+        // Then it should come back the same, a signature should be taken for one,
+        // and anything made up should be rejected:
+        assert_eq!(decode_after(&cursor).unwrap(), After::Key(key));
+        let signature = Signature::new_unique();
+        let after = decode_after(&signature.to_string()).unwrap();
+        assert_eq!(after, After::Signature(signature));
+        assert!(decode_after("0OIl").is_err());
+        assert!(decode_after("2").is_err());
+    }
+}
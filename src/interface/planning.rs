@@ -0,0 +1,402 @@
+//! Choosing how to look up the records a query asks for.
+//! Each filter set narrows the records down, but only one of them gets looked up by an index;
+//! the rest get checked in memory, so the plan picks the index leaving the fewest to check.
+
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::Instant;
+
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
+use super::{parse_pubkey, parse_signatures, Criteria, Sort, SortField};
+use crate::record::{Kind, StakeDelegation, Transfer, Vote};
+use crate::result::Error;
+use crate::Result;
+
+/// The filters of a query, parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filters {
+    pub block: Option<u64>,
+    /// The first of the blocks, inclusive.
+    pub block_from: Option<u64>,
+    /// The last of the blocks, inclusive.
+    pub block_to: Option<u64>,
+    /// The earliest time of the block, inclusive.
+    pub ts_from: Option<u64>,
+    /// The latest time of the block, inclusive.
+    pub ts_to: Option<u64>,
+    pub to: Option<Pubkey>,
+    pub from: Option<Pubkey>,
+    pub lamports: Option<u64>,
+    pub indexed_since: Option<u64>,
+    pub sort: Option<Sort>,
+}
+
+impl Filters {
+    /// Whether anything but the block is there to check.
+    pub fn beyond_block(&self) -> bool {
+        self.block_from.is_some()
+            || self.block_to.is_some()
+            || self.by_timestamp()
+            || self.to.is_some()
+            || self.from.is_some()
+            || self.lamports.is_some()
+            || self.indexed_since.is_some()
+    }
+
+    /// The blocks the records may come from, all of them unless narrowed down,
+    /// and none of them if the block is out of the range.
+    pub fn blocks(&self) -> RangeInclusive<u64> {
+        let (from, to) = (
+            self.block_from.unwrap_or(0),
+            self.block_to.unwrap_or(u64::MAX),
+        );
+        match self.block {
+            Some(block) => block.max(from)..=block.min(to),
+            None => from..=to,
+        }
+    }
+
+    /// Whether the time of the block is narrowed down at all.
+    pub fn by_timestamp(&self) -> bool {
+        self.ts_from.is_some() || self.ts_to.is_some()
+    }
+
+    /// The times the blocks of the records may have, all of them unless narrowed down.
+    pub fn timestamps(&self) -> RangeInclusive<u64> {
+        self.ts_from.unwrap_or(0)..=self.ts_to.unwrap_or(u64::MAX)
+    }
+
+    /// Whether the vote passes all the filters.
+    pub fn admit_vote(&self, vote: &Vote) -> bool {
+        self.blocks().contains(&vote.block_index)
+            && self.timestamps().contains(&vote.timestamp)
+            && (self.to.is_none() || self.to == Some(vote.target))
+            && (self.from.is_none() || self.from == Some(vote.author))
+            && self.admit_indexed_at(vote.indexed_at)
+    }
+
+    /// Whether the transfer passes all the filters.
+    pub fn admit_transfer(&self, transfer: &Transfer) -> bool {
+        self.blocks().contains(&transfer.block_index)
+            && self.timestamps().contains(&transfer.timestamp)
+            && (self.to.is_none() || self.to == Some(transfer.destination))
+            && (self.from.is_none() || self.from == Some(transfer.source))
+            && (self.lamports.is_none() || self.lamports == Some(transfer.lamports))
+            && self.admit_indexed_at(transfer.indexed_at)
+    }
+
+    /// Whether the stake delegation passes all the filters,
+    /// the vote account standing for `to`, and the stake account for `from`.
+    pub fn admit_stake_delegation(&self, delegation: &StakeDelegation) -> bool {
+        self.blocks().contains(&delegation.block_index)
+            && self.timestamps().contains(&delegation.timestamp)
+            && (self.to.is_none() || self.to == Some(delegation.vote_account))
+            && (self.from.is_none() || self.from == Some(delegation.stake_account))
+            && self.admit_indexed_at(delegation.indexed_at)
+    }
+
+    fn admit_indexed_at(&self, indexed_at: Option<u64>) -> bool {
+        match self.indexed_since {
+            Some(since) => indexed_at.is_some_and(|at| at >= since),
+            None => true,
+        }
+    }
+}
+
+/// How the records get looked up, from the cheapest to the costliest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// By the primary keys at once, whatever else is set.
+    Signatures(Vec<Signature>),
+    /// By the program index, with the rest checked in memory.
+    Program(Pubkey),
+    /// By the account index of the destination, for the transfers with an exact amount,
+    /// as an account receives few transfers of any exact amount.
+    Destination(Pubkey),
+    /// By the account index, intersecting the entries of the author with those of the target,
+    /// for the votes only, as a validator votes for the same few accounts over and over.
+    Pair(Pubkey, Pubkey),
+    /// By the primary key, when nothing else is set.
+    Signature(Signature),
+    /// By the block index, with the accounts checked in memory,
+    /// as there are few records to a block.
+    Block(u64),
+    /// By the block index over a range of blocks, with the rest checked in memory.
+    Blocks(u64, u64),
+    /// By the timestamp index over a range of times, with the rest checked in memory.
+    Timestamps(u64, u64),
+    /// By a scan over all the records.
+    FullScan,
+}
+
+impl Strategy {
+    /// How the strategy shows in the logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Strategy::Signatures(_) => "signatures",
+            Strategy::Program(_) => "program",
+            Strategy::Destination(_) => "destination",
+            Strategy::Pair(..) => "pair",
+            Strategy::Signature(_) => "signature",
+            Strategy::Block(_) => "block",
+            Strategy::Blocks(..) => "blocks",
+            Strategy::Timestamps(..) => "timestamps",
+            Strategy::FullScan => "full_scan",
+        }
+    }
+}
+
+/// What to look up, and how.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryPlan {
+    pub strategy: Strategy,
+    pub filters: Filters,
+}
+
+impl QueryPlan {
+    /// Parse the criteria, and pick the cheapest way to look up the records of the kind.
+    pub fn of(criteria: &Criteria, kind: Kind) -> Result<Self> {
+        let filters = Filters {
+            block: criteria.block,
+            block_from: criteria.block_from,
+            block_to: criteria.block_to,
+            ts_from: criteria.ts_from,
+            ts_to: criteria.ts_to,
+            to: parse_pubkey("to", criteria.to.as_deref())?,
+            from: parse_pubkey("from", criteria.from.as_deref())?,
+            lamports: criteria.lamports,
+            indexed_since: criteria.indexed_since,
+            sort: criteria.sort,
+        };
+        let program = parse_pubkey("program", criteria.program.as_deref())?;
+        if let (Some((SortField::Lamports, _)), true) =
+            (filters.sort.map(Sort::split), kind != Kind::Transfer)
+        {
+            return Err(Error::BadQueryParam {
+                param: "sort".into(),
+                reason: "only the transfers carry lamports".to_owned(),
+            });
+        }
+
+        let nothing_else = filters.block.is_none()
+            && filters.block_from.is_none()
+            && filters.block_to.is_none()
+            && !filters.by_timestamp()
+            && filters.to.is_none()
+            && filters.from.is_none();
+        let strategy = if let Some(signatures) = &criteria.signatures {
+            Strategy::Signatures(parse_signatures(signatures)?)
+        } else if let Some(program) = program {
+            Strategy::Program(program)
+        } else if let (Kind::Transfer, Some(to), Some(_)) = (kind, filters.to, filters.lamports) {
+            Strategy::Destination(to)
+        } else if let (Some(signature), true) = (&criteria.signature, nothing_else) {
+            Strategy::Signature(Signature::from_str(signature)?)
+        } else if let Some(block) = filters.block {
+            Strategy::Block(block)
+        } else if filters.block_from.is_some() || filters.block_to.is_some() {
+            let blocks = filters.blocks();
+            Strategy::Blocks(*blocks.start(), *blocks.end())
+        } else if filters.by_timestamp() {
+            let timestamps = filters.timestamps();
+            Strategy::Timestamps(*timestamps.start(), *timestamps.end())
+        } else if let (Kind::Vote, Some(from), Some(to)) = (kind, filters.from, filters.to) {
+            Strategy::Pair(from, to)
+        } else {
+            Strategy::FullScan
+        };
+        Ok(Self { strategy, filters })
+    }
+}
+
+/// A query under way, to be logged along with how it went once done,
+/// so that the ones going through everything stand out.
+#[derive(Debug)]
+pub struct QueryLog {
+    kind: Kind,
+    strategy: &'static str,
+    filters: Filters,
+    started: Instant,
+}
+
+impl QueryLog {
+    /// Start timing the query as planned.
+    pub fn of(kind: Kind, plan: &QueryPlan) -> Self {
+        Self {
+            kind,
+            strategy: plan.strategy.name(),
+            filters: plan.filters.clone(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Tell how many records the query has matched, and how long that took.
+    pub fn finish(self, matched: usize) {
+        tracing::info!(
+            kind = ?self.kind,
+            strategy = self.strategy,
+            filters = ?self.filters,
+            matched,
+            elapsed = ?self.started.elapsed(),
+            "Query done"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn criteria() -> Criteria {
+        Criteria {
+            block: None,
+            block_from: None,
+            block_to: None,
+            ts_from: None,
+            ts_to: None,
+            signature: None,
+            signatures: None,
+            to: None,
+            from: None,
+            program: None,
+            lamports: None,
+            indexed_since: None,
+            sort: None,
+            after: None,
+            offset: None,
+            limit: None,
+            with_total: None,
+            format: None,
+        }
+    }
+
+    #[test]
+    fn cheapest_index_picked() {
+        // Given the criteria narrowing down by the block and the destination:
+        let to = Pubkey::new_unique();
+        let by_block = Criteria {
+            block: Some(777),
+            to: Some(to.to_string()),
+            ..criteria()
+        };
+
+        // When the exact amount is given as well:
+        let with_lamports = Criteria {
+            lamports: Some(100),
+            ..by_block
+        };
+
+        // Then the transfers should go by the destination, but the votes should not:
+        let plan = QueryPlan::of(&with_lamports, Kind::Transfer).unwrap();
+        assert_eq!(plan.strategy, Strategy::Destination(to));
+        let plan = QueryPlan::of(&with_lamports, Kind::Vote).unwrap();
+        assert_eq!(plan.strategy, Strategy::Block(777));
+        assert_eq!(plan.filters.to, Some(to));
+    }
+
+    #[test]
+    fn signature_alone_by_primary_key() {
+        // Given a signature, alone and with a block:
+        let signature = Signature::new_unique();
+        let alone = Criteria {
+            signature: Some(signature.to_string()),
+            ..criteria()
+        };
+        let with_block = Criteria {
+            block: Some(777),
+            signature: Some(signature.to_string()),
+            ..criteria()
+        };
+
+        // When we plan them, then only the lone one should go by the primary key:
+        let plan = QueryPlan::of(&alone, Kind::Vote).unwrap();
+        assert_eq!(plan.strategy, Strategy::Signature(signature));
+        let plan = QueryPlan::of(&with_block, Kind::Vote).unwrap();
+        assert_eq!(plan.strategy, Strategy::Block(777));
+
+        // And the author along with the target should go by both, unless there is a block:
+        let (from, to) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let pair = Criteria {
+            from: Some(from.to_string()),
+            to: Some(to.to_string()),
+            ..criteria()
+        };
+        let plan = QueryPlan::of(&pair, Kind::Vote).unwrap();
+        assert_eq!(plan.strategy, Strategy::Pair(from, to));
+        let plan = QueryPlan::of(&pair, Kind::Transfer).unwrap();
+        assert_eq!(plan.strategy, Strategy::FullScan);
+        let with_block = Criteria {
+            block: Some(777),
+            ..pair
+        };
+        let plan = QueryPlan::of(&with_block, Kind::Vote).unwrap();
+        assert_eq!(plan.strategy, Strategy::Block(777));
+
+        // And nothing at all should take a full scan:
+        let plan = QueryPlan::of(&criteria(), Kind::Vote).unwrap();
+        assert_eq!(plan.strategy, Strategy::FullScan);
+    }
+
+    #[test]
+    fn block_ranges_by_block_index() {
+        // Given a range of blocks, open-ended, and along with a block:
+        let range = Criteria {
+            block_from: Some(777),
+            block_to: Some(779),
+            ..criteria()
+        };
+        let open = Criteria {
+            block_from: Some(777),
+            ..criteria()
+        };
+        let with_block = Criteria {
+            block: Some(780),
+            ..range
+        };
+
+        // When we plan them, then the range should go by the block index, up to the end if open:
+        let plan = QueryPlan::of(&range, Kind::Vote).unwrap();
+        assert_eq!(plan.strategy, Strategy::Blocks(777, 779));
+        let plan = QueryPlan::of(&open, Kind::Transfer).unwrap();
+        assert_eq!(plan.strategy, Strategy::Blocks(777, u64::MAX));
+
+        // And a block out of the range should leave no blocks to look at:
+        let plan = QueryPlan::of(&with_block, Kind::Vote).unwrap();
+        assert_eq!(plan.strategy, Strategy::Block(780));
+        assert!(plan.filters.blocks().is_empty());
+    }
+
+    #[test]
+    fn time_ranges_by_timestamp_index() {
+        // Given a range of times, open-ended, and along with a range of blocks:
+        let range = Criteria {
+            ts_from: Some(1_700_000_000),
+            ts_to: Some(1_700_000_060),
+            ..criteria()
+        };
+        let open = Criteria {
+            ts_to: Some(1_700_000_060),
+            ..criteria()
+        };
+        let with_blocks = Criteria {
+            block_from: Some(777),
+            ..range
+        };
+
+        // When we plan them, then the range should go by the timestamp index, from the start if open:
+        let plan = QueryPlan::of(&range, Kind::Vote).unwrap();
+        assert_eq!(
+            plan.strategy,
+            Strategy::Timestamps(1_700_000_000, 1_700_000_060)
+        );
+        let plan = QueryPlan::of(&open, Kind::Transfer).unwrap();
+        assert_eq!(plan.strategy, Strategy::Timestamps(0, 1_700_000_060));
+
+        // And the blocks should still come first, with the times checked in memory:
+        let plan = QueryPlan::of(&with_blocks, Kind::Vote).unwrap();
+        assert_eq!(plan.strategy, Strategy::Blocks(777, u64::MAX));
+        assert!(plan.filters.beyond_block());
+    }
+}
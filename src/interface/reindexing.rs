@@ -0,0 +1,98 @@
+//! Extracting a block anew on demand, outside of the continuous extraction.
+
+use std::sync::Arc;
+
+use tokio::runtime::Handle;
+
+use crate::channel::{channel, Extracted};
+use crate::extraction::{extract_all_transactions_in_block, ClockSkew, Connection};
+use crate::metrics::Metrics;
+use crate::record::{Kind, Record};
+use crate::store::{stamp, Store};
+use crate::Result;
+
+/// What it takes to extract a block on demand.
+#[derive(Clone)]
+pub struct Reindexer {
+    pub connection: Connection,
+    pub metrics: Arc<Metrics>,
+    /// What kinds of records to keep, the same as for the continuous extraction.
+    pub kinds: Vec<Kind>,
+    /// How far ahead of the wall clock a block time may be, the same as for the continuous extraction.
+    pub clock_skew: ClockSkew,
+    /// The runtime of the continuous extraction, as the blocking RPC client
+    /// cannot run on the single-threaded ones serving the requests.
+    pub runtime: Handle,
+}
+
+/// How a block has been extracted anew.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Reindexed {
+    pub block: u64,
+    pub votes: usize,
+    pub transfers: usize,
+    pub rewards: usize,
+    pub token_transfers: usize,
+    pub stake_delegations: usize,
+}
+
+/// Forget everything about the block, then extract it again, storing the records directly.
+pub async fn reindex_block(
+    store: &Arc<Store>,
+    reindexer: &Reindexer,
+    block: u64,
+) -> Result<Reindexed> {
+    let (votes, transfers, rewards, token_transfers, stake_delegations) =
+        store.clear_block(block).await?;
+    tracing::info!(
+        "Cleared {votes} votes, {transfers} transfers, {rewards} rewards, \
+         {token_transfers} token transfers, and {stake_delegations} stake delegations \
+         of block #{block}"
+    );
+
+    let (tx, mut rx) = channel(1, None);
+    let connection = reindexer.connection.clone();
+    let metrics = reindexer.metrics.clone();
+    let kinds = reindexer.kinds.clone();
+    let clock_skew = reindexer.clock_skew;
+    let extractor = reindexer.runtime.spawn(async move {
+        let client = connection.client()?;
+        extract_all_transactions_in_block(&tx, &client, &metrics, &kinds, &clock_skew, block).await
+    });
+
+    let mut reindexed = Reindexed {
+        block,
+        votes: 0,
+        transfers: 0,
+        rewards: 0,
+        token_transfers: 0,
+        stake_delegations: 0,
+    };
+    while let Some(extracted) = rx.recv().await {
+        let mut records = match extracted {
+            Extracted::Records(records) => records,
+            Extracted::Skipped(block) => {
+                store.mark_skipped(block).await?;
+                continue;
+            }
+            Extracted::Empty(block) => {
+                store.mark_empty(block).await?;
+                continue;
+            }
+        };
+        stamp(&mut records);
+        store.save_block(&records).await?;
+        for record in &records {
+            match record {
+                Record::Vote(_) => reindexed.votes += 1,
+                Record::Transfer(_) => reindexed.transfers += 1,
+                Record::Reward(_) => reindexed.rewards += 1,
+                Record::TokenTransfer(_) => reindexed.token_transfers += 1,
+                Record::StakeDelegation(_) => reindexed.stake_delegations += 1,
+            }
+        }
+    }
+
+    extractor.await.map_err(std::io::Error::other)??;
+    Ok(reindexed)
+}
@@ -0,0 +1,77 @@
+//! Sending the records out one per line as they get dressed up,
+//! so that the client may start on them before the last one is ready.
+
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::HttpRequest;
+use futures::stream::{self, Stream};
+
+/// One JSON object per line.
+pub const NDJSON: &str = "application/x-ndjson";
+
+/// Whether the client asked for the records one per line rather than in an array.
+pub fn wants_lines(request: &HttpRequest) -> bool {
+    accepts(request, NDJSON)
+}
+
+/// Whether the media type is among those in `Accept`, other than with a weight of zero.
+pub fn accepts(request: &HttpRequest, media_type: &str) -> bool {
+    let Some(accept) = request.headers().get(header::ACCEPT) else {
+        return false;
+    };
+    let Ok(accept) = accept.to_str() else {
+        return false;
+    };
+    accept.split(',').any(|media| {
+        let mut parts = media.split(';').map(str::trim);
+        let named = parts.next() == Some(media_type);
+        // With `q=0` the client tells it does not want the type at all:
+        let refused = parts
+            .filter_map(|param| param.strip_prefix("q="))
+            .any(|weight| weight.parse::<f32>().is_ok_and(|weight| weight == 0.0));
+        named && !refused
+    })
+}
+
+/// The records, each written out on a line of its own only once it is its turn.
+pub fn lines<I>(records: I) -> impl Stream<Item = Result<Bytes, serde_json::Error>>
+where
+    I: IntoIterator,
+    I::Item: serde::Serialize,
+{
+    stream::iter(records.into_iter().map(|record| {
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        Ok(Bytes::from(line))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn weights_of_zero_refused() {
+        // Given the requests accepting the lines in a few ways:
+        let accepting = |accept: &str| {
+            let request = TestRequest::get()
+                .insert_header((header::ACCEPT, accept))
+                .to_http_request();
+            accepts(&request, NDJSON)
+        };
+
+        // When the lines are among the types, whatever the weight but zero, then they should be taken:
+        assert!(accepting("application/x-ndjson"));
+        assert!(accepting("application/json, application/x-ndjson;q=0.5"));
+        assert!(accepting("application/x-ndjson; q=1"));
+
+        // When they are not there, or weighed at zero, then they should not be:
+        assert!(!accepting("application/json"));
+        assert!(!accepting("application/x-ndjson;q=0"));
+        assert!(!accepting(
+            "application/json, application/x-ndjson; q=0.000"
+        ));
+    }
+}
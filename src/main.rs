@@ -1,36 +1,140 @@
 #![doc = include_str!("../README.md")]
 
 use std::sync::Arc;
+use std::time::Duration;
 
-use clap::Parser;
-use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt as _, EnvFilter};
 
 mod args;
-use args::Args;
+use args::{Args, Command, LogFormat};
+
+mod channel;
+use channel::{channel, staged_channel};
+
+mod compaction;
+use compaction::compact_periodically;
+
+mod completions;
+
+mod epochs;
+
+mod event_log;
+use event_log::{EventLog, Rotation};
+
+mod health;
+use health::Health;
+
+mod metrics;
+use metrics::Metrics;
 
 mod record;
 
 mod result;
 use result::Result;
 
+mod pool;
+
+mod throttle;
+
 mod store;
-use store::{store_all_records_from, Store};
+use store::{store_all_records_from, FlushCadence, Store, StoreConfig, Tuning};
 
 mod extraction;
-use extraction::extract_continuously;
+use extraction::{extract_continuously, ClockSkew, Connection, Token};
+
+mod verification;
+use verification::verify_sample;
 
 mod interface;
 use interface::serve_forever;
 
+/// Stop everything on SIGINT or SIGTERM, whichever comes first,
+/// as supervisors and container runtimes send the latter.
+async fn stop_on_signal(stop: CancellationToken) {
+    let interrupted = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for SIGINT: {e:?}");
+            std::future::pending::<()>().await;
+        }
+    };
+    let terminated = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {e:?}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    tokio::select! {
+        _ = interrupted => tracing::info!("Received SIGINT"),
+        _ = terminated => tracing::info!("Received SIGTERM"),
+    }
+    stop.cancel();
+}
+
+/// Read an extra root certificate, failing right away rather than on the first request.
+fn load_ca_cert(path: &std::path::Path) -> Result<reqwest::Certificate> {
+    let bad = |reason: String| result::Error::Config(format!("`{}`: {reason}", path.display()));
+    let pem = std::fs::read(path).map_err(|e| bad(e.to_string()))?;
+    // An empty bundle would pass for a valid one, trusting nothing extra.
+    const BEGIN: &[u8] = b"-----BEGIN CERTIFICATE-----";
+    if !pem.windows(BEGIN.len()).any(|window| window == BEGIN) {
+        return Err(bad("no certificate in it".to_owned()));
+    }
+    reqwest::Certificate::from_pem(&pem).map_err(|e| bad(e.to_string()))
+}
+
+/// Set up the export of the spans to the OpenTelemetry collector at the given endpoint, over gRPC.
+fn otel_tracer(endpoint: &str) -> Result<opentelemetry_sdk::trace::Tracer> {
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::new_exporter()
+        .tonic()
+        .with_endpoint(endpoint);
+    let resource = opentelemetry_sdk::Resource::new([KeyValue::new("service.name", "surf")]);
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(opentelemetry_sdk::trace::config().with_resource(resource))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)
+        .map_err(|e| result::Error::Config(format!("bad `otel_endpoint`: {e}")))
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let args = Args::load()?;
+    let backfill = args.backfill()?;
+    let kinds = args.kinds();
+    let clock_skew = ClockSkew {
+        tolerance: Duration::from_secs(args.max_clock_skew),
+        clamp: args.clamp_block_times,
+    };
 
+    let (text, json) = match args.log_format {
+        LogFormat::Text => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => {
+            let json = tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(true);
+            (None, Some(json))
+        }
+    };
+    // Nothing leaves the process unless asked for, so there is nothing to pay for by default.
+    let otel = args.otel_endpoint.as_deref().map(otel_tracer).transpose()?;
+    let otel = otel.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
+        .with(text)
+        .with(json)
+        .with(otel)
         .with(
             EnvFilter::builder()
                 .with_default_directive(LevelFilter::INFO.into())
@@ -42,11 +146,124 @@ async fn main() -> Result<()> {
 
     // The database that gets filled in the background
     // and that the web interface queries:
-    let store = Arc::new(Store::with_path(args.store_path).await?);
+    let cadence = FlushCadence {
+        records: args.flush_every,
+        interval: Duration::from_secs(args.flush_interval),
+    };
+    let tuning = Tuning {
+        readahead: args.readahead_kb << 10,
+        tailing: args.tailing_scans,
+        sync_writes: args.sync_writes,
+    };
+    let metrics = Arc::new(Metrics::new()?);
+    let config = StoreConfig {
+        write_buffer: args.db_write_buffer_mb.map(|mb| mb << 20),
+        block_cache: args.db_block_cache_mb.map(|mb| mb << 20),
+        max_open_files: args.db_max_open_files,
+        compression: args.db_compression,
+    };
+    let store = match args.command {
+        Some(Command::Migrate) => Store::with_path_migrated(args.store_path, &config).await?,
+        _ => Store::with_path(args.store_path, &config).await?,
+    };
+    let mut store = store
+        .with_metrics(metrics.clone())
+        .with_strict_reads(args.strict)
+        .with_tuning(tuning)
+        .with_flush_cadence(cadence);
+    if let Some(keys) = args.primary_key {
+        store = store.with_primary_key(keys)?;
+    }
+    if let Some(ref blocks) = backfill {
+        store = store.with_backfill(blocks.clone())?;
+    }
+    let store = Arc::new(store);
+
+    if args.rpc_insecure {
+        tracing::warn!(
+            "NOT verifying the certificate of the RPC node; anyone in between can forge the blocks"
+        );
+    }
+    let ca_cert = args.rpc_ca_cert.as_deref().map(load_ca_cert).transpose()?;
+    let connection = Connection {
+        urls: args.url.clone(),
+        quarantine: Duration::from_secs(args.rpc_quarantine_secs),
+        timeout: Duration::from_secs(args.rpc_timeout),
+        headers: args.rpc_headers.clone(),
+        user_agent: args
+            .rpc_user_agent
+            .clone()
+            .unwrap_or_else(|| concat!("surf/", env!("CARGO_PKG_VERSION")).to_owned()),
+        token: args.rpc_token.clone().map(|secret| Token {
+            secret,
+            scheme: args.rpc_token_scheme,
+        }),
+        ca_cert,
+        insecure: args.rpc_insecure,
+    };
+    // Making sure the certificates are fine before anything else.
+    connection.client()?;
+
+    if let Some(command) = args.command {
+        match command {
+            Command::Checkpoint { out } => {
+                store.checkpoint(&out).await?;
+                tracing::info!("Written a checkpoint to `{out}`");
+            }
+            Command::Verify { sample } => {
+                let report = verify_sample(&store, &connection, sample).await?;
+                tracing::info!(
+                    "Checked {} records, {} mismatched, {} failed to come from the cluster",
+                    report.checked,
+                    report.mismatched,
+                    report.failed
+                );
+                if report.mismatched > 0 {
+                    return Err(result::Error::Mismatch(report.mismatched));
+                }
+            }
+            Command::Export { out } => {
+                store.export_to_json_file(&out).await?;
+                tracing::info!("Exported the records to `{}`", out.display());
+            }
+            Command::Import { from } => {
+                let report = store.import_from_json_file(&from).await?;
+                tracing::info!(
+                    "Imported {} records from `{}`, skipped {} lines",
+                    report.imported,
+                    from.display(),
+                    report.skipped
+                );
+            }
+            Command::Migrate => {
+                tracing::info!("The database is up to date");
+            }
+            Command::Check => {
+                let report = store.check_integrity()?;
+                tracing::info!(
+                    "Found {} dangling index entries, {} orphaned records",
+                    report.dangling_indexes,
+                    report.orphaned_records
+                );
+                if !report.is_consistent() {
+                    return Err(result::Error::Inconsistent {
+                        dangling: report.dangling_indexes,
+                        orphaned: report.orphaned_records,
+                    });
+                }
+            }
+        }
+        return Ok(());
+    }
 
     let stop = CancellationToken::new();
+    let health = Arc::new(Health::default());
 
-    let (tx, rx) = mpsc::channel(1);
+    let (tx, rx) = if args.staging && !args.dry && !args.parse_only {
+        staged_channel(args.channel_capacity, Some(metrics.clone()), store.clone())?
+    } else {
+        channel(args.channel_capacity, Some(metrics.clone()))
+    };
 
     let last_known_block = store.last_known_block().await;
     tracing::trace!("Last known block index: {:?}", last_known_block);
@@ -56,35 +273,158 @@ async fn main() -> Result<()> {
         // The background task that reads the blocks,
         // forms the relevant records from it, and sends those records
         // by the given channel:
-        let extractor = tokio::spawn(extract_continuously(
-            tx,
-            stop.clone(),
-            args.url.to_owned(),
-            last_known_block,
-        ));
+        let settings = extraction::Settings {
+            max_block_retries: args.max_block_retries,
+            max_retries: args.max_retries,
+            backoff: extraction::Backoff {
+                base: Duration::from_millis(args.retry_base_ms),
+                max: Duration::from_millis(args.retry_max_ms),
+            },
+            lag_threshold: args.lag_threshold,
+            catch_up_threshold: args.catch_up_threshold,
+            confirmation_depth: args.confirmation_depth,
+            poll_interval: Duration::from_millis(args.poll_interval_ms),
+            start_at: args.start_at,
+            kinds: kinds.clone(),
+            clock_skew,
+            leader_schedule: args.leader_schedule,
+            concurrency: args.concurrency,
+            fail_on_gap: args.fail_on_gap,
+            stop_once_given_up: args.no_serve,
+        };
+        let extractor = match backfill {
+            Some(ref blocks) => {
+                // Picking up where the same backfill has left off, if anywhere:
+                let from = last_known_block.map_or(*blocks.start(), |block| block + 1);
+                tokio::spawn(extraction::backfill(
+                    tx,
+                    stop.clone(),
+                    connection.clone(),
+                    settings,
+                    from..=*blocks.end(),
+                    health.clone(),
+                    metrics.clone(),
+                ))
+            }
+            None => tokio::spawn(extract_continuously(
+                tx,
+                stop.clone(),
+                connection.clone(),
+                settings,
+                last_known_block,
+                health.clone(),
+                metrics.clone(),
+            )),
+        };
 
         // The background task that reads the records sent,
         // and stores them in the database:
-        let committer = tokio::spawn(store_all_records_from(rx, store.clone(), stop.clone()));
+        let event_log = match args.event_log {
+            Some(ref path) if !args.parse_only => {
+                let rotation = Rotation {
+                    max_bytes: args.event_log_max_mb << 20,
+                    sync_interval: Duration::from_millis(args.event_log_sync_ms),
+                };
+                Some(EventLog::open(path.clone(), rotation)?)
+            }
+            _ => None,
+        };
+        let committer = tokio::spawn(store_all_records_from(
+            rx,
+            store.clone(),
+            event_log,
+            stop.clone(),
+            args.parse_only,
+        ));
 
         tasks.push(extractor);
         tasks.push(committer);
     }
 
-    // The web interface:
-    serve_forever((args.host, args.port), store.clone(), stop.clone()).await?;
+    if let Some(interval) = args.compact_interval {
+        let interval = Duration::from_secs(interval);
+        tasks.push(tokio::spawn(compact_periodically(
+            store.clone(),
+            interval,
+            stop.clone(),
+        )));
+    }
 
-    // Assuming `actix-web` has already handled the SIGINT.
-    stop.cancel();
-    tracing::info!("Received SIGINT; waiting for the network to finish...");
+    tokio::spawn(stop_on_signal(stop.clone()));
+
+    if backfill.is_some() {
+        tracing::info!(
+            "Not serving while backfilling; running until done, or until SIGINT or SIGTERM..."
+        );
+        stop.cancelled().await;
+    } else if args.no_serve {
+        tracing::info!("Not serving; running until SIGINT or SIGTERM...");
+        stop.cancelled().await;
+    } else {
+        // The web interface:
+        let settings = interface::Settings {
+            admin: args.admin,
+            debug_endpoints: args.debug_endpoints,
+            workers: args.workers,
+            backlog: args.backlog,
+            ready_file: args.ready_file,
+            kinds,
+            clock_skew,
+            compress: args.compress_responses && !args.no_compress,
+            max_response_records: args.max_response_records,
+            max_lookup_signatures: args.max_lookup_signatures,
+            cors_origins: args.cors_origins,
+            checkpoint_dir: args.checkpoint_dir,
+        };
+        serve_forever(
+            (args.host, args.port),
+            store.clone(),
+            health.clone(),
+            metrics.clone(),
+            connection,
+            settings,
+            stop.clone(),
+        )
+        .await?;
 
-    for task in tasks.into_iter() {
-        let awaited = task.await;
-        if let Err(e) = awaited {
-            tracing::error!("Failed to rejoin a background task: {e:?}");
+        // In case the server has stopped for any other reason.
+        stop.cancel();
+    }
+    tracing::info!("Stopping; waiting for the network to finish...");
+
+    // A task stuck in a blocking call never gets to see the cancellation,
+    // so give each of them a while, and no longer. One after the other, the extractor first,
+    // so that the committer gets the whole while for what is left once nothing more comes.
+    let timeout = Duration::from_secs(args.shutdown_timeout);
+    let mut abandoned = false;
+    for mut task in tasks.into_iter() {
+        match tokio::time::timeout(timeout, &mut task).await {
+            Ok(Err(e)) => tracing::error!("Failed to rejoin a background task: {e:?}"),
+            Ok(Ok(())) => {}
+            Err(_) => {
+                tracing::error!(
+                    "A background task is still running past the shutdown timeout; abandoning it"
+                );
+                task.abort();
+                abandoned = true;
+            }
         }
     }
 
+    if args.otel_endpoint.is_some() {
+        // Sending off whatever spans are still batched up.
+        opentelemetry::global::shutdown_tracer_provider();
+    }
+
+    if abandoned {
+        // The runtime would wait for the stuck thread on the way out, so not going that way.
+        tracing::warn!("Stopped, leaving the abandoned tasks behind");
+        std::process::exit(1);
+    }
+    // Having given up, the extraction may have stopped the rest, which is no reason to exit cleanly.
+    if let Some(reason) = health.unready_reason() {
+        return Err(result::Error::ExtractionFailed(reason));
+    }
     tracing::info!("Stopped");
 
     Ok(())
@@ -0,0 +1,194 @@
+//! How long things take and how much is pending, for the operators to scrape.
+
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+use crate::Result;
+
+/// Bucket boundaries in seconds, from a snappy RPC call to a stuck one.
+const LATENCY_BUCKETS: &[f64] = &[0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Bucket boundaries in seconds, from a write landing in the memtable to one held up by a stall.
+const WRITE_LATENCY_BUCKETS: &[f64] = &[
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.1, 1.0,
+];
+
+/// The distributions and the counts shared between the extraction and the web interface.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    /// Wall time of each `getBlock` call.
+    pub rpc_get_block: Histogram,
+    /// Wall time of extracting a whole block, the RPC calls included.
+    pub block_processing: Histogram,
+    /// How many records await the committer.
+    pub channel_depth: IntGauge,
+    /// How many records may await the committer before the extraction stalls.
+    pub channel_capacity: IntGauge,
+    /// How many blocks claimed a time too far ahead of the wall clock.
+    pub future_block_times: IntCounter,
+    /// How many blocks the extraction has gone through.
+    pub blocks_processed: IntCounter,
+    /// How many records have been written down, by their `kind`.
+    pub records_stored: IntCounterVec,
+    /// How many calls to the RPC node have failed.
+    pub rpc_errors: IntCounter,
+    /// Wall time of each write of the committer.
+    pub db_write: Histogram,
+    /// How many requests the web interface has answered, by their `method`, `path`, and `status`.
+    pub api_requests: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new();
+        let rpc_get_block = histogram(
+            &registry,
+            "surf_rpc_get_block_seconds",
+            "Wall time of each getBlock call",
+            LATENCY_BUCKETS,
+        )?;
+        let block_processing = histogram(
+            &registry,
+            "surf_block_processing_seconds",
+            "Wall time of extracting a whole block",
+            LATENCY_BUCKETS,
+        )?;
+        let channel_depth = gauge(
+            &registry,
+            "surf_channel_depth",
+            "How many extracted blocks await the committer",
+        )?;
+        let channel_capacity = gauge(
+            &registry,
+            "surf_channel_capacity",
+            "How many extracted blocks may await the committer",
+        )?;
+        let future_block_times = counter(
+            &registry,
+            "surf_future_block_times_total",
+            "How many blocks claimed a time too far ahead of the wall clock",
+        )?;
+        let blocks_processed = counter(
+            &registry,
+            "surf_blocks_processed_total",
+            "How many blocks the extraction has gone through",
+        )?;
+        let records_stored = counter_vec(
+            &registry,
+            "surf_records_stored_total",
+            "How many records have been written down",
+            &["kind"],
+        )?;
+        let rpc_errors = counter(
+            &registry,
+            "surf_rpc_errors_total",
+            "How many calls to the RPC node have failed",
+        )?;
+        let db_write = histogram(
+            &registry,
+            "surf_db_write_latency_seconds",
+            "Wall time of each write of the committer",
+            WRITE_LATENCY_BUCKETS,
+        )?;
+        let api_requests = counter_vec(
+            &registry,
+            "surf_api_requests_total",
+            "How many requests the web interface has answered",
+            &["method", "path", "status"],
+        )?;
+        Ok(Self {
+            registry,
+            rpc_get_block,
+            block_processing,
+            channel_depth,
+            channel_capacity,
+            future_block_times,
+            blocks_processed,
+            records_stored,
+            rpc_errors,
+            db_write,
+            api_requests,
+        })
+    }
+
+    /// Everything in the Prometheus text format.
+    pub fn render(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+fn histogram(registry: &Registry, name: &str, help: &str, buckets: &[f64]) -> Result<Histogram> {
+    let opts = HistogramOpts::new(name, help).buckets(buckets.to_vec());
+    let histogram = Histogram::with_opts(opts)?;
+    registry.register(Box::new(histogram.clone()))?;
+    Ok(histogram)
+}
+
+fn gauge(registry: &Registry, name: &str, help: &str) -> Result<IntGauge> {
+    let gauge = IntGauge::new(name, help)?;
+    registry.register(Box::new(gauge.clone()))?;
+    Ok(gauge)
+}
+
+fn counter(registry: &Registry, name: &str, help: &str) -> Result<IntCounter> {
+    let counter = IntCounter::new(name, help)?;
+    registry.register(Box::new(counter.clone()))?;
+    Ok(counter)
+}
+
+fn counter_vec(
+    registry: &Registry,
+    name: &str,
+    help: &str,
+    labels: &[&str],
+) -> Result<IntCounterVec> {
+    let counter = IntCounterVec::new(Opts::new(name, help), labels)?;
+    registry.register(Box::new(counter.clone()))?;
+    Ok(counter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms_rendered() {
+        // Given some observations:
+        let metrics = Metrics::new().unwrap();
+        metrics.rpc_get_block.observe(0.3);
+        metrics.block_processing.observe(1.2);
+
+        // When we render them:
+        let rendered = metrics.render().unwrap();
+
+        // Then the buckets should be there:
+        assert!(rendered.contains("surf_rpc_get_block_seconds_bucket{le=\"0.5\"} 1"));
+        assert!(rendered.contains("surf_block_processing_seconds_count 1"));
+    }
+
+    #[test]
+    fn counters_labeled() {
+        // Given records of a couple of kinds written down:
+        let metrics = Metrics::new().unwrap();
+        metrics
+            .records_stored
+            .with_label_values(&["vote"])
+            .inc_by(3);
+        metrics
+            .records_stored
+            .with_label_values(&["transfer"])
+            .inc();
+
+        // When we render them:
+        let rendered = metrics.render().unwrap();
+
+        // Then each kind should be counted on its own:
+        assert!(rendered.contains("surf_records_stored_total{kind=\"vote\"} 3"));
+        assert!(rendered.contains("surf_records_stored_total{kind=\"transfer\"} 1"));
+    }
+}
@@ -0,0 +1,285 @@
+//! Spreading the requests over the RPC nodes, and steering clear of the failing ones.
+//! A node failing a few times in a row gets set aside for a while,
+//! and the requests go to the others in turn meanwhile.
+//! A node yet to catch up on a block only gets the request passed on to the next one.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_request::{RpcError, RpcRequest};
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+
+use crate::result;
+
+/// How many failures in a row get a node set aside.
+const FAILURES_BEFORE_QUARANTINE: u32 = 3;
+
+/// A node, along with how it has been doing lately.
+struct Endpoint {
+    sender: Box<dyn RpcSender + Send + Sync>,
+    /// Where the node is, fit for the logs.
+    origin: String,
+    health: Mutex<EndpointHealth>,
+}
+
+#[derive(Debug, Default)]
+struct EndpointHealth {
+    failures_in_a_row: u32,
+    /// Until when the node is set aside, if it is.
+    quarantined_until: Option<Instant>,
+}
+
+impl Endpoint {
+    fn is_available(&self, now: Instant) -> bool {
+        let health = self.health.lock().unwrap();
+        !matches!(health.quarantined_until, Some(until) if until > now)
+    }
+
+    /// Pass the request on, keeping track of whether the node is doing fine.
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+        quarantine: Duration,
+    ) -> Result<serde_json::Value, ClientError> {
+        let result = self.sender.send(request, params).await;
+        let mut health = self.health.lock().unwrap();
+        match result {
+            Err(ref e) if is_node_failure(e) => {
+                health.failures_in_a_row += 1;
+                if health.failures_in_a_row >= FAILURES_BEFORE_QUARANTINE {
+                    tracing::warn!(
+                        "Setting `{}` aside for {quarantine:?} after {} failures in a row",
+                        self.origin,
+                        health.failures_in_a_row
+                    );
+                    health.failures_in_a_row = 0;
+                    health.quarantined_until = Some(Instant::now() + quarantine);
+                }
+            }
+            _ => *health = EndpointHealth::default(),
+        }
+        result
+    }
+}
+
+/// Whether the error is down to the node rather than to the request:
+/// the node being unreachable, timing out, or answering with an error status.
+fn is_node_failure(e: &ClientError) -> bool {
+    matches!(e.kind, ClientErrorKind::Reqwest(_) | ClientErrorKind::Io(_))
+}
+
+/// Whether the node does not have the block yet, lagging behind the others,
+/// so that another node may well have it.
+pub fn is_behind(e: &ClientError) -> bool {
+    // The block not available, or its status not available yet.
+    matches!(
+        e.kind,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            code: -32004 | -32014,
+            ..
+        })
+    )
+}
+
+/// The nodes to talk to, taken in turns, failing over to the next one should one fail.
+pub struct RpcClientPool {
+    endpoints: Vec<Endpoint>,
+    /// Which node to try first next time.
+    next: AtomicUsize,
+    /// How long a failing node gets set aside for.
+    quarantine: Duration,
+}
+
+impl RpcClientPool {
+    /// Pool the given nodes, each along with where it is, fit for the logs; there must be at least one.
+    pub fn new(
+        senders: Vec<(String, Box<dyn RpcSender + Send + Sync>)>,
+        quarantine: Duration,
+    ) -> Self {
+        let endpoints = senders
+            .into_iter()
+            .map(|(origin, sender)| Endpoint {
+                sender,
+                origin,
+                health: Mutex::default(),
+            })
+            .collect();
+        Self {
+            endpoints,
+            next: AtomicUsize::new(0),
+            quarantine,
+        }
+    }
+
+    /// The order to try the nodes in this time: the available ones in turn,
+    /// then the ones set aside, so that the request still goes somewhere should all of them be.
+    fn order(&self) -> Vec<&Endpoint> {
+        let now = Instant::now();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let count = self.endpoints.len();
+        let (available, set_aside): (Vec<_>, Vec<_>) = (0..count)
+            .map(|i| &self.endpoints[(start + i) % count])
+            .partition(|endpoint| endpoint.is_available(now));
+        available.into_iter().chain(set_aside).collect()
+    }
+}
+
+#[async_trait]
+impl RpcSender for RpcClientPool {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, ClientError> {
+        let order = self.order();
+        let (last, others) = order.split_last().expect("a pool of no nodes");
+        for endpoint in others {
+            match endpoint
+                .send(request, params.clone(), self.quarantine)
+                .await
+            {
+                Err(e) if is_node_failure(&e) => {
+                    let e = result::redacted(e);
+                    tracing::warn!("`{}` failed, trying the next node: {e}", endpoint.origin);
+                }
+                Err(e) if is_behind(&e) => {
+                    tracing::debug!("`{}` is behind, trying the next node: {e}", endpoint.origin);
+                }
+                result => return result,
+            }
+        }
+        last.send(request, params, self.quarantine).await
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        let mut total = RpcTransportStats::default();
+        for endpoint in &self.endpoints {
+            let stats = endpoint.sender.get_transport_stats();
+            total.request_count += stats.request_count;
+            total.elapsed_time += stats.elapsed_time;
+            total.rate_limited_time += stats.rate_limited_time;
+        }
+        total
+    }
+
+    fn url(&self) -> String {
+        let origins: Vec<_> = self.endpoints.iter().map(|e| e.origin.as_str()).collect();
+        origins.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use solana_client::mock_sender::MockSender;
+    use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+
+    /// A node that is down, counting how many times it gets asked anyway.
+    #[derive(Default)]
+    struct Down {
+        asked: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl RpcSender for Down {
+        async fn send(
+            &self,
+            _request: RpcRequest,
+            _params: serde_json::Value,
+        ) -> Result<serde_json::Value, ClientError> {
+            self.asked.fetch_add(1, Ordering::Relaxed);
+            Err(std::io::Error::other("connection refused").into())
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "down".to_owned()
+        }
+    }
+
+    /// A node lagging behind the others, without any block, counting how many times it gets asked.
+    #[derive(Default)]
+    struct Lagging {
+        asked: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl RpcSender for Lagging {
+        async fn send(
+            &self,
+            _request: RpcRequest,
+            _params: serde_json::Value,
+        ) -> Result<serde_json::Value, ClientError> {
+            self.asked.fetch_add(1, Ordering::Relaxed);
+            Err(RpcError::RpcResponseError {
+                code: -32004,
+                message: "Block not available for slot 777".to_owned(),
+                data: solana_client::rpc_request::RpcResponseErrorData::Empty,
+            }
+            .into())
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "lagging".to_owned()
+        }
+    }
+
+    #[test]
+    fn lagging_node_failed_over() {
+        // Given a pool with a node lagging behind the other:
+        let lagging = Lagging::default();
+        let asked = lagging.asked.clone();
+        let senders: Vec<(String, Box<dyn RpcSender + Send + Sync>)> = vec![
+            ("lagging".to_owned(), Box::new(lagging)),
+            ("up".to_owned(), Box::new(MockSender::new("succeeds"))),
+        ];
+        let pool = RpcClientPool::new(senders, Duration::from_secs(3600));
+        let client = RpcClient::new_sender(pool, RpcClientConfig::default());
+
+        // When asked over and over, each node going first in turn:
+        for _ in 0..10 {
+            // Then every request should get answered by the node that has the block:
+            assert_eq!(client.get_slot().unwrap(), 0);
+        }
+
+        // And the lagging node should still be asked whenever its turn comes, not being down:
+        assert_eq!(asked.load(Ordering::Relaxed), 5);
+    }
+
+    #[test]
+    fn failing_node_failed_over_and_set_aside() {
+        // Given a pool whose first node is down:
+        let down = Down::default();
+        let asked = down.asked.clone();
+        let senders: Vec<(String, Box<dyn RpcSender + Send + Sync>)> = vec![
+            ("down".to_owned(), Box::new(down)),
+            ("up".to_owned(), Box::new(MockSender::new("succeeds"))),
+        ];
+        let pool = RpcClientPool::new(senders, Duration::from_secs(3600));
+        let client = RpcClient::new_sender(pool, RpcClientConfig::default());
+
+        // When asked for the slot over and over:
+        for _ in 0..10 {
+            // Then every request should get answered all the same:
+            assert_eq!(client.get_slot().unwrap(), 0);
+        }
+
+        // And the node down should be left alone once it has failed enough times in a row:
+        let asked = asked.load(Ordering::Relaxed);
+        assert_eq!(asked, FAILURES_BEFORE_QUARANTINE as usize);
+    }
+}
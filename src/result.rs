@@ -1,7 +1,10 @@
 //! An application-specific result type.
 
+use std::borrow::Cow;
+
 use actix_web::http::StatusCode;
-use actix_web::ResponseError;
+use actix_web::{HttpResponse, ResponseError};
+use solana_client::client_error::{ClientError, ClientErrorKind};
 use thiserror::Error;
 
 /// A custom error type for our application.
@@ -13,18 +16,70 @@ pub enum Error {
     NotFound,
     #[error("failed to open the database: {0}")]
     Database(#[from] rocksdb::Error),
+    #[error("the database is of schema version {found}, newer than the supported {supported}; upgrade surf")]
+    SchemaTooNew { found: u32, supported: u32 },
+    #[error("the database is of schema version {found}, older than the expected {expected}; run `surf migrate` to upgrade it")]
+    SchemaMismatch { found: u32, expected: u32 },
     #[error("failed to deserialize: {0}")]
     Coding(#[from] postcard::Error),
     #[error("failed to serialize: {0}")]
     Serialization(#[from] serde_json::Error),
+    #[error("failed to collect metrics: {0}")]
+    Metrics(#[from] prometheus::Error),
+    #[error("bad configuration: {0}")]
+    Config(String),
+    #[error("failed to set up the HTTP client: {0}")]
+    Http(#[from] reqwest::Error),
     #[error("failed to communicate with the cluster: {0}")]
-    SolanaClient(#[from] solana_client::client_error::ClientError),
+    SolanaClient(solana_client::client_error::ClientError),
     #[error("bad signature: {0}")]
     SolanaBadSignature(#[from] solana_sdk::signature::ParseSignatureError),
     #[error("bad account address: {0}")]
     SolanaBadPubkey(#[from] solana_sdk::pubkey::ParsePubkeyError),
     #[error("bad numeric: {0}")]
     SolanaBadNumber(String),
+    #[error("bad `{param}`: {reason}")]
+    BadQueryParam {
+        param: Cow<'static, str>,
+        reason: String,
+    },
+    #[error("bad query: {0}")]
+    BadQuery(String),
+    #[error("bad body: {0}")]
+    BadBody(String),
+    #[error("bad `{header}` header: {reason}")]
+    BadHeader {
+        header: &'static str,
+        reason: String,
+    },
+    #[error("gave up after {0:?}; narrow the query down")]
+    TimedOut(std::time::Duration),
+    #[error("passed over {0} records without filling the page; narrow the blocks down")]
+    TooBroad(usize),
+    #[error("{0} of the sampled records do not match the chain")]
+    Mismatch(usize),
+    #[error("{dangling} index entries point to no record, and {orphaned} records are not indexed")]
+    Inconsistent { dangling: u64, orphaned: u64 },
+    #[error("{0}")]
+    ExtractionFailed(String),
+}
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        Error::SolanaClient(redacted(e))
+    }
+}
+
+/// The same error, but without the URL of the node, as it may carry the token.
+pub fn redacted(e: ClientError) -> ClientError {
+    let kind = match e.kind {
+        ClientErrorKind::Reqwest(e) => ClientErrorKind::Reqwest(e.without_url()),
+        kind => kind,
+    };
+    ClientError {
+        request: e.request,
+        kind,
+    }
 }
 
 /// A specialization of `std::result::Result` for our application.
@@ -38,7 +93,20 @@ impl ResponseError for Error {
             Error::SolanaBadSignature(_) => StatusCode::BAD_REQUEST,
             Error::SolanaBadPubkey(_) => StatusCode::BAD_REQUEST,
             Error::SolanaBadNumber(_) => StatusCode::BAD_REQUEST,
+            Error::BadQueryParam { .. } => StatusCode::BAD_REQUEST,
+            Error::BadQuery(_) => StatusCode::BAD_REQUEST,
+            Error::BadBody(_) => StatusCode::BAD_REQUEST,
+            Error::BadHeader { .. } => StatusCode::BAD_REQUEST,
+            Error::TimedOut(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Error::TooBroad(_) => StatusCode::BAD_REQUEST,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    /// The message along with the status, as `{"error":"..."}`.
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(serde_json::json!({
+            "error": self.to_string(),
+        }))
+    }
 }
@@ -1,326 +1,2772 @@
 /* spellchecker:words rocksdb stdvec */
 //! Everything we remember.
 
-use serde::Serialize;
-use solana_sdk::signature::Signature;
-use std::sync::Arc;
-use tokio::{select, sync::mpsc::Receiver};
+use serde::{de::DeserializeOwned, Serialize};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::collections::{BTreeSet, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::select;
 use tokio_util::sync::CancellationToken;
-
-use crate::record::{Record, Transfer, Vote};
+use tracing::instrument;
+
+use crate::channel::{Extracted, Receiver};
+use crate::event_log::EventLog;
+use crate::metrics::Metrics;
+use crate::record::{
+    ExportedRecord, Kind, Record, Reward, StakeDelegation, TokenTransfer, Transfer, Vote,
+};
+use crate::result::Error;
 use crate::Result;
 
+mod migrations;
+
 /// A database of records.
 pub struct Store {
     db: rocksdb::DB,
+    /// Whether a query fails on an unreadable row, rather than skipping it.
+    strict: bool,
+    cadence: FlushCadence,
+    tuning: Tuning,
+    keys: PrimaryKey,
+    /// The freshest last known block, possibly not yet written down; zero if unknown.
+    last_known_block: AtomicU64,
+    /// The last known block as it was last written down.
+    persisted_block: AtomicU64,
+    /// The earliest block of any record, written down as soon as it changes; `u64::MAX` if unknown.
+    first_known_block: AtomicU64,
+    /// How many records have been committed since the last write-down.
+    unflushed: AtomicU64,
+    last_flush: Mutex<Instant>,
+    /// Where the last known block gets written down.
+    checkpoint_key: &'static [u8],
+    /// Where to count the writes, if anywhere.
+    metrics: Option<Arc<Metrics>>,
 }
 
-const VOTES_NS: &str = "vote";
-const TRANSFERS_NS: &str = "transfer";
-const VOTES_INDEX_NS: &str = "+votes";
-const TRANSFERS_INDEX_NS: &str = "+transfers";
-impl Store {
-    /// Open a store at the given path, creating it if necessary.
-    pub async fn with_path<Path: AsRef<std::path::Path>>(path: Path) -> Result<Self> {
+/// How often the last known block gets written down to the database.
+/// Whatever is not written down yet is lost on a crash,
+/// and gets re-extracted on the next start.
+#[derive(Clone, Copy, Debug)]
+pub struct FlushCadence {
+    /// Write down after this many records.
+    pub records: u64,
+    /// Write down once this much time has passed since the last write.
+    pub interval: Duration,
+}
+
+/// What the votes and the transfers are keyed by, chosen once the store gets created.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PrimaryKey {
+    /// The signature alone, so that a transaction seen again overwrites what it has left before,
+    /// as does every instruction of it after the first.
+    #[default]
+    Signature,
+    /// The signature along with the block and the position within it,
+    /// so that every occurrence is kept on its own, at the cost of the duplicates
+    /// of any transaction the node happens to serve under more than one block.
+    Occurrence,
+}
+
+/// How the reads and the writes go about the single database they share,
+/// so that the scans serving the web interface stay snappy under the indexing load.
+#[derive(Clone, Copy, Debug)]
+pub struct Tuning {
+    /// How many bytes to read ahead during scans.
+    pub readahead: usize,
+    /// Whether the forward scans should see through the writes happening meanwhile,
+    /// rather than pin a snapshot of the whole database for their duration.
+    pub tailing: bool,
+    /// Whether every write should wait for the log to hit the disk.
+    pub sync_writes: bool,
+}
+
+/// How the database gets set up when opened, for all of its column families alike;
+/// whatever is unset is left to RocksDB.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StoreConfig {
+    /// How many bytes each column family may buffer before writing a table file.
+    pub write_buffer: Option<usize>,
+    /// How many bytes of blocks to keep in memory, shared across the column families.
+    pub block_cache: Option<usize>,
+    /// How many files may stay open at once; -1 for as many as there are.
+    pub max_open_files: Option<i32>,
+    pub compression: Option<Compression>,
+}
+
+/// How the table files get compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl StoreConfig {
+    /// The options to open the database, and each of its column families, with.
+    fn options(&self) -> rocksdb::Options {
         let mut opts = rocksdb::Options::default();
         opts.create_if_missing(true);
         opts.create_missing_column_families(true);
+        if let Some(bytes) = self.write_buffer {
+            opts.set_write_buffer_size(bytes);
+        }
+        if let Some(bytes) = self.block_cache {
+            let mut table = rocksdb::BlockBasedOptions::default();
+            table.set_block_cache(&rocksdb::Cache::new_lru_cache(bytes));
+            opts.set_block_based_table_factory(&table);
+        }
+        if let Some(files) = self.max_open_files {
+            opts.set_max_open_files(files);
+        }
+        if let Some(compression) = self.compression {
+            opts.set_compression_type(match compression {
+                Compression::None => rocksdb::DBCompressionType::None,
+                Compression::Snappy => rocksdb::DBCompressionType::Snappy,
+                Compression::Lz4 => rocksdb::DBCompressionType::Lz4,
+                Compression::Zstd => rocksdb::DBCompressionType::Zstd,
+            });
+        }
+        opts
+    }
+}
 
-        let db = rocksdb::DB::open_cf(
-            &opts,
-            path,
-            vec![VOTES_NS, TRANSFERS_NS, VOTES_INDEX_NS, TRANSFERS_INDEX_NS],
-        )?;
-        Ok(Self { db })
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            readahead: 2 << 20,
+            tailing: false,
+            sync_writes: false,
+        }
+    }
+}
+
+impl Default for FlushCadence {
+    fn default() -> Self {
+        Self {
+            records: 1000,
+            interval: Duration::from_secs(5),
+        }
     }
 }
 
-const LAST_KNOWN_BLOCK_KEY: &[u8] = b"\x1b\x11";
+pub const VOTES_NS: &str = "vote";
+pub const TRANSFERS_NS: &str = "transfer";
+pub const VOTES_INDEX_NS: &str = "+votes";
+pub const TRANSFERS_INDEX_NS: &str = "+transfers";
+pub const VOTES_CHRONO_NS: &str = "+votes-chrono";
+pub const TRANSFERS_CHRONO_NS: &str = "+transfers-chrono";
+pub const REWARDS_NS: &str = "reward";
+pub const REWARDS_INDEX_NS: &str = "+rewards";
+pub const TOKEN_TRANSFERS_NS: &str = "token-transfer";
+pub const TOKEN_TRANSFERS_INDEX_NS: &str = "+token-transfers";
+pub const STAKE_DELEGATIONS_NS: &str = "stake-delegation";
+pub const STAKE_DELEGATIONS_INDEX_NS: &str = "+stake-delegations";
+pub const SKIPPED_NS: &str = "skipped";
+pub const EMPTY_NS: &str = "empty";
+pub const STAGED_NS: &str = "staged";
+
+/// Every column family there is, the default one included.
+pub const COLUMN_FAMILIES: [&str; 16] = [
+    rocksdb::DEFAULT_COLUMN_FAMILY_NAME,
+    VOTES_NS,
+    TRANSFERS_NS,
+    VOTES_INDEX_NS,
+    TRANSFERS_INDEX_NS,
+    VOTES_CHRONO_NS,
+    TRANSFERS_CHRONO_NS,
+    REWARDS_NS,
+    REWARDS_INDEX_NS,
+    TOKEN_TRANSFERS_NS,
+    TOKEN_TRANSFERS_INDEX_NS,
+    STAKE_DELEGATIONS_NS,
+    STAKE_DELEGATIONS_INDEX_NS,
+    SKIPPED_NS,
+    EMPTY_NS,
+    STAGED_NS,
+];
+
+/// What the entries of the secondary indexes are keyed by.
+/// Each field gets a key space of its own, marked by the first byte,
+/// and the numbers within are big-endian, so that the lexicographic order
+/// is the numeric one, and a range scan never spills over into another field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexField {
+    Block,
+    Lamports,
+    Account,
+    Program,
+    Timestamp,
+}
+
+impl IndexField {
+    pub fn tag(self) -> u8 {
+        match self {
+            IndexField::Block => b'b',
+            IndexField::Lamports => b'l',
+            IndexField::Account => b'a',
+            IndexField::Program => b'p',
+            IndexField::Timestamp => b't',
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        [
+            IndexField::Block,
+            IndexField::Lamports,
+            IndexField::Account,
+            IndexField::Program,
+            IndexField::Timestamp,
+        ]
+        .into_iter()
+        .find(|field| field.tag() == tag)
+    }
+
+    /// The prefix of all the entries with the given value of the field.
+    pub fn prefix(self, value: &[u8]) -> Vec<u8> {
+        let mut key = Vec::with_capacity(1 + value.len() + 64);
+        key.push(self.tag());
+        key.extend_from_slice(value);
+        key
+    }
+}
 impl Store {
-    /// Maximum of all the "block index" fields across all the records.
-    pub async fn last_known_block(&self) -> Option<u64> {
-        let gotten = self.db.get_pinned(LAST_KNOWN_BLOCK_KEY).ok().flatten()?;
-        postcard::from_bytes(&gotten).ok()
+    /// Open a store at the given path, creating it if necessary,
+    /// and refusing it if it was written by another version.
+    pub async fn with_path<Path: AsRef<std::path::Path>>(
+        path: Path,
+        config: &StoreConfig,
+    ) -> Result<Self> {
+        let store = Self::open(path, config)?;
+        store.check_schema()?;
+        Ok(store)
     }
 
-    /// Set the last known block to the given value.
-    pub async fn set_last_known_block(&self, block: u64) -> Result<()> {
-        let bytes = postcard::to_stdvec(&block).unwrap();
-        self.db.put(LAST_KNOWN_BLOCK_KEY, bytes)?;
-        Ok(())
+    /// Open a store at the given path, creating it if necessary,
+    /// and upgrading it if it was written by an older version.
+    pub async fn with_path_migrated<Path: AsRef<std::path::Path>>(
+        path: Path,
+        config: &StoreConfig,
+    ) -> Result<Self> {
+        let store = Self::open(path, config)?;
+        store.migrate().await?;
+        Ok(store)
+    }
+
+    fn open<Path: AsRef<std::path::Path>>(path: Path, config: &StoreConfig) -> Result<Self> {
+        let opts = config.options();
+        let names = [
+            VOTES_NS,
+            TRANSFERS_NS,
+            VOTES_INDEX_NS,
+            TRANSFERS_INDEX_NS,
+            VOTES_CHRONO_NS,
+            TRANSFERS_CHRONO_NS,
+            REWARDS_NS,
+            REWARDS_INDEX_NS,
+            TOKEN_TRANSFERS_NS,
+            TOKEN_TRANSFERS_INDEX_NS,
+            STAKE_DELEGATIONS_NS,
+            STAKE_DELEGATIONS_INDEX_NS,
+            SKIPPED_NS,
+            EMPTY_NS,
+            STAGED_NS,
+        ];
+        // Otherwise, the column families would get the defaults rather than the options.
+        let families = names
+            .into_iter()
+            .map(|name| rocksdb::ColumnFamilyDescriptor::new(name, opts.clone()));
+        let db = rocksdb::DB::open_cf_descriptors(&opts, path, families)?;
+
+        let persisted_block = db
+            .get_pinned(LAST_KNOWN_BLOCK_KEY)?
+            .and_then(|gotten| postcard::from_bytes(&gotten).ok())
+            .unwrap_or(0);
+        let first_known_block = db
+            .get_pinned(FIRST_KNOWN_BLOCK_KEY)?
+            .and_then(|gotten| postcard::from_bytes(&gotten).ok())
+            .unwrap_or(u64::MAX);
+        // Whatever was written before the choice was there is keyed by the signature.
+        let keys = db
+            .get_pinned(PRIMARY_KEY_KEY)?
+            .and_then(|gotten| postcard::from_bytes(&gotten).ok())
+            .unwrap_or_default();
+
+        let store = Self {
+            db,
+            strict: false,
+            cadence: FlushCadence::default(),
+            tuning: Tuning::default(),
+            keys,
+            last_known_block: AtomicU64::new(persisted_block),
+            persisted_block: AtomicU64::new(persisted_block),
+            first_known_block: AtomicU64::new(first_known_block),
+            unflushed: AtomicU64::new(0),
+            last_flush: Mutex::new(Instant::now()),
+            checkpoint_key: LAST_KNOWN_BLOCK_KEY,
+            metrics: None,
+        };
+        Ok(store)
+    }
+
+    /// Key the records as given; only possible while the store is empty,
+    /// and refused if it is already keyed otherwise.
+    pub fn with_primary_key(mut self, keys: PrimaryKey) -> Result<Self> {
+        if keys == self.keys {
+            return Ok(self);
+        }
+        if !self.is_pristine() {
+            return Err(Error::Config(format!(
+                "the store is keyed by {:?}, and cannot be rekeyed by {keys:?}",
+                self.keys
+            )));
+        }
+        let bytes = postcard::to_stdvec(&keys).unwrap();
+        self.db
+            .put_opt(PRIMARY_KEY_KEY, bytes, &self.write_opts())?;
+        self.keys = keys;
+        Ok(self)
+    }
+
+    /// Fail queries hitting unreadable rows instead of skipping them,
+    /// so that an incomplete result never passes for a complete one.
+    pub fn with_strict_reads(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Keep track of how far the backfill of the given blocks has got instead of the continuous extraction,
+    /// leaving the checkpoint of the latter as it is; whatever was kept for other blocks is started over.
+    pub fn with_backfill(mut self, blocks: RangeInclusive<u64>) -> Result<Self> {
+        let progress = self
+            .db
+            .get_pinned(BACKFILL_PROGRESS_KEY)?
+            .and_then(|gotten| postcard::from_bytes(&gotten).ok())
+            .filter(|block| blocks.contains(block))
+            .unwrap_or(0);
+        self.last_known_block = AtomicU64::new(progress);
+        self.persisted_block = AtomicU64::new(progress);
+        self.checkpoint_key = BACKFILL_PROGRESS_KEY;
+        Ok(self)
+    }
+
+    /// Count the records written down, and time the writes.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Use the given options for the reads and the writes.
+    pub fn with_tuning(mut self, tuning: Tuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
+
+    /// Use the given cadence for writing down the last known block.
+    pub fn with_flush_cadence(mut self, cadence: FlushCadence) -> Self {
+        self.cadence = cadence;
+        self
     }
 }
 
 impl Store {
-    /// Add a record of `{secondary_key}:{primary_key} -> {primary_key}` to the database
-    /// so that it could later be retrieved by a prefix scan.
-    fn associate<T, Y>(
-        &self,
-        cf: &rocksdb::ColumnFamily,
-        secondary_key: &T,
-        primary_key: &Y,
-    ) -> Result<()>
-    where
-        T: Sized + Serialize,
-        Y: Sized + Serialize,
-    {
-        let bytes = Vec::with_capacity(64);
-        let bytes = postcard::to_extend(&secondary_key, bytes)?;
-        let bytes = postcard::to_extend(&primary_key, bytes)?;
-
-        let primary_key = postcard::to_stdvec(&primary_key).unwrap();
-
-        self.db.put_cf(cf, bytes, primary_key)?;
-        Ok(())
+    /// Options for the scans: reading ahead, and not evicting the hot blocks from the cache
+    /// for the sake of the data that is likely to be read once.
+    pub fn read_opts(&self) -> rocksdb::ReadOptions {
+        let mut opts = rocksdb::ReadOptions::default();
+        opts.set_readahead_size(self.tuning.readahead);
+        opts.set_tailing(self.tuning.tailing);
+        opts.set_total_order_seek(true);
+        opts.fill_cache(false);
+        opts
+    }
+
+    /// Options for the scans going from the end backwards,
+    /// which a tailing iterator cannot do, so these always pin a snapshot.
+    pub fn backward_read_opts(&self) -> rocksdb::ReadOptions {
+        let mut opts = self.read_opts();
+        opts.set_tailing(false);
+        opts
+    }
+
+    /// Options for the writes of the committer.
+    pub fn write_opts(&self) -> rocksdb::WriteOptions {
+        let mut opts = rocksdb::WriteOptions::default();
+        opts.set_sync(self.tuning.sync_writes);
+        opts
     }
 }
 
 impl Store {
-    /// Update the last known block to the given value
-    /// if it is greater than the current one.
-    async fn bump_last_known_block(&self, block_index: u64) -> Result<()> {
-        let last_known_block = self.last_known_block().await.unwrap_or(0);
-        if block_index > last_known_block {
-            self.set_last_known_block(block_index).await?;
+    /// Write a consistent point-in-time copy of the database into the given directory.
+    /// The table files get hard-linked rather than copied, so this is cheap,
+    /// and it is safe to do while the records are being written.
+    pub async fn checkpoint<Path: AsRef<std::path::Path>>(&self, path: Path) -> Result<()> {
+        // So that the copy knows how far it goes:
+        self.flush().await?;
+
+        let checkpoint = rocksdb::checkpoint::Checkpoint::new(&self.db)?;
+        checkpoint.create_checkpoint(path)?;
+        Ok(())
+    }
+
+    /// Write all the votes and the transfers into a new file, one per line, tagged by their kind,
+    /// so that any version can read them back, unlike the table files.
+    /// The file only appears once complete, so that a cut-off export is never taken for one.
+    pub async fn export_to_json_file(&self, path: &std::path::Path) -> Result<()> {
+        if path.exists() {
+            let e = std::io::Error::new(std::io::ErrorKind::AlreadyExists, "already exists");
+            return Err(e.into());
         }
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = std::path::PathBuf::from(partial);
+
+        let exported = self.export_into(&partial);
+        if exported.is_err() {
+            let _ = std::fs::remove_file(&partial);
+            return exported;
+        }
+        std::fs::rename(&partial, path)?;
         Ok(())
     }
 
-    /// Write down a Vote record, possibly overwriting the same primary-keyed record.
-    pub async fn save_vote(&self, vote: &Vote) -> Result<()> {
-        self.bump_last_known_block(vote.block_index).await?;
+    fn export_into(&self, path: &std::path::Path) -> Result<()> {
+        let file = File::create(path)?;
+        let mut file = BufWriter::new(file);
+        self.export_cf(&mut file, VOTES_NS, |vote: Vote| {
+            ExportedRecord::Vote(vote.into())
+        })?;
+        self.export_cf(&mut file, TRANSFERS_NS, |transfer: Transfer| {
+            ExportedRecord::Transfer(transfer.into())
+        })?;
+        let file = file.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        Ok(())
+    }
 
-        // Writing down the contents:
-        let cf = self.db.cf_handle(VOTES_NS).unwrap();
-        let key = postcard::to_stdvec(&vote.signature).unwrap();
-        self.db.put_cf(cf, key, postcard::to_stdvec(&vote)?)?;
+    fn export_cf<T: DeserializeOwned>(
+        &self,
+        file: &mut impl Write,
+        ns: &str,
+        into_exported: fn(T) -> ExportedRecord,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(ns).unwrap();
+        let mode = rocksdb::IteratorMode::Start;
+        for each in self.db.iterator_cf_opt(cf, self.read_opts(), mode) {
+            let Some((_k, v)) = self.row(each)? else {
+                continue;
+            };
+            let Some(record) = self.decode(&v)? else {
+                continue;
+            };
+            serde_json::to_writer(&mut *file, &into_exported(record))?;
+            file.write_all(b"\n")?;
+        }
+        Ok(())
+    }
 
-        // Indexing:
-        let cf = self.db.cf_handle(VOTES_INDEX_NS).unwrap();
-        self.associate(cf, &vote.block_index, &vote.signature)?;
-        self.associate(cf, &vote.target, &vote.signature)?;
-        self.associate(cf, &vote.author, &vote.signature)?;
+    /// Write down the votes and the transfers from a file as written by [Store::export_to_json_file],
+    /// or by the event log, skipping the lines that are neither.
+    pub async fn import_from_json_file(&self, path: &std::path::Path) -> Result<ImportReport> {
+        let file = BufReader::new(File::open(path)?);
+        let mut report = ImportReport::default();
+        for (number, line) in file.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match read_imported(&line) {
+                Ok(Record::Vote(vote)) => self.save_vote(&vote).await?,
+                Ok(Record::Transfer(transfer)) => self.save_transfer(&transfer).await?,
+                Ok(_) => {
+                    tracing::warn!(
+                        "Skipping line {}: neither a vote nor a transfer",
+                        number + 1
+                    );
+                    report.skipped += 1;
+                    continue;
+                }
+                Err(e) => {
+                    tracing::warn!("Skipping line {}: {e}", number + 1);
+                    report.skipped += 1;
+                    continue;
+                }
+            }
+            report.imported += 1;
+        }
+        self.flush().await?;
+        Ok(report)
+    }
+}
 
-        Ok(())
+/// Read a record back from a line of an export, tagged by `kind`,
+/// or from one of the event log, tagged by `type` as on the wire.
+fn read_imported(line: &str) -> Result<Record> {
+    let value: serde_json::Value = serde_json::from_str(line)?;
+    if value.get("kind").is_some() {
+        let exported: ExportedRecord = serde_json::from_value(value)?;
+        return exported.try_into();
     }
+    Ok(serde_json::from_value(value)?)
+}
 
-    /// Write down a Transfer record, possibly overwriting the same primary-keyed record.
-    pub async fn save_transfer(&self, transfer: &Transfer) -> Result<()> {
-        self.bump_last_known_block(transfer.block_index).await?;
+pub const LAST_KNOWN_BLOCK_KEY: &[u8] = b"\x1b\x11";
+const FIRST_KNOWN_BLOCK_KEY: &[u8] = b"\x1b\x66";
+const BACKFILL_PROGRESS_KEY: &[u8] = b"\x1b\x62";
+const PRIMARY_KEY_KEY: &[u8] = b"\x1b\x6b";
+impl Store {
+    /// Maximum of all the "block index" fields across all the records.
+    pub async fn last_known_block(&self) -> Option<u64> {
+        let block = self.last_known_block.load(Ordering::Acquire);
+        (block != 0).then_some(block)
+    }
 
-        // The contents:
-        let cf = self.db.cf_handle(TRANSFERS_NS).unwrap();
-        let key = postcard::to_stdvec(&transfer.signature).unwrap();
-        self.db.put_cf(cf, key, postcard::to_stdvec(&transfer)?)?;
+    /// Minimum of all the "block index" fields across all the records.
+    pub async fn first_known_block(&self) -> Option<u64> {
+        let block = self.first_known_block.load(Ordering::Acquire);
+        (block != u64::MAX).then_some(block)
+    }
 
-        // Indexing:
-        let cf = self.db.cf_handle(TRANSFERS_INDEX_NS).unwrap();
-        self.associate(cf, &transfer.block_index, &transfer.signature)?;
-        self.associate(cf, &transfer.source, &transfer.signature)?;
-        self.associate(cf, &transfer.destination, &transfer.signature)?;
-        self.associate(cf, &transfer.lamports, &transfer.signature)?;
+    /// Set the last known block to the given value, writing it down immediately.
+    pub async fn set_last_known_block(&self, block: u64) -> Result<()> {
+        self.last_known_block.store(block, Ordering::Release);
+        self.flush().await
+    }
+
+    /// Read something back, so as to tell whether the database still answers.
+    pub fn probe(&self) -> Result<()> {
+        self.db.get_pinned(PRIMARY_KEY_KEY)?;
+        Ok(())
+    }
+
+    /// Write down the last known block if it has changed since the last write.
+    pub async fn flush(&self) -> Result<()> {
+        self.unflushed.store(0, Ordering::Release);
+        *self.last_flush.lock().unwrap() = Instant::now();
 
+        let block = self.last_known_block.load(Ordering::Acquire);
+        if self.persisted_block.swap(block, Ordering::AcqRel) == block {
+            return Ok(());
+        }
+        let bytes = postcard::to_stdvec(&block).unwrap();
+        self.db
+            .put_opt(self.checkpoint_key, bytes, &self.write_opts())?;
         Ok(())
     }
 }
 
+/// Add a record of `{field}:{value}:{primary_key} -> {primary_key}` to the batch
+/// so that it could later be retrieved by a prefix or a range scan.
+fn associate(
+    batch: &mut rocksdb::WriteBatch,
+    cf: &rocksdb::ColumnFamily,
+    field: IndexField,
+    value: &[u8],
+    primary_key: &[u8],
+) {
+    let key = index_key(field, value, primary_key);
+    batch.put_cf(cf, key, primary_key);
+}
+
+/// Add a record of `{block_index}:{instruction_index}:{signature} -> {primary_key}` to the batch
+/// so that a scan over the column family yields the records in their on-chain order.
+fn associate_chronologically(
+    batch: &mut rocksdb::WriteBatch,
+    cf: &rocksdb::ColumnFamily,
+    block_index: u64,
+    instruction_index: u32,
+    signature: &Signature,
+    primary_key: &[u8],
+) {
+    let key = chrono_key(block_index, instruction_index, signature);
+    batch.put_cf(cf, key, primary_key);
+}
+
+/// The key of the index entry `{field}:{value}:{primary_key}`.
+fn index_key(field: IndexField, value: &[u8], primary_key: &[u8]) -> Vec<u8> {
+    let mut key = field.prefix(value);
+    key.extend_from_slice(primary_key);
+    key
+}
+
+/// The key of the chronological index entry `{block_index}:{instruction_index}:{signature}`.
+fn chrono_key(block_index: u64, instruction_index: u32, signature: &Signature) -> Vec<u8> {
+    // Big-endian, so that the lexicographic order of the keys is the numeric one.
+    let mut key = Vec::with_capacity(8 + 4 + 64);
+    key.extend_from_slice(&block_index.to_be_bytes());
+    key.extend_from_slice(&instruction_index.to_be_bytes());
+    key.extend_from_slice(signature.as_ref());
+    key
+}
+
+/// The key of a reward `{block_index}:{reward_index}`, so that the rewards of a block
+/// lie together, in the order the block lists them.
+fn reward_key(block_index: u64, reward_index: u32) -> Vec<u8> {
+    let mut key = Vec::with_capacity(8 + 4);
+    key.extend_from_slice(&block_index.to_be_bytes());
+    key.extend_from_slice(&reward_index.to_be_bytes());
+    key
+}
+
+/// The key of the reward index entry `{pubkey}:{block_index}:{reward_index}`,
+/// or of the token transfer or stake delegation index entry
+/// `{pubkey}:{block_index}:{instruction_index}:{signature}`.
+fn reward_index_key(pubkey: &Pubkey, primary_key: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(32 + primary_key.len());
+    key.extend_from_slice(pubkey.as_ref());
+    key.extend_from_slice(primary_key);
+    key
+}
+
 impl Store {
-    /// Get the unique Vote record with the given primary key if it exists.
-    pub async fn find_vote(&self, key: &Signature) -> Option<Vote> {
-        let cf = self.db.cf_handle(VOTES_NS).unwrap();
-        let key = postcard::to_stdvec(&key).unwrap();
-        let vote = self.db.get_pinned_cf(cf, key).ok().flatten()?;
+    /// The primary key of the record the transaction has left at the given position.
+    /// Its signature always comes first, so that all the records of a transaction lie together.
+    fn primary_key(
+        &self,
+        signature: &Signature,
+        block_index: u64,
+        instruction_index: u32,
+    ) -> Vec<u8> {
+        let mut key = postcard::to_stdvec(signature).unwrap();
+        if self.keys == PrimaryKey::Occurrence {
+            key.extend_from_slice(&block_index.to_be_bytes());
+            key.extend_from_slice(&instruction_index.to_be_bytes());
+        }
+        key
+    }
 
-        let Ok(vote) = postcard::from_bytes(&vote) else {
-            return None;
-        };
-        Some(vote)
+    /// Remove all the records of the given block along with their index entries,
+    /// so that the block could be extracted anew.
+    /// Yields how many votes, transfers, rewards, token transfers, and stake delegations there were.
+    pub async fn clear_block(
+        &self,
+        block_index: u64,
+    ) -> Result<(usize, usize, usize, usize, usize)> {
+        let votes = self.find_votes_by_block_index(block_index).await?;
+        let transfers = self.find_transfers_by_block_index(block_index).await?;
+        let rewards = self.find_rewards_by_block_index(block_index).await?;
+        let token_transfers = self
+            .find_token_transfers_by_block_index(block_index)
+            .await?;
+        let stake_delegations = self
+            .find_stake_delegations_by_block_range(block_index, block_index)
+            .await?;
+
+        let block = block_index.to_be_bytes();
+        let mut batch = rocksdb::WriteBatch::default();
+
+        for vote in &votes {
+            self.forget_vote(&mut batch, vote);
+        }
+        for transfer in &transfers {
+            self.forget_transfer(&mut batch, transfer);
+        }
+
+        let (cf, index_cf) = (
+            self.db.cf_handle(REWARDS_NS).unwrap(),
+            self.db.cf_handle(REWARDS_INDEX_NS).unwrap(),
+        );
+        for reward in &rewards {
+            let key = reward_key(block_index, reward.reward_index);
+            batch.delete_cf(index_cf, reward_index_key(&reward.pubkey, &key));
+            batch.delete_cf(cf, key);
+        }
+
+        let (cf, index_cf) = (
+            self.db.cf_handle(TOKEN_TRANSFERS_NS).unwrap(),
+            self.db.cf_handle(TOKEN_TRANSFERS_INDEX_NS).unwrap(),
+        );
+        for transfer in &token_transfers {
+            let key = chrono_key(block_index, transfer.instruction_index, &transfer.signature);
+            for account in transfer.accounts() {
+                batch.delete_cf(index_cf, reward_index_key(account, &key));
+            }
+            batch.delete_cf(cf, key);
+        }
+
+        let (cf, index_cf) = (
+            self.db.cf_handle(STAKE_DELEGATIONS_NS).unwrap(),
+            self.db.cf_handle(STAKE_DELEGATIONS_INDEX_NS).unwrap(),
+        );
+        for delegation in &stake_delegations {
+            let instruction_index = delegation.instruction_index;
+            let key = chrono_key(block_index, instruction_index, &delegation.signature);
+            for account in delegation.accounts() {
+                batch.delete_cf(index_cf, reward_index_key(account, &key));
+            }
+            batch.delete_cf(cf, key);
+        }
+        batch.delete_cf(self.db.cf_handle(SKIPPED_NS).unwrap(), block);
+        batch.delete_cf(self.db.cf_handle(EMPTY_NS).unwrap(), block);
+
+        self.db.write_opt(batch, &self.write_opts())?;
+        Ok((
+            votes.len(),
+            transfers.len(),
+            rewards.len(),
+            token_transfers.len(),
+            stake_delegations.len(),
+        ))
     }
 
-    /// Retrieve the unique Transfer record with the given primary key if it exists.
-    pub async fn find_transfer(&self, key: &Signature) -> Option<Transfer> {
-        let cf = self.db.cf_handle(TRANSFERS_NS).unwrap();
-        let key = postcard::to_stdvec(&key).unwrap();
-        let transfer = self.db.get_pinned_cf(cf, key).ok().flatten()?;
+    /// Remove the Vote records with the given signature along with their index entries, all at once.
+    pub async fn delete_vote(&self, signature: &Signature) -> Result<()> {
+        let votes = self.find_vote(signature).await?;
+        if votes.is_empty() {
+            return Err(Error::NotFound);
+        }
+        let mut batch = rocksdb::WriteBatch::default();
+        for vote in &votes {
+            self.forget_vote(&mut batch, vote);
+        }
+        self.db.write_opt(batch, &self.write_opts())?;
+        Ok(())
+    }
 
-        let Ok(transfer) = postcard::from_bytes(&transfer) else {
-            return None;
-        };
-        Some(transfer)
+    /// Remove the Transfer records with the given signature along with their index entries, all at once.
+    pub async fn delete_transfer(&self, signature: &Signature) -> Result<()> {
+        let transfers = self.find_transfer(signature).await?;
+        if transfers.is_empty() {
+            return Err(Error::NotFound);
+        }
+        let mut batch = rocksdb::WriteBatch::default();
+        for transfer in &transfers {
+            self.forget_transfer(&mut batch, transfer);
+        }
+        self.db.write_opt(batch, &self.write_opts())?;
+        Ok(())
     }
 
-    /// Retrieve all the matching records from the database.
-    pub async fn find_all_votes(&self) -> Result<Vec<Vote>> {
-        let mut votes = Vec::new();
-        for each in self.db.full_iterator_cf(
+    /// Add the removal of a Vote record along with its index entries to the batch,
+    /// the entries being told by the record itself.
+    fn forget_vote(&self, batch: &mut rocksdb::WriteBatch, vote: &Vote) {
+        let (cf, index_cf, chrono_cf) = (
             self.db.cf_handle(VOTES_NS).unwrap(),
-            rocksdb::IteratorMode::Start,
-        ) {
-            let Ok((_k, v)) = each else {
-                tracing::error!("Failed to get a row from the database");
-                continue;
-            };
-            let Ok(vote) = postcard::from_bytes(&v) else {
-                continue;
-            };
-            votes.push(vote);
+            self.db.cf_handle(VOTES_INDEX_NS).unwrap(),
+            self.db.cf_handle(VOTES_CHRONO_NS).unwrap(),
+        );
+        let signature = &vote.signature;
+        let key = self.primary_key(signature, vote.block_index, vote.instruction_index);
+        let block = vote.block_index.to_be_bytes();
+        let timestamp = vote.timestamp.to_be_bytes();
+        batch.delete_cf(index_cf, index_key(IndexField::Block, &block, &key));
+        batch.delete_cf(index_cf, index_key(IndexField::Timestamp, &timestamp, &key));
+        for account in [&vote.target, &vote.author] {
+            batch.delete_cf(
+                index_cf,
+                index_key(IndexField::Account, account.as_ref(), &key),
+            );
         }
-        Ok(votes)
+        batch.delete_cf(
+            index_cf,
+            index_key(IndexField::Program, vote.program_id.as_ref(), &key),
+        );
+        batch.delete_cf(
+            chrono_cf,
+            chrono_key(vote.block_index, vote.instruction_index, signature),
+        );
+        batch.delete_cf(cf, key);
     }
 
-    /// Retrieve all the matching records from the database.
-    pub async fn find_all_transfers(&self) -> Result<Vec<Transfer>> {
-        let mut transfers = Vec::new();
-        for each in self.db.full_iterator_cf(
+    /// Add the removal of a Transfer record along with its index entries to the batch,
+    /// the entries being told by the record itself.
+    fn forget_transfer(&self, batch: &mut rocksdb::WriteBatch, transfer: &Transfer) {
+        let (cf, index_cf, chrono_cf) = (
             self.db.cf_handle(TRANSFERS_NS).unwrap(),
-            rocksdb::IteratorMode::Start,
-        ) {
-            let Ok((_k, v)) = each else {
-                tracing::error!("Failed to get a row from the database");
-                continue;
-            };
-            let Ok(transfer) = postcard::from_bytes(&v) else {
-                continue;
-            };
-            transfers.push(transfer);
+            self.db.cf_handle(TRANSFERS_INDEX_NS).unwrap(),
+            self.db.cf_handle(TRANSFERS_CHRONO_NS).unwrap(),
+        );
+        let signature = &transfer.signature;
+        let key = self.primary_key(signature, transfer.block_index, transfer.instruction_index);
+        let block = transfer.block_index.to_be_bytes();
+        let lamports = transfer.lamports.to_be_bytes();
+        let timestamp = transfer.timestamp.to_be_bytes();
+        batch.delete_cf(index_cf, index_key(IndexField::Block, &block, &key));
+        batch.delete_cf(index_cf, index_key(IndexField::Timestamp, &timestamp, &key));
+        batch.delete_cf(index_cf, index_key(IndexField::Lamports, &lamports, &key));
+        for account in [&transfer.source, &transfer.destination] {
+            batch.delete_cf(
+                index_cf,
+                index_key(IndexField::Account, account.as_ref(), &key),
+            );
         }
-        Ok(transfers)
+        batch.delete_cf(
+            index_cf,
+            index_key(IndexField::Program, transfer.program_id.as_ref(), &key),
+        );
+        batch.delete_cf(
+            chrono_cf,
+            chrono_key(transfer.block_index, transfer.instruction_index, signature),
+        );
+        batch.delete_cf(cf, key);
     }
+}
 
-    /// Retrieve all the matching records from the database.
-    pub async fn find_votes_by_block_index(&self, block_index: u64) -> Result<Vec<Vote>> {
-        let cf = self.db.cf_handle(VOTES_INDEX_NS).unwrap();
-        let prefix = postcard::to_stdvec(&block_index).unwrap();
+impl Store {
+    /// Update the last known block to the given value
+    /// if it is greater than the current one,
+    /// and write it down if the cadence says so.
+    /// Likewise the first known block if it is smaller, only written down right away,
+    /// as that hardly ever happens past the first record.
+    async fn bump_last_known_block(&self, block_index: u64, records: u64) -> Result<()> {
+        self.lower_first_known_block(block_index)?;
+        self.pass_block(block_index, records).await
+    }
 
-        let mut votes = Vec::new();
-        for each in self.db.prefix_iterator_cf(cf, &prefix) {
-            let Ok((k, v)) = each else {
-                tracing::error!("Failed to get a row from the database");
-                continue;
-            };
-            if !k.starts_with(&prefix) {
-                break;
-            }
-            let Ok(key) = postcard::from_bytes::<Signature>(&v) else {
-                continue;
-            };
-            let Some(vote) = self.find_vote(&key).await else {
-                tracing::error!("Dangling index entry for a vote");
-                continue;
-            };
-            votes.push(vote);
+    /// Move the last known block up to the given one if it is behind,
+    /// writing it down if the cadence says so.
+    async fn pass_block(&self, block_index: u64, records: u64) -> Result<()> {
+        self.last_known_block
+            .fetch_max(block_index, Ordering::AcqRel);
+
+        let unflushed = self.unflushed.fetch_add(records, Ordering::AcqRel) + records;
+        let due = unflushed >= self.cadence.records
+            || self.last_flush.lock().unwrap().elapsed() >= self.cadence.interval;
+        if due {
+            self.flush().await?;
         }
-        Ok(votes)
+        Ok(())
     }
 
-    /// Retrieve all the matching records from the database.
-    pub async fn find_transfers_by_block_index(&self, block_index: u64) -> Result<Vec<Transfer>> {
-        let cf = self.db.cf_handle(TRANSFERS_INDEX_NS).unwrap();
-        let prefix = postcard::to_stdvec(&block_index).unwrap();
+    /// Update the first known block to the given value if it is smaller than the current one,
+    /// writing it down right away.
+    fn lower_first_known_block(&self, block_index: u64) -> Result<()> {
+        let first = self
+            .first_known_block
+            .fetch_min(block_index, Ordering::AcqRel);
+        if block_index < first {
+            let bytes = postcard::to_stdvec(&block_index).unwrap();
+            self.db
+                .put_opt(FIRST_KNOWN_BLOCK_KEY, bytes, &self.write_opts())?;
+        }
+        Ok(())
+    }
 
-        let mut transfers = Vec::new();
-        for each in self.db.prefix_iterator_cf(cf, &prefix) {
-            let Ok((k, v)) = each else {
-                tracing::error!("Failed to get a row from the database");
-                continue;
-            };
-            if !k.starts_with(&prefix) {
-                break;
-            }
-            let Ok(key) = postcard::from_bytes::<Signature>(&v) else {
+    /// Note that the cluster has skipped the block, so that it does not count as a gap.
+    pub async fn mark_skipped(&self, block_index: u64) -> Result<()> {
+        self.mark(SKIPPED_NS, block_index).await
+    }
+
+    /// Note that the block got processed with nothing of the indexed kinds in it,
+    /// so that it does not count as a gap either.
+    pub async fn mark_empty(&self, block_index: u64) -> Result<()> {
+        self.mark(EMPTY_NS, block_index).await
+    }
+
+    /// Note the block, and move past it, so that it does not get extracted anew on restart
+    /// however long a run of such blocks there is, nor any block before it that had no records.
+    async fn mark(&self, ns: &str, block_index: u64) -> Result<()> {
+        let cf = self.db.cf_handle(ns).unwrap();
+        self.db
+            .put_cf_opt(cf, block_index.to_be_bytes(), b"", &self.write_opts())?;
+        self.pass_block(block_index, 0).await
+    }
+
+    /// Keep what the extraction has handed over until the committer is done with it,
+    /// under its place in the line.
+    pub fn stage(&self, seq: u64, extracted: &Extracted) -> Result<()> {
+        // As JSON, the records being tagged by their kind in a way postcard cannot take.
+        let value = serde_json::to_vec(extracted)?;
+        let cf = self.db.cf_handle(STAGED_NS).unwrap();
+        self.db
+            .put_cf_opt(cf, seq.to_be_bytes(), value, &self.write_opts())?;
+        Ok(())
+    }
+
+    /// Forget what the committer is done with.
+    pub fn unstage(&self, seq: u64) -> Result<()> {
+        let cf = self.db.cf_handle(STAGED_NS).unwrap();
+        self.db
+            .delete_cf_opt(cf, seq.to_be_bytes(), &self.write_opts())?;
+        Ok(())
+    }
+
+    /// Whatever has been staged but never committed, in the order it was handed over.
+    pub fn staged(&self) -> Result<Vec<(u64, Extracted)>> {
+        let cf = self.db.cf_handle(STAGED_NS).unwrap();
+        let mut staged = Vec::new();
+        for each in self
+            .db
+            .iterator_cf_opt(cf, self.read_opts(), rocksdb::IteratorMode::Start)
+        {
+            let Some((k, v)) = self.row(each)? else {
                 continue;
             };
-            let Some(transfer) = self.find_transfer(&key).await else {
-                tracing::error!("Dangling index entry for a transfer");
+            let Ok(seq) = <[u8; 8]>::try_from(&*k).map(u64::from_be_bytes) else {
+                tracing::error!("Malformed key in `{STAGED_NS}`: {k:?}");
                 continue;
             };
-            transfers.push(transfer);
+            match serde_json::from_slice(&v) {
+                Ok(extracted) => staged.push((seq, extracted)),
+                Err(e) if self.strict => return Err(e.into()),
+                Err(e) => tracing::error!("Failed to read back staged entry #{seq}: {e:?}"),
+            }
         }
-        Ok(transfers)
+        Ok(staged)
     }
-}
 
-/// [store_all_records_from] sans cancellation.
-async fn do_store_all_records_from(mut rx: Receiver<Record>, store: Arc<Store>) {
-    while let Some(record) = rx.recv().await {
-        match record {
-            Record::Vote(vote) => {
-                let res = store.save_vote(&vote).await;
-                if let Err(e) = res {
-                    tracing::error!("Failed to store a vote: {e:?}");
-                    return;
+    /// Write down a Vote record, possibly overwriting the same primary-keyed record.
+    #[instrument(name = "save", skip_all, fields(kind = "vote", block = vote.block_index), err)]
+    pub async fn save_vote(&self, vote: &Vote) -> Result<()> {
+        self.bump_last_known_block(vote.block_index, 1).await?;
+
+        let mut batch = rocksdb::WriteBatch::default();
+        self.put_vote(&mut batch, vote)?;
+        self.write(batch, [Kind::Vote])
+    }
+
+    /// Write down a Transfer record, possibly overwriting the same primary-keyed record.
+    #[instrument(
+        name = "save",
+        skip_all,
+        fields(kind = "transfer", block = transfer.block_index),
+        err
+    )]
+    pub async fn save_transfer(&self, transfer: &Transfer) -> Result<()> {
+        self.bump_last_known_block(transfer.block_index, 1).await?;
+
+        let mut batch = rocksdb::WriteBatch::default();
+        self.put_transfer(&mut batch, transfer)?;
+        self.write(batch, [Kind::Transfer])
+    }
+
+    /// Write down all the records of a block at once, so that either all of them land or none.
+    #[instrument(name = "save", skip_all, fields(records = records.len()), err)]
+    pub async fn save_block(&self, records: &[Record]) -> Result<()> {
+        let Some(block_index) = records.iter().map(Record::block_index).max() else {
+            return Ok(());
+        };
+        self.bump_last_known_block(block_index, records.len() as u64)
+            .await?;
+
+        // Only the last instruction of a transaction would be left anyway.
+        let superseded = self.superseded(records);
+        let written: Vec<_> = records
+            .iter()
+            .enumerate()
+            .filter(|(position, _)| !superseded.contains(position))
+            .map(|(_, record)| record)
+            .collect();
+
+        let mut batch = rocksdb::WriteBatch::default();
+        for &record in &written {
+            match record {
+                Record::Vote(vote) => self.put_vote(&mut batch, vote)?,
+                Record::Transfer(transfer) => self.put_transfer(&mut batch, transfer)?,
+                Record::Reward(reward) => self.put_reward(&mut batch, reward)?,
+                Record::TokenTransfer(transfer) => self.put_token_transfer(&mut batch, transfer)?,
+                Record::StakeDelegation(delegation) => {
+                    self.put_stake_delegation(&mut batch, delegation)?
                 }
             }
-            Record::Transfer(transfer) => {
-                let res = store.save_transfer(&transfer).await;
-                if let Err(e) = res {
-                    tracing::error!("Failed to store a transfer: {e:?}");
-                    return;
-                }
+        }
+        self.write(batch, written.into_iter().map(Record::kind))
+    }
+
+    /// Apply the batch of the records of the given kinds, timing it and counting them.
+    fn write(
+        &self,
+        batch: rocksdb::WriteBatch,
+        kinds: impl IntoIterator<Item = Kind>,
+    ) -> Result<()> {
+        let timer = self
+            .metrics
+            .as_ref()
+            .map(|metrics| metrics.db_write.start_timer());
+        let written = self.db.write_opt(batch, &self.write_opts());
+        if let Some(timer) = timer {
+            timer.observe_duration();
+        }
+        written?;
+        if let Some(metrics) = &self.metrics {
+            for kind in kinds {
+                metrics
+                    .records_stored
+                    .with_label_values(&[kind.name()])
+                    .inc();
             }
         }
+        Ok(())
     }
-}
 
-/// Drain the channel and commit the records to the database.
-pub async fn store_all_records_from(
-    rx: Receiver<Record>,
-    store: Arc<Store>,
-    stop: CancellationToken,
-) {
-    select! {
-        biased; // Making sure the signal gets polled first.
-        _ = stop.cancelled() => {
-            tracing::trace!("Storing cancelled");
+    /// The record the primary key holds already, if it is to be overwritten,
+    /// so that its index entries could go along with it rather than point to whatever takes its place.
+    /// Only keyed by the signature alone may a record get overwritten by another instruction,
+    /// and an unreadable one is left for the migrations to deal with.
+    fn displaced<T: DeserializeOwned>(&self, ns: &str, key: &[u8]) -> Result<Option<T>> {
+        if self.keys == PrimaryKey::Occurrence {
+            return Ok(None);
         }
-        _ = do_store_all_records_from(rx, store) => {
-            tracing::trace!("Stream depleted");
+        let cf = self.db.cf_handle(ns).unwrap();
+        let Some(value) = self.db.get_pinned_cf(cf, key)? else {
+            return Ok(None);
+        };
+        Ok(postcard::from_bytes(&value).ok())
+    }
+
+    /// The positions of the votes and the transfers that a later one of the same primary key
+    /// would overwrite within the same batch, as the reads cannot see what the batch holds.
+    fn superseded(&self, records: &[Record]) -> HashSet<usize> {
+        let mut superseded = HashSet::new();
+        if self.keys == PrimaryKey::Occurrence {
+            return superseded;
+        }
+        let mut seen = HashSet::new();
+        for (position, record) in records.iter().enumerate().rev() {
+            let key = match record {
+                Record::Vote(vote) => (VOTES_NS, vote.signature),
+                Record::Transfer(transfer) => (TRANSFERS_NS, transfer.signature),
+                _ => continue,
+            };
+            if !seen.insert(key) {
+                superseded.insert(position);
+            }
+        }
+        superseded
+    }
+
+    /// Add a Vote record along with its index entries to the batch.
+    fn put_vote(&self, batch: &mut rocksdb::WriteBatch, vote: &Vote) -> Result<()> {
+        // Writing down the contents:
+        let cf = self.db.cf_handle(VOTES_NS).unwrap();
+        let signature = &vote.signature;
+        let key = self.primary_key(signature, vote.block_index, vote.instruction_index);
+        if let Some(displaced) = self.displaced::<Vote>(VOTES_NS, &key)? {
+            self.forget_vote(batch, &displaced);
+        }
+        batch.put_cf(cf, &key, postcard::to_stdvec(&vote)?);
+
+        // Indexing:
+        let cf = self.db.cf_handle(VOTES_INDEX_NS).unwrap();
+        let (block_index, timestamp) =
+            (vote.block_index.to_be_bytes(), vote.timestamp.to_be_bytes());
+        associate(batch, cf, IndexField::Block, &block_index, &key);
+        associate(batch, cf, IndexField::Timestamp, &timestamp, &key);
+        associate(batch, cf, IndexField::Account, vote.target.as_ref(), &key);
+        associate(batch, cf, IndexField::Account, vote.author.as_ref(), &key);
+        associate(
+            batch,
+            cf,
+            IndexField::Program,
+            vote.program_id.as_ref(),
+            &key,
+        );
+
+        let cf = self.db.cf_handle(VOTES_CHRONO_NS).unwrap();
+        associate_chronologically(
+            batch,
+            cf,
+            vote.block_index,
+            vote.instruction_index,
+            signature,
+            &key,
+        );
+
+        Ok(())
+    }
+
+    /// Add a Transfer record along with its index entries to the batch.
+    fn put_transfer(&self, batch: &mut rocksdb::WriteBatch, transfer: &Transfer) -> Result<()> {
+        // The contents:
+        let cf = self.db.cf_handle(TRANSFERS_NS).unwrap();
+        let signature = &transfer.signature;
+        let instruction_index = transfer.instruction_index;
+        let key = self.primary_key(signature, transfer.block_index, instruction_index);
+        if let Some(displaced) = self.displaced::<Transfer>(TRANSFERS_NS, &key)? {
+            self.forget_transfer(batch, &displaced);
         }
+        batch.put_cf(cf, &key, postcard::to_stdvec(&transfer)?);
+
+        // Indexing:
+        let cf = self.db.cf_handle(TRANSFERS_INDEX_NS).unwrap();
+        let (block_index, lamports, timestamp) = (
+            transfer.block_index.to_be_bytes(),
+            transfer.lamports.to_be_bytes(),
+            transfer.timestamp.to_be_bytes(),
+        );
+        associate(batch, cf, IndexField::Block, &block_index, &key);
+        associate(batch, cf, IndexField::Timestamp, &timestamp, &key);
+        associate(
+            batch,
+            cf,
+            IndexField::Account,
+            transfer.source.as_ref(),
+            &key,
+        );
+        associate(
+            batch,
+            cf,
+            IndexField::Account,
+            transfer.destination.as_ref(),
+            &key,
+        );
+        associate(batch, cf, IndexField::Lamports, &lamports, &key);
+        associate(
+            batch,
+            cf,
+            IndexField::Program,
+            transfer.program_id.as_ref(),
+            &key,
+        );
+
+        let cf = self.db.cf_handle(TRANSFERS_CHRONO_NS).unwrap();
+        associate_chronologically(
+            batch,
+            cf,
+            transfer.block_index,
+            instruction_index,
+            signature,
+            &key,
+        );
+
+        Ok(())
+    }
+
+    /// Add a Reward record along with its index entry to the batch.
+    /// Its key is its position, so the block index needs no index of its own.
+    fn put_reward(&self, batch: &mut rocksdb::WriteBatch, reward: &Reward) -> Result<()> {
+        // The contents:
+        let cf = self.db.cf_handle(REWARDS_NS).unwrap();
+        let key = reward_key(reward.block_index, reward.reward_index);
+        batch.put_cf(cf, &key, postcard::to_stdvec(&reward)?);
+
+        // Indexing:
+        let cf = self.db.cf_handle(REWARDS_INDEX_NS).unwrap();
+        batch.put_cf(cf, reward_index_key(&reward.pubkey, &key), &key);
+
+        Ok(())
+    }
+
+    /// Add a TokenTransfer record along with its index entries to the batch.
+    /// Like a reward, it is keyed by its position, so that the blocks need no index of their own,
+    /// with the signature along, so that the key tells the transaction without decoding.
+    fn put_token_transfer(
+        &self,
+        batch: &mut rocksdb::WriteBatch,
+        transfer: &TokenTransfer,
+    ) -> Result<()> {
+        // The contents:
+        let cf = self.db.cf_handle(TOKEN_TRANSFERS_NS).unwrap();
+        let (block_index, instruction_index) = (transfer.block_index, transfer.instruction_index);
+        let key = chrono_key(block_index, instruction_index, &transfer.signature);
+        batch.put_cf(cf, &key, postcard::to_stdvec(&transfer)?);
+
+        // Indexing:
+        let cf = self.db.cf_handle(TOKEN_TRANSFERS_INDEX_NS).unwrap();
+        for account in transfer.accounts() {
+            batch.put_cf(cf, reward_index_key(account, &key), &key);
+        }
+
+        Ok(())
+    }
+
+    /// Add a StakeDelegation record along with its index entries to the batch,
+    /// keyed the same way as [Store::put_token_transfer].
+    fn put_stake_delegation(
+        &self,
+        batch: &mut rocksdb::WriteBatch,
+        delegation: &StakeDelegation,
+    ) -> Result<()> {
+        // The contents:
+        let cf = self.db.cf_handle(STAKE_DELEGATIONS_NS).unwrap();
+        let (block_index, instruction_index) =
+            (delegation.block_index, delegation.instruction_index);
+        let key = chrono_key(block_index, instruction_index, &delegation.signature);
+        batch.put_cf(cf, &key, postcard::to_stdvec(&delegation)?);
+
+        // Indexing:
+        let cf = self.db.cf_handle(STAKE_DELEGATIONS_INDEX_NS).unwrap();
+        for account in delegation.accounts() {
+            batch.put_cf(cf, reward_index_key(account, &key), &key);
+        }
+
+        Ok(())
+    }
+}
+
+/// How big a column family is, as estimated by the database itself.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct CfStats {
+    pub name: &'static str,
+    pub estimated_keys: u64,
+    /// Bytes in the table files of the current version.
+    pub live_sst_files_size: u64,
+    /// Bytes in all the table files, including the ones awaiting deletion.
+    pub total_sst_files_size: u64,
+    /// How many table files there are across all the levels.
+    pub sst_files: u64,
+}
+
+/// How many levels the database has by default.
+const LEVELS: usize = 7;
+
+impl Store {
+    /// Compact the whole of the column family, blocking until done.
+    pub fn compact(&self, name: &str) {
+        let cf = self.db.cf_handle(name).unwrap();
+        self.db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+    }
+
+    /// About how many votes there are, without going through them.
+    pub fn estimated_votes(&self) -> Result<u64> {
+        self.estimated_keys(VOTES_NS)
+    }
+
+    /// About how many transfers there are, without going through them.
+    pub fn estimated_transfers(&self) -> Result<u64> {
+        self.estimated_keys(TRANSFERS_NS)
+    }
+
+    fn estimated_keys(&self, name: &str) -> Result<u64> {
+        let cf = self.db.cf_handle(name).unwrap();
+        let keys = self
+            .db
+            .property_int_value_cf(cf, "rocksdb.estimate-num-keys")?;
+        Ok(keys.unwrap_or(0))
+    }
+
+    /// How many bytes the table files of all the column families take up on disk.
+    pub fn approx_size_bytes(&self) -> Result<u64> {
+        let mut size = 0;
+        for name in COLUMN_FAMILIES {
+            let cf = self.db.cf_handle(name).unwrap();
+            let property = self
+                .db
+                .property_int_value_cf(cf, "rocksdb.total-sst-files-size")?;
+            size += property.unwrap_or(0);
+        }
+        Ok(size)
+    }
+
+    /// The size estimates of every column family.
+    pub fn cf_stats(&self) -> Result<Vec<CfStats>> {
+        let mut stats = Vec::with_capacity(COLUMN_FAMILIES.len());
+        for name in COLUMN_FAMILIES {
+            let cf = self.db.cf_handle(name).unwrap();
+            let property = |property: &str| -> Result<u64> {
+                Ok(self.db.property_int_value_cf(cf, property)?.unwrap_or(0))
+            };
+            let mut sst_files = 0;
+            for level in 0..LEVELS {
+                sst_files += property(&format!("rocksdb.num-files-at-level{level}"))?;
+            }
+            stats.push(CfStats {
+                name,
+                estimated_keys: property("rocksdb.estimate-num-keys")?,
+                live_sst_files_size: property("rocksdb.live-sst-files-size")?,
+                total_sst_files_size: property("rocksdb.total-sst-files-size")?,
+                sst_files,
+            });
+        }
+        Ok(stats)
+    }
+}
+
+/// What came out of reading the records back from a file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// How many records got written down.
+    pub imported: u64,
+    /// How many lines could not be read as a vote or a transfer.
+    pub skipped: u64,
+}
+
+/// What came out of checking the indexes of the votes and the transfers against their records.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// How many index entries point to no record.
+    pub dangling_indexes: u64,
+    /// How many records are missing from the index by the block.
+    pub orphaned_records: u64,
+}
+
+impl IntegrityReport {
+    pub fn is_consistent(&self) -> bool {
+        self.dangling_indexes == 0 && self.orphaned_records == 0
+    }
+}
+
+impl Store {
+    /// Go through every index entry of the votes and the transfers to see that it points to a record,
+    /// then through every record to see that it is indexed by its block, as every one gets to be.
+    pub fn check_integrity(&self) -> Result<IntegrityReport> {
+        let mut report = IntegrityReport::default();
+        for (ns, indexes) in [
+            (VOTES_NS, [VOTES_INDEX_NS, VOTES_CHRONO_NS]),
+            (TRANSFERS_NS, [TRANSFERS_INDEX_NS, TRANSFERS_CHRONO_NS]),
+        ] {
+            let cf = self.db.cf_handle(ns).unwrap();
+            for index in indexes {
+                let index_cf = self.db.cf_handle(index).unwrap();
+                let mode = rocksdb::IteratorMode::Start;
+                for each in self.db.iterator_cf_opt(index_cf, self.read_opts(), mode) {
+                    let Some((k, primary_key)) = self.row(each)? else {
+                        continue;
+                    };
+                    if self.db.get_pinned_cf(cf, &primary_key)?.is_none() {
+                        tracing::warn!("Dangling entry in `{index}`: {k:?}");
+                        report.dangling_indexes += 1;
+                    }
+                }
+            }
+        }
+        report.orphaned_records +=
+            self.count_orphans(VOTES_NS, VOTES_INDEX_NS, |x: &Vote| x.block_index)?;
+        report.orphaned_records +=
+            self.count_orphans(TRANSFERS_NS, TRANSFERS_INDEX_NS, |x: &Transfer| {
+                x.block_index
+            })?;
+        Ok(report)
+    }
+
+    /// How many records of the column family have no entry under their block in the index.
+    fn count_orphans<T: DeserializeOwned>(
+        &self,
+        ns: &str,
+        index_ns: &str,
+        block_of: impl Fn(&T) -> u64,
+    ) -> Result<u64> {
+        let cf = self.db.cf_handle(ns).unwrap();
+        let index_cf = self.db.cf_handle(index_ns).unwrap();
+
+        let mut orphans = 0;
+        let mode = rocksdb::IteratorMode::Start;
+        for each in self.db.iterator_cf_opt(cf, self.read_opts(), mode) {
+            let Some((k, v)) = self.row(each)? else {
+                continue;
+            };
+            let Some(record) = self.decode(&v)? else {
+                continue;
+            };
+            let block = block_of(&record).to_be_bytes();
+            let entry = index_key(IndexField::Block, &block, &k);
+            if self.db.get_pinned_cf(index_cf, entry)?.is_none() {
+                tracing::warn!("Orphaned record in `{ns}`: {k:?}");
+                orphans += 1;
+            }
+        }
+        Ok(orphans)
+    }
+}
+
+/// A run of consecutive blocks that left nothing in the store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Gap {
+    pub from: u64,
+    pub to: u64,
+    /// Whether the cluster has skipped these, rather than them never getting processed.
+    pub skipped: bool,
+}
+
+/// Some of the records in their on-chain order, and where the page after them starts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Page<T> {
+    pub records: Vec<T>,
+    /// The chronological key of the last record, if the page is full, so that there may be more.
+    pub next: Option<Vec<u8>>,
+}
+
+/// How many records a page may pass over for not matching the filters,
+/// as it goes by the chronological index whatever the query.
+pub const MAX_SCANNED: usize = 100_000;
+
+/// Where a page starts: right after a chronological key, or at the first of the blocks, or both,
+/// then past as many of the records taken.
+#[derive(Clone, Debug)]
+struct PageStart<'a> {
+    after: Option<&'a [u8]>,
+    blocks: RangeInclusive<u64>,
+    offset: usize,
+}
+
+/// A key-value pair as yielded by the database iterators.
+type Row = (Box<[u8]>, Box<[u8]>);
+
+/// The records of one kind from some block on, as they get read; see [Store::iter_since].
+pub struct Cursor<'a> {
+    store: &'a Store,
+    kind: Kind,
+    entries: rocksdb::DBIteratorWithThreadMode<'a, rocksdb::DB>,
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (_, value) = match self.store.row(self.entries.next()?) {
+                Ok(Some(row)) => row,
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            };
+            // The rewards, the token transfers, and the stake delegations are stored in place,
+            // the rest pointed to by their primary keys.
+            let store = self.store;
+            let record = match self.kind {
+                Kind::Vote => store
+                    .find_one(VOTES_NS, &value)
+                    .map(|x| x.map(Record::Vote)),
+                Kind::Transfer => store
+                    .find_one(TRANSFERS_NS, &value)
+                    .map(|x| x.map(Record::Transfer)),
+                Kind::Reward => store.decode(&value).map(|x| x.map(Record::Reward)),
+                Kind::TokenTransfer => store.decode(&value).map(|x| x.map(Record::TokenTransfer)),
+                Kind::StakeDelegation => {
+                    store.decode(&value).map(|x| x.map(Record::StakeDelegation))
+                }
+            };
+            match record {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) if matches!(self.kind, Kind::Vote | Kind::Transfer) => {
+                    tracing::error!("Dangling index entry for a {:?}", self.kind);
+                }
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Which rows of a column family to go through.
+#[derive(Clone, Copy, Debug)]
+enum Scan<'a> {
+    All,
+    /// Only the rows whose keys start with these bytes.
+    Prefix(&'a [u8]),
+    /// Only the rows whose keys start with a block from the first to the last, big-endian.
+    Blocks(u64, u64),
+}
+
+impl Store {
+    /// The bytes stored under the exact key in the named column family.
+    pub fn raw_get(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let Some(cf) = self.db.cf_handle(cf) else {
+            return Err(Error::NotFound);
+        };
+        Ok(self.db.get_cf(cf, key)?)
+    }
+
+    /// Up to `limit` rows of the named column family whose keys start with the prefix.
+    pub fn raw_scan(&self, cf: &str, prefix: &[u8], limit: usize) -> Result<Vec<Row>> {
+        let Some(cf) = self.db.cf_handle(cf) else {
+            return Err(Error::NotFound);
+        };
+        let mode = rocksdb::IteratorMode::From(prefix, rocksdb::Direction::Forward);
+
+        let mut rows = Vec::new();
+        for each in self.db.iterator_cf_opt(cf, self.read_opts(), mode) {
+            let (k, v) = each?;
+            if !k.starts_with(prefix) || rows.len() >= limit {
+                break;
+            }
+            rows.push((k, v));
+        }
+        Ok(rows)
+    }
+}
+
+impl Store {
+    /// Take a row yielded by an iterator.
+    /// In strict mode, a failed read aborts the whole query;
+    /// otherwise, it gets logged and skipped.
+    fn row(&self, each: std::result::Result<Row, rocksdb::Error>) -> Result<Option<Row>> {
+        match each {
+            Ok(row) => Ok(Some(row)),
+            Err(e) if self.strict => Err(e.into()),
+            Err(e) => {
+                tracing::error!("Failed to get a row from the database: {e:?}");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Decode a stored value, failing or skipping the same way as [Store::row] does.
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<Option<T>> {
+        match postcard::from_bytes(bytes) {
+            Ok(decoded) => Ok(Some(decoded)),
+            Err(e) if self.strict => Err(e.into()),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl Store {
+    /// Get the Vote records with the given signatures in one go,
+    /// in the order asked, skipping the ones that do not exist.
+    /// Keyed by the occurrence, a signature may yield more than one.
+    pub async fn find_votes(&self, signatures: &[Signature]) -> Result<Vec<Vote>> {
+        self.find_by_signatures(VOTES_NS, signatures)
+    }
+
+    /// Get the Transfer records with the given signatures in one go,
+    /// in the order asked, skipping the ones that do not exist.
+    /// Keyed by the occurrence, a signature may yield more than one.
+    pub async fn find_transfers(&self, signatures: &[Signature]) -> Result<Vec<Transfer>> {
+        self.find_by_signatures(TRANSFERS_NS, signatures)
+    }
+
+    /// Get the Vote records with the given signature, if any.
+    pub async fn find_vote(&self, signature: &Signature) -> Result<Vec<Vote>> {
+        self.find_by_signatures(VOTES_NS, std::slice::from_ref(signature))
+    }
+
+    /// Get the Transfer records with the given signature, if any.
+    pub async fn find_transfer(&self, signature: &Signature) -> Result<Vec<Transfer>> {
+        self.find_by_signatures(TRANSFERS_NS, std::slice::from_ref(signature))
+    }
+
+    /// Whether any vote or transfer has the given signature, without reading it.
+    pub async fn has_signature(&self, signature: &Signature) -> Result<bool> {
+        let prefix = postcard::to_stdvec(signature).unwrap();
+        for ns in [VOTES_NS, TRANSFERS_NS] {
+            let cf = self.db.cf_handle(ns).unwrap();
+            let found = if self.keys == PrimaryKey::Signature {
+                // The signature is the whole key, so no need to scan.
+                self.db.get_pinned_cf(cf, &prefix)?.is_some()
+            } else {
+                let mut entries = self.db.raw_iterator_cf_opt(cf, self.read_opts());
+                entries.seek(&prefix);
+                entries.status()?;
+                entries.key().is_some_and(|key| key.starts_with(&prefix))
+            };
+            if found {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn find_by_signatures<T: DeserializeOwned>(
+        &self,
+        ns: &str,
+        signatures: &[Signature],
+    ) -> Result<Vec<T>> {
+        let prefixes: Vec<_> = signatures
+            .iter()
+            .map(|signature| postcard::to_stdvec(signature).unwrap())
+            .collect();
+        if self.keys == PrimaryKey::Signature {
+            // The signature is the whole key, so no need to scan.
+            return self.find_many(ns, &prefixes);
+        }
+
+        let mut found = Vec::with_capacity(prefixes.len());
+        for prefix in prefixes {
+            found.extend(self.scan(ns, Scan::Prefix(&prefix))?);
+        }
+        Ok(found)
+    }
+
+    /// The records under the given primary keys, in the order asked,
+    /// skipping the ones that do not exist.
+    fn find_many<T: DeserializeOwned>(&self, ns: &str, keys: &[Vec<u8>]) -> Result<Vec<T>> {
+        let cf = self.db.cf_handle(ns).unwrap();
+
+        let mut found = Vec::with_capacity(keys.len());
+        for each in self.db.multi_get_cf(keys.iter().map(|key| (cf, key))) {
+            let value = match each {
+                Ok(Some(value)) => value,
+                Ok(None) => continue,
+                Err(e) if self.strict => return Err(e.into()),
+                Err(e) => {
+                    tracing::error!("Failed to get a row from the database: {e:?}");
+                    continue;
+                }
+            };
+            let Some(record) = self.decode(&value)? else {
+                continue;
+            };
+            found.push(record);
+        }
+        Ok(found)
+    }
+
+    /// The record under the given primary key, if it exists and is readable.
+    fn find_one<T: DeserializeOwned>(&self, ns: &str, key: &[u8]) -> Result<Option<T>> {
+        let cf = self.db.cf_handle(ns).unwrap();
+        let Some(value) = self.db.get_pinned_cf(cf, key)? else {
+            return Ok(None);
+        };
+        self.decode(&value)
+    }
+
+    /// How many votes `admit` takes, going through all of them by the time `deadline` comes.
+    pub async fn count_votes(
+        &self,
+        deadline: Instant,
+        admit: impl Fn(&Vote) -> bool,
+    ) -> Result<usize> {
+        self.count_all(VOTES_NS, deadline, admit)
+    }
+
+    /// How many transfers `admit` takes, going through all of them by the time `deadline` comes.
+    pub async fn count_transfers(
+        &self,
+        deadline: Instant,
+        admit: impl Fn(&Transfer) -> bool,
+    ) -> Result<usize> {
+        self.count_all(TRANSFERS_NS, deadline, admit)
+    }
+
+    fn count_all<T: DeserializeOwned>(
+        &self,
+        ns: &str,
+        deadline: Instant,
+        admit: impl Fn(&T) -> bool,
+    ) -> Result<usize> {
+        let started = Instant::now();
+        let cf = self.db.cf_handle(ns).unwrap();
+
+        let mut count = 0;
+        for each in self
+            .db
+            .iterator_cf_opt(cf, self.read_opts(), rocksdb::IteratorMode::Start)
+        {
+            if Instant::now() >= deadline {
+                return Err(Error::TimedOut(started.elapsed()));
+            }
+            let Some((_k, v)) = self.row(each)? else {
+                continue;
+            };
+            let Some(record) = self.decode(&v)? else {
+                continue;
+            };
+            if admit(&record) {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Retrieve all the matching records from the database.
+    pub async fn find_all_votes(&self) -> Result<Vec<Vote>> {
+        self.scan(VOTES_NS, Scan::All)
+    }
+
+    /// Retrieve all the matching records from the database.
+    pub async fn find_all_transfers(&self) -> Result<Vec<Transfer>> {
+        self.scan(TRANSFERS_NS, Scan::All)
+    }
+
+    /// Retrieve all the records from the database, in their on-chain order.
+    pub async fn find_all_votes_chronological(&self) -> Result<Vec<Vote>> {
+        self.find_chronological(VOTES_CHRONO_NS, VOTES_NS)
+    }
+
+    /// Retrieve all the records from the database, in their on-chain order.
+    pub async fn find_all_transfers_chronological(&self) -> Result<Vec<Transfer>> {
+        self.find_chronological(TRANSFERS_CHRONO_NS, TRANSFERS_NS)
+    }
+
+    /// The decoded records of the column family, in the order of their keys.
+    fn scan<T: DeserializeOwned>(&self, ns: &str, scan: Scan) -> Result<Vec<T>> {
+        let cf = self.db.cf_handle(ns).unwrap();
+        let start = match scan {
+            Scan::All => vec![],
+            Scan::Prefix(prefix) => prefix.to_vec(),
+            Scan::Blocks(from, _) => from.to_be_bytes().to_vec(),
+        };
+        let within = |key: &[u8]| match scan {
+            Scan::All => true,
+            Scan::Prefix(prefix) => key.starts_with(prefix),
+            Scan::Blocks(_, to) => key
+                .get(..8)
+                .is_some_and(|block| block <= &to.to_be_bytes()[..]),
+        };
+        let mode = rocksdb::IteratorMode::From(&start, rocksdb::Direction::Forward);
+
+        let mut found = Vec::new();
+        for each in self.db.iterator_cf_opt(cf, self.read_opts(), mode) {
+            let Some((k, v)) = self.row(each)? else {
+                continue;
+            };
+            if !within(&k) {
+                break;
+            }
+            let Some(record) = self.decode(&v)? else {
+                continue;
+            };
+            found.push(record);
+        }
+        Ok(found)
+    }
+
+    /// The chronological key of the vote with the given signature, to page on from;
+    /// the latest one, should the signature occur more than once.
+    pub async fn position_of_vote(&self, signature: &Signature) -> Result<Option<Vec<u8>>> {
+        let votes = self.find_votes(std::slice::from_ref(signature)).await?;
+        let latest = votes
+            .iter()
+            .map(|x| (x.block_index, x.instruction_index))
+            .max();
+        Ok(latest.map(|(block, instruction)| chrono_key(block, instruction, signature)))
+    }
+
+    /// The chronological key of the transfer with the given signature, the same way as
+    /// [Store::position_of_vote].
+    pub async fn position_of_transfer(&self, signature: &Signature) -> Result<Option<Vec<u8>>> {
+        let transfers = self.find_transfers(std::slice::from_ref(signature)).await?;
+        let latest = transfers
+            .iter()
+            .map(|x| (x.block_index, x.instruction_index))
+            .max();
+        Ok(latest.map(|(block, instruction)| chrono_key(block, instruction, signature)))
+    }
+
+    /// Up to `limit` of the votes `admit` takes within the `blocks`, in their on-chain order,
+    /// from right after the chronological key `after` if given, skipping the first `offset` of them.
+    pub async fn find_votes_page(
+        &self,
+        after: Option<&[u8]>,
+        blocks: RangeInclusive<u64>,
+        offset: usize,
+        limit: usize,
+        admit: impl Fn(&Vote) -> bool,
+    ) -> Result<Page<Vote>> {
+        let start = PageStart {
+            after,
+            blocks,
+            offset,
+        };
+        self.page_chronological(VOTES_CHRONO_NS, VOTES_NS, start, limit, MAX_SCANNED, admit)
+    }
+
+    /// Up to `limit` of the transfers `admit` takes, the same way as [Store::find_votes_page].
+    pub async fn find_transfers_page(
+        &self,
+        after: Option<&[u8]>,
+        blocks: RangeInclusive<u64>,
+        offset: usize,
+        limit: usize,
+        admit: impl Fn(&Transfer) -> bool,
+    ) -> Result<Page<Transfer>> {
+        let start = PageStart {
+            after,
+            blocks,
+            offset,
+        };
+        self.page_chronological(
+            TRANSFERS_CHRONO_NS,
+            TRANSFERS_NS,
+            start,
+            limit,
+            MAX_SCANNED,
+            admit,
+        )
+    }
+
+    /// Seek into the chronological index `chrono_ns` where the page starts, and go on
+    /// until `limit` of the records of `ns` it points to are taken, or the blocks are over;
+    /// giving up once more than `max_scanned` of them are passed over.
+    fn page_chronological<T: DeserializeOwned>(
+        &self,
+        chrono_ns: &str,
+        ns: &str,
+        start: PageStart,
+        limit: usize,
+        max_scanned: usize,
+        admit: impl Fn(&T) -> bool,
+    ) -> Result<Page<T>> {
+        let cf = self.db.cf_handle(chrono_ns).unwrap();
+        let mut entries = self.db.raw_iterator_cf_opt(cf, self.read_opts());
+        // The keys start with the block, big-endian, so that the first block is a seek away,
+        // and so is the record after the last one of the page before.
+        let first = start.blocks.start().to_be_bytes();
+        match start.after {
+            Some(after) if after >= &first[..] => {
+                entries.seek(after);
+                if entries.key() == Some(after) {
+                    entries.next();
+                }
+            }
+            _ => entries.seek(first),
+        }
+
+        let mut records = Vec::new();
+        let mut last_key = None;
+        let (mut skipped, mut passed_over) = (0, 0);
+        while records.len() < limit {
+            let (Some(key), Some(primary_key)) = (entries.key(), entries.value()) else {
+                break;
+            };
+            if passed_over > max_scanned {
+                return Err(Error::TooBroad(max_scanned));
+            }
+            let block = u64::from_be_bytes(key[..8].try_into().unwrap());
+            if block > *start.blocks.end() {
+                break;
+            }
+            match self.find_one::<T>(ns, primary_key)? {
+                Some(record) if admit(&record) => {
+                    // The offset counts the records taken, so the ones passed over get checked too.
+                    if skipped < start.offset {
+                        skipped += 1;
+                    } else {
+                        records.push(record);
+                        last_key = Some(key.to_vec());
+                    }
+                }
+                Some(_) => passed_over += 1,
+                None => {
+                    tracing::error!("Dangling index entry in `{chrono_ns}`");
+                    passed_over += 1;
+                }
+            }
+            entries.next();
+        }
+        entries.status()?;
+
+        // Only a full page may have more after it.
+        let next = last_key.filter(|_| records.len() == limit);
+        Ok(Page { records, next })
+    }
+
+    /// The records of `ns` in the order of the chronological index `chrono_ns` pointing to them.
+    fn find_chronological<T: DeserializeOwned>(&self, chrono_ns: &str, ns: &str) -> Result<Vec<T>> {
+        let cf = self.db.cf_handle(chrono_ns).unwrap();
+
+        let mut found = Vec::new();
+        for each in self
+            .db
+            .iterator_cf_opt(cf, self.read_opts(), rocksdb::IteratorMode::Start)
+        {
+            let Some((_k, key)) = self.row(each)? else {
+                continue;
+            };
+            let Some(record) = self.find_one(ns, &key)? else {
+                tracing::error!("Dangling index entry in `{chrono_ns}`");
+                continue;
+            };
+            found.push(record);
+        }
+        Ok(found)
+    }
+
+    /// The newest records of both kinds, up to `limit` of them, the newest first.
+    /// Walks both chronological indexes backwards at once, always taking the newer entry,
+    /// so that at most `limit` entries of each index get looked at.
+    pub async fn recent_records(&self, limit: usize) -> Result<Vec<Record>> {
+        let mode = rocksdb::IteratorMode::End;
+        let votes_cf = self.db.cf_handle(VOTES_CHRONO_NS).unwrap();
+        let transfers_cf = self.db.cf_handle(TRANSFERS_CHRONO_NS).unwrap();
+        let mut votes = self
+            .db
+            .iterator_cf_opt(votes_cf, self.backward_read_opts(), mode);
+        let mut transfers = self
+            .db
+            .iterator_cf_opt(transfers_cf, self.backward_read_opts(), mode);
+
+        let mut next_vote = self.next_chronological(&mut votes)?;
+        let mut next_transfer = self.next_chronological(&mut transfers)?;
+        let mut records = Vec::with_capacity(limit);
+        while records.len() < limit {
+            // The keys start with the block and the position within it, both big-endian,
+            // so the greater key is the newer record.
+            let take_vote = match (&next_vote, &next_transfer) {
+                (None, None) => break,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (Some((vote_key, _)), Some((transfer_key, _))) => vote_key > transfer_key,
+            };
+            if take_vote {
+                let (_, key) = next_vote.take().unwrap();
+                match self.find_one(VOTES_NS, &key)? {
+                    Some(vote) => records.push(Record::Vote(vote)),
+                    None => tracing::error!("Dangling index entry for a vote"),
+                }
+                next_vote = self.next_chronological(&mut votes)?;
+            } else {
+                let (_, key) = next_transfer.take().unwrap();
+                match self.find_one(TRANSFERS_NS, &key)? {
+                    Some(transfer) => records.push(Record::Transfer(transfer)),
+                    None => tracing::error!("Dangling index entry for a transfer"),
+                }
+                next_transfer = self.next_chronological(&mut transfers)?;
+            }
+        }
+        Ok(records)
+    }
+
+    /// The next readable entry of a chronological index, if there is any left.
+    fn next_chronological(
+        &self,
+        entries: &mut impl Iterator<Item = std::result::Result<Row, rocksdb::Error>>,
+    ) -> Result<Option<Row>> {
+        for each in entries {
+            if let Some(row) = self.row(each)? {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The records of the kind from the blocks past `block_index` on, in their on-chain order,
+    /// for the consumers pulling whatever is new since they last looked.
+    pub fn iter_since(&self, kind: Kind, block_index: u64) -> Cursor<'_> {
+        // The chronological keys, as well as those of the records stored in place, start with the block,
+        // big-endian, so the first block past this one is a seek away.
+        let start = block_index.saturating_add(1).to_be_bytes();
+        self.cursor(kind, &start)
+    }
+
+    /// All the records of the kind, in their on-chain order, read as they are needed.
+    pub fn iter_all(&self, kind: Kind) -> Cursor<'_> {
+        self.cursor(kind, &[])
+    }
+
+    /// The records of the kind from the given chronological key on.
+    fn cursor(&self, kind: Kind, start: &[u8]) -> Cursor<'_> {
+        let ns = match kind {
+            Kind::Vote => VOTES_CHRONO_NS,
+            Kind::Transfer => TRANSFERS_CHRONO_NS,
+            Kind::Reward => REWARDS_NS,
+            Kind::TokenTransfer => TOKEN_TRANSFERS_NS,
+            Kind::StakeDelegation => STAKE_DELEGATIONS_NS,
+        };
+        let cf = self.db.cf_handle(ns).unwrap();
+        let mode = rocksdb::IteratorMode::From(start, rocksdb::Direction::Forward);
+        Cursor {
+            store: self,
+            kind,
+            entries: self.db.iterator_cf_opt(cf, self.read_opts(), mode),
+        }
+    }
+
+    /// Retrieve all the matching records from the database.
+    pub async fn find_votes_by_block_index(&self, block_index: u64) -> Result<Vec<Vote>> {
+        self.find_votes_by_block_range(block_index, block_index)
+            .await
+    }
+
+    /// Retrieve all the votes within the blocks from `from` to `to` inclusive,
+    /// by the block, but in no particular order within it.
+    pub async fn find_votes_by_block_range(&self, from: u64, to: u64) -> Result<Vec<Vote>> {
+        let keys = self.index_range(VOTES_INDEX_NS, IndexField::Block, from, to)?;
+        self.find_indexed(VOTES_NS, &keys)
+    }
+
+    /// Retrieve all the matching records from the database.
+    pub async fn find_transfers_by_block_index(&self, block_index: u64) -> Result<Vec<Transfer>> {
+        self.find_transfers_by_block_range(block_index, block_index)
+            .await
+    }
+
+    /// Retrieve all the records within the blocks from `from` to `to` inclusive,
+    /// by the block, but in no particular order within it.
+    pub async fn find_transfers_by_block_range(&self, from: u64, to: u64) -> Result<Vec<Transfer>> {
+        let keys = self.index_range(TRANSFERS_INDEX_NS, IndexField::Block, from, to)?;
+        self.find_indexed(TRANSFERS_NS, &keys)
+    }
+
+    /// Retrieve all the votes of the blocks produced from `from` to `to` inclusive,
+    /// in seconds since the Unix epoch, by the time, but in no particular order within a second.
+    pub async fn find_votes_by_timestamp_range(&self, from: u64, to: u64) -> Result<Vec<Vote>> {
+        let keys = self.index_range(VOTES_INDEX_NS, IndexField::Timestamp, from, to)?;
+        self.find_indexed(VOTES_NS, &keys)
+    }
+
+    /// Retrieve all the transfers the same way as [Store::find_votes_by_timestamp_range].
+    pub async fn find_transfers_by_timestamp_range(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<Transfer>> {
+        let keys = self.index_range(TRANSFERS_INDEX_NS, IndexField::Timestamp, from, to)?;
+        self.find_indexed(TRANSFERS_NS, &keys)
+    }
+
+    /// Retrieve all the records where the given account is the source or the destination.
+    /// The index is shared across the fields, so the caller should filter the results.
+    pub async fn find_transfers_by_account(&self, account: &Pubkey) -> Result<Vec<Transfer>> {
+        self.find_transfers_by_account_up_to(account, usize::MAX)
+            .await
+    }
+
+    /// The same as [Store::find_transfers_by_account], but looking at `limit` entries at most.
+    pub async fn find_transfers_by_account_up_to(
+        &self,
+        account: &Pubkey,
+        limit: usize,
+    ) -> Result<Vec<Transfer>> {
+        let prefix = IndexField::Account.prefix(account.as_ref());
+        let mut scanned = 0;
+        let keys = self.index_scan(TRANSFERS_INDEX_NS, &prefix, |key| {
+            scanned += 1;
+            scanned <= limit && key.starts_with(&prefix)
+        })?;
+        self.find_indexed(TRANSFERS_NS, &keys)
+    }
+
+    /// Retrieve the votes where both accounts take part, intersecting their index entries
+    /// before looking up a single record, the fewer of the two against the more.
+    /// The index is shared across the fields, so the caller should filter the results.
+    pub async fn find_votes_by_account_pair(
+        &self,
+        from: &Pubkey,
+        to: &Pubkey,
+    ) -> Result<Vec<Vote>> {
+        let keys_of = |account: &Pubkey| {
+            let prefix = IndexField::Account.prefix(account.as_ref());
+            self.index_scan(VOTES_INDEX_NS, &prefix, |key| key.starts_with(&prefix))
+        };
+        let (of_from, of_to) = (keys_of(from)?, keys_of(to)?);
+        let (fewer, more) = if of_from.len() <= of_to.len() {
+            (of_from, of_to)
+        } else {
+            (of_to, of_from)
+        };
+        // The entries under a prefix come by the primary key, so the more of them are sorted already.
+        let mut keys: Vec<_> = fewer
+            .into_iter()
+            .filter(|key| more.binary_search(key).is_ok())
+            .collect();
+        // So are the fewer, so that a record gets looked up once, however many entries point to it.
+        keys.dedup();
+        self.find_indexed(VOTES_NS, &keys)
+    }
+
+    /// Retrieve all the votes the given program produced.
+    pub async fn find_votes_by_program(&self, program_id: &Pubkey) -> Result<Vec<Vote>> {
+        let prefix = IndexField::Program.prefix(program_id.as_ref());
+        self.find_by_index_prefix(VOTES_INDEX_NS, VOTES_NS, &prefix)
+    }
+
+    /// Retrieve all the transfers the given program produced.
+    pub async fn find_transfers_by_program(&self, program_id: &Pubkey) -> Result<Vec<Transfer>> {
+        let prefix = IndexField::Program.prefix(program_id.as_ref());
+        self.find_by_index_prefix(TRANSFERS_INDEX_NS, TRANSFERS_NS, &prefix)
+    }
+
+    /// Retrieve all the rewards of the given block, in the order the block lists them.
+    pub async fn find_rewards_by_block_index(&self, block_index: u64) -> Result<Vec<Reward>> {
+        self.scan(REWARDS_NS, Scan::Prefix(&block_index.to_be_bytes()))
+    }
+
+    /// Retrieve all the rewards of the given account, by the block.
+    pub async fn find_rewards_by_pubkey(&self, pubkey: &Pubkey) -> Result<Vec<Reward>> {
+        self.find_by_index_prefix(REWARDS_INDEX_NS, REWARDS_NS, pubkey.as_ref())
+    }
+
+    /// Retrieve all the token transfers of the given block, in their on-chain order.
+    pub async fn find_token_transfers_by_block_index(
+        &self,
+        block_index: u64,
+    ) -> Result<Vec<TokenTransfer>> {
+        self.scan(TOKEN_TRANSFERS_NS, Scan::Prefix(&block_index.to_be_bytes()))
+    }
+
+    /// Retrieve all the token transfers the given account took part in,
+    /// be it the mint, the owner, or either token account, in their on-chain order.
+    pub async fn find_token_transfers_by_account(
+        &self,
+        account: &Pubkey,
+    ) -> Result<Vec<TokenTransfer>> {
+        self.find_by_index_prefix(
+            TOKEN_TRANSFERS_INDEX_NS,
+            TOKEN_TRANSFERS_NS,
+            account.as_ref(),
+        )
+    }
+
+    /// Retrieve all the stake delegations within the blocks from `from` to `to` inclusive,
+    /// in their on-chain order.
+    pub async fn find_stake_delegations_by_block_range(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<StakeDelegation>> {
+        self.scan(STAKE_DELEGATIONS_NS, Scan::Blocks(from, to))
+    }
+
+    /// Retrieve all the stake delegations the given account took part in,
+    /// be it the stake account, the vote account, or the authority, in their on-chain order.
+    pub async fn find_stake_delegations_by_account(
+        &self,
+        account: &Pubkey,
+    ) -> Result<Vec<StakeDelegation>> {
+        let (index_ns, ns) = (STAKE_DELEGATIONS_INDEX_NS, STAKE_DELEGATIONS_NS);
+        self.find_by_index_prefix(index_ns, ns, account.as_ref())
+    }
+
+    /// How many votes the given block holds, by the index, without decoding any.
+    pub async fn count_votes_by_block_index(&self, block_index: u64) -> Result<usize> {
+        let keys = self.index_range(VOTES_INDEX_NS, IndexField::Block, block_index, block_index)?;
+        self.count_present(VOTES_NS, keys)
+    }
+
+    /// How many transfers the given block holds, by the index, without decoding any.
+    pub async fn count_transfers_by_block_index(&self, block_index: u64) -> Result<usize> {
+        let keys = self.index_range(
+            TRANSFERS_INDEX_NS,
+            IndexField::Block,
+            block_index,
+            block_index,
+        )?;
+        self.count_present(TRANSFERS_NS, keys)
+    }
+
+    /// How many votes the given program produced, by the index, without decoding any.
+    pub async fn count_votes_by_program(&self, program_id: &Pubkey) -> Result<usize> {
+        let prefix = IndexField::Program.prefix(program_id.as_ref());
+        let keys = self.index_scan(VOTES_INDEX_NS, &prefix, |key| key.starts_with(&prefix))?;
+        self.count_present(VOTES_NS, keys)
+    }
+
+    /// How many transfers the given program produced, by the index, without decoding any.
+    pub async fn count_transfers_by_program(&self, program_id: &Pubkey) -> Result<usize> {
+        let prefix = IndexField::Program.prefix(program_id.as_ref());
+        let keys = self.index_scan(TRANSFERS_INDEX_NS, &prefix, |key| key.starts_with(&prefix))?;
+        self.count_present(TRANSFERS_NS, keys)
+    }
+
+    /// How many distinct records the primary keys point to, leaving out the dangling ones.
+    fn count_present(&self, ns: &str, keys: Vec<Vec<u8>>) -> Result<usize> {
+        let keys: BTreeSet<_> = keys.into_iter().collect();
+        let cf = self.db.cf_handle(ns).unwrap();
+
+        let mut count = 0;
+        for each in self.db.multi_get_cf(keys.iter().map(|key| (cf, key))) {
+            match each {
+                Ok(Some(_)) => count += 1,
+                Ok(None) => {}
+                Err(e) if self.strict => return Err(e.into()),
+                Err(e) => tracing::error!("Failed to get a row from the database: {e:?}"),
+            }
+        }
+        Ok(count)
+    }
+
+    /// The runs of the blocks from `from` to `to` inclusive that left nothing in the store,
+    /// telling the ones the cluster has skipped from the ones never processed.
+    pub async fn find_block_gaps(&self, from: u64, to: u64) -> Result<Vec<Gap>> {
+        let mut present = BTreeSet::new();
+        let block_tag = [IndexField::Block.tag()];
+        for ns in [VOTES_INDEX_NS, TRANSFERS_INDEX_NS] {
+            self.distinct_blocks(ns, &block_tag, from, to, &mut present)?;
+        }
+        for ns in [
+            REWARDS_NS,
+            TOKEN_TRANSFERS_NS,
+            STAKE_DELEGATIONS_NS,
+            EMPTY_NS,
+        ] {
+            self.distinct_blocks(ns, &[], from, to, &mut present)?;
+        }
+        let mut skipped = BTreeSet::new();
+        self.distinct_blocks(SKIPPED_NS, &[], from, to, &mut skipped)?;
+
+        let mut gaps: Vec<Gap> = Vec::new();
+        for block in (from..=to).filter(|block| !present.contains(block)) {
+            let was_skipped = skipped.contains(&block);
+            match gaps.last_mut() {
+                Some(gap) if gap.to + 1 == block && gap.skipped == was_skipped => gap.to = block,
+                _ => gaps.push(Gap {
+                    from: block,
+                    to: block,
+                    skipped: was_skipped,
+                }),
+            }
+        }
+        Ok(gaps)
+    }
+
+    /// Collect the distinct blocks the keys of the column family start with, right after the tag,
+    /// seeking past each block once seen rather than going through all of its entries.
+    fn distinct_blocks(
+        &self,
+        ns: &str,
+        tag: &[u8],
+        from: u64,
+        to: u64,
+        into: &mut BTreeSet<u64>,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(ns).unwrap();
+        let mut entries = self.db.raw_iterator_cf_opt(cf, self.read_opts());
+        let mut next = Some(from);
+        while let Some(block) = next.filter(|block| *block <= to) {
+            entries.seek([tag, &block.to_be_bytes()].concat());
+            let Some(key) = entries.key() else {
+                break;
+            };
+            // Past the tag, there are only the other fields.
+            let Some(found) = key.strip_prefix(tag).and_then(|rest| rest.get(..8)) else {
+                break;
+            };
+            let found = u64::from_be_bytes(found.try_into().unwrap());
+            if found > to {
+                break;
+            }
+            into.insert(found);
+            next = found.checked_add(1);
+        }
+        entries.status()?;
+        Ok(())
+    }
+
+    /// The records of `ns` the entries of `index_ns` under the prefix point to.
+    fn find_by_index_prefix<T: DeserializeOwned>(
+        &self,
+        index_ns: &str,
+        ns: &str,
+        prefix: &[u8],
+    ) -> Result<Vec<T>> {
+        let keys = self.index_scan(index_ns, prefix, |key| key.starts_with(prefix))?;
+        self.find_indexed(ns, &keys)
+    }
+
+    /// The primary keys of the index entries with the numeric field from `from` to `to` inclusive.
+    fn index_range(&self, ns: &str, field: IndexField, from: u64, to: u64) -> Result<Vec<Vec<u8>>> {
+        let lower = field.prefix(&from.to_be_bytes());
+        let upper = match to.checked_add(1) {
+            Some(next) => field.prefix(&next.to_be_bytes()),
+            // Past the very last number, there are only the other fields.
+            None => vec![field.tag() + 1],
+        };
+        self.index_scan(ns, &lower, |key| key < upper.as_slice())
+    }
+
+    /// The primary keys of the index entries from `lower` on, for as long as they are `within`.
+    fn index_scan(
+        &self,
+        ns: &str,
+        lower: &[u8],
+        mut within: impl FnMut(&[u8]) -> bool,
+    ) -> Result<Vec<Vec<u8>>> {
+        let cf = self.db.cf_handle(ns).unwrap();
+
+        let mut keys = Vec::new();
+        let mode = rocksdb::IteratorMode::From(lower, rocksdb::Direction::Forward);
+        for each in self.db.iterator_cf_opt(cf, self.read_opts(), mode) {
+            let Some((k, v)) = self.row(each)? else {
+                continue;
+            };
+            if !within(&k[..]) {
+                break;
+            }
+            keys.push(v.into_vec());
+        }
+        Ok(keys)
+    }
+
+    /// The records the index entries point to.
+    fn find_indexed<T: DeserializeOwned>(&self, ns: &str, keys: &[Vec<u8>]) -> Result<Vec<T>> {
+        let found = self.find_many(ns, keys)?;
+        if found.len() < keys.len() {
+            tracing::error!(
+                "{} dangling index entries in `{ns}`",
+                keys.len() - found.len()
+            );
+        }
+        Ok(found)
+    }
+}
+
+/// [store_all_records_from] sans cancellation.
+async fn do_store_all_records_from(
+    rx: &mut Receiver,
+    store: &Store,
+    event_log: &mut Option<EventLog>,
+    parse_only: bool,
+) {
+    while let Some(extracted) = rx.recv().await {
+        if commit(store, event_log, extracted, parse_only)
+            .await
+            .is_err()
+        {
+            return;
+        }
+        rx.ack();
+    }
+}
+
+/// Write down the records of a block, and append them to the event log if there is one,
+/// or only log them if `parse_only`.
+async fn commit(
+    store: &Store,
+    event_log: &mut Option<EventLog>,
+    extracted: Extracted,
+    parse_only: bool,
+) -> Result<()> {
+    let mut records = match extracted {
+        Extracted::Records(records) => records,
+        Extracted::Skipped(block) if parse_only => {
+            tracing::debug!(block, "Skipped by the cluster");
+            return Ok(());
+        }
+        Extracted::Skipped(block) => {
+            let res = store.mark_skipped(block).await;
+            if let Err(ref e) = res {
+                tracing::error!("Failed to note block #{block} as skipped: {e:?}");
+            }
+            return res;
+        }
+        Extracted::Empty(block) if parse_only => {
+            tracing::debug!(block, "Nothing to index");
+            return Ok(());
+        }
+        Extracted::Empty(block) => {
+            let res = store.mark_empty(block).await;
+            if let Err(ref e) = res {
+                tracing::error!("Failed to note block #{block} as empty: {e:?}");
+            }
+            return res;
+        }
+    };
+    if parse_only {
+        for record in &records {
+            tracing::debug!(?record, "Parsed");
+        }
+        return Ok(());
+    }
+    stamp(&mut records);
+    let res = store.save_block(&records).await;
+    if let Err(ref e) = res {
+        tracing::error!("Failed to store {} records: {e:?}", records.len());
+        return res;
+    }
+    // Only what has been committed; failing that, the block stays unacknowledged,
+    // so that it gets committed and appended anew when replayed.
+    if let Some(event_log) = event_log {
+        if let Err(e) = event_log.append(&records) {
+            tracing::error!(
+                "Failed to append {} records to the event log: {e:?}",
+                records.len()
+            );
+            return Err(e);
+        }
+    }
+    res
+}
+
+/// Note the current time on the records about to be committed.
+/// Done by the committer rather than the extraction, so that the time includes
+/// however long the records have waited in the channel.
+pub fn stamp(records: &mut [Record]) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    for record in records {
+        record.set_indexed_at(now);
+    }
+}
+
+/// Drain the channel and commit the records to the database.
+/// Once stopped, commit whatever blocks are already in the channel without waiting for more,
+/// then write down the last known block.
+/// If `parse_only`, log the records instead, leaving the database as it is.
+pub async fn store_all_records_from(
+    mut rx: Receiver,
+    store: Arc<Store>,
+    mut event_log: Option<EventLog>,
+    stop: CancellationToken,
+    parse_only: bool,
+) {
+    select! {
+        biased; // Making sure the signal gets polled first.
+        _ = stop.cancelled() => {
+            tracing::trace!("Storing cancelled; draining the channel...");
+            while let Some(extracted) = rx.try_recv() {
+                if commit(&store, &mut event_log, extracted, parse_only).await.is_err() {
+                    break;
+                }
+                rx.ack();
+            }
+        }
+        _ = do_store_all_records_from(&mut rx, &store, &mut event_log, parse_only) => {
+            tracing::trace!("Stream depleted");
+        }
+    }
+
+    if parse_only {
+        return;
+    }
+
+    if let Err(e) = store.flush().await {
+        tracing::error!("Failed to write down the last known block: {e:?}");
+    }
+    if let Some(mut event_log) = event_log {
+        if let Err(e) = event_log.sync() {
+            tracing::error!("Failed to write down the event log: {e:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{fixtures, RewardType};
+
+    impl Store {
+        pub(crate) fn disposable_path() -> std::path::PathBuf {
+            use rand::Rng;
+
+            let mut rng = rand::thread_rng();
+            let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            path.push("...store");
+            path.push(rng.gen::<u64>().to_string());
+            path
+        }
+
+        pub(crate) async fn disposable() -> Result<Self> {
+            Self::with_path(&Self::disposable_path(), &StoreConfig::default()).await
+        }
+
+        /// Write what the column family holds in memory into a table file of its own.
+        pub(crate) fn flush_table(&self, name: &str) {
+            let cf = self.db.cf_handle(name).unwrap();
+            self.db.flush_cf(cf).unwrap();
+        }
+
+        /// How many table files the column family is in.
+        pub(crate) fn table_files(&self, name: &str) -> usize {
+            let files = self.db.live_files().unwrap();
+            files
+                .iter()
+                .filter(|x| x.column_family_name == name)
+                .count()
+        }
+    }
+
+    #[tokio::test]
+    async fn last_known_block_persists() {
+        // Given an empty store:
+        let store = Store::disposable().await.unwrap();
+
+        // When we query the last known block from it:
+        let last_known_block = store.last_known_block().await;
+
+        // Then it should be unset:
+        assert!(last_known_block.is_none());
+
+        // And when we set the last known block to a certain value:
+        let lucky_eight = 8888;
+        store.set_last_known_block(lucky_eight).await.unwrap();
+
+        // And when we query it again:
+        let last_known_block = store.last_known_block().await;
+
+        // Then it should be the same:
+        assert_eq!(last_known_block, Some(lucky_eight));
+    }
+
+    #[tokio::test]
+    async fn deleted_transfer_gone_from_indexes() {
+        // Given a store with a transfer:
+        let transfer = fixtures::transfer(777, 0);
+        let store = Store::disposable().await.unwrap();
+        store.save_transfer(&transfer).await.unwrap();
+
+        // When it gets deleted:
+        store.delete_transfer(&transfer.signature).await.unwrap();
+
+        // Then it should be found neither by its signature nor by any index:
+        let found = store.find_transfers(&[transfer.signature]).await.unwrap();
+        assert!(found.is_empty());
+        let found = store.find_transfers_by_block_index(777).await.unwrap();
+        assert!(found.is_empty());
+        let index_cf = store.db.cf_handle(TRANSFERS_INDEX_NS).unwrap();
+        let chrono_cf = store.db.cf_handle(TRANSFERS_CHRONO_NS).unwrap();
+        for cf in [index_cf, chrono_cf] {
+            let mut entries = store.db.iterator_cf(cf, rocksdb::IteratorMode::Start);
+            assert!(entries.next().is_none());
+        }
+
+        // And deleting it again should find nothing to delete:
+        let again = store.delete_transfer(&transfer.signature).await;
+        assert!(matches!(again, Err(Error::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn inconsistencies_found() {
+        // Given a store with a few votes:
+        let store = Store::disposable().await.unwrap();
+        let votes: Vec<_> = (0..3)
+            .map(|instruction_index| Vote {
+                program_id: Pubkey::new_unique(),
+                ..fixtures::vote(777, instruction_index)
+            })
+            .collect();
+        for vote in &votes {
+            store.save_vote(vote).await.unwrap();
+        }
+        assert!(store.check_integrity().unwrap().is_consistent());
+
+        // When one of them loses its record, and another one its entry in the index by the block:
+        let cf = store.db.cf_handle(VOTES_NS).unwrap();
+        let key = store.primary_key(&votes[0].signature, 777, 0);
+        store.db.delete_cf(cf, key).unwrap();
+        let index_cf = store.db.cf_handle(VOTES_INDEX_NS).unwrap();
+        let key = store.primary_key(&votes[1].signature, 777, 1);
+        let block = 777u64.to_be_bytes();
+        let entry = index_key(IndexField::Block, &block, &key);
+        store.db.delete_cf(index_cf, entry).unwrap();
+
+        // Then the entries of the first should be dangling, and the second orphaned:
+        let report = store.check_integrity().unwrap();
+        let expected = IntegrityReport {
+            // By the block, the time, both accounts, the program, and the chronological index.
+            dangling_indexes: 6,
+            orphaned_records: 1,
+        };
+        assert_eq!(report, expected);
+    }
+
+    #[tokio::test]
+    async fn opened_with_options_of_its_own() {
+        // Given a database set up otherwise than by default:
+        let config = StoreConfig {
+            write_buffer: Some(1 << 20),
+            block_cache: Some(8 << 20),
+            max_open_files: Some(64),
+            compression: Some(Compression::Zstd),
+        };
+        let path = Store::disposable_path();
+        let store = Store::with_path(&path, &config).await.unwrap();
+
+        // When a vote gets written down, and made it into a table file:
+        let vote = fixtures::vote(777, 0);
+        store.save_vote(&vote).await.unwrap();
+        let cf = store.db.cf_handle(VOTES_NS).unwrap();
+        store.db.flush_cf(cf).unwrap();
+
+        // Then it should read back the same, then and once opened with the defaults:
+        let found = store.find_votes(&[vote.signature]).await.unwrap();
+        assert_eq!(found, vec![vote.clone()]);
+        drop(store);
+        let store = Store::with_path(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+        let found = store.find_votes(&[vote.signature]).await.unwrap();
+        assert_eq!(found, vec![vote]);
+    }
+
+    #[tokio::test]
+    async fn exported_records_imported_back() {
+        // Given a store with a vote and a transfer:
+        let vote = Vote {
+            indexed_at: Some(1234567891),
+            ..fixtures::vote(777, 0)
+        };
+        let transfer = Transfer {
+            timestamp: 1234567892,
+            compute_unit_price: Some(25_000),
+            instruction_kind: Some("transfer".to_owned()),
+            ..fixtures::transfer(778, 1)
+        };
+        let store = Store::disposable().await.unwrap();
+        store.save_vote(&vote).await.unwrap();
+        store.save_transfer(&transfer).await.unwrap();
+
+        // When they get exported, a line of the event log and a garbled one get added,
+        // and the file gets imported elsewhere:
+        let path = Store::disposable_path();
+        std::fs::create_dir_all(&path).unwrap();
+        let file = path.join("backup.jsonl");
+        store.export_to_json_file(&file).await.unwrap();
+        let contents = std::fs::read_to_string(&file).unwrap();
+        let logged = fixtures::vote(779, 0);
+        let mut exported = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&file)
+            .unwrap();
+        let line = serde_json::to_string(&Record::Vote(logged.clone())).unwrap();
+        writeln!(exported, "{line}").unwrap();
+        exported.write_all(b"{\"kind\":\"vote\"\n").unwrap();
+        let restored = Store::disposable().await.unwrap();
+        let report = restored.import_from_json_file(&file).await.unwrap();
+
+        // Then the export should tag them by their kind, and leave nothing else behind:
+        assert!(contents.starts_with(r#"{"kind":"vote","#), "{contents}");
+        assert!(contents.contains(r#"{"kind":"transfer","#), "{contents}");
+        let left = std::fs::read_dir(&path).unwrap().count();
+        assert_eq!(left, 1);
+
+        // And all should come back the same, the garbled line skipped:
+        let expected = ImportReport {
+            imported: 3,
+            skipped: 1,
+        };
+        assert_eq!(report, expected);
+        let mut votes = restored.find_all_votes().await.unwrap();
+        votes.sort_by_key(|x| x.block_index);
+        assert_eq!(votes, vec![vote, logged]);
+        assert_eq!(restored.find_all_transfers().await.unwrap(), vec![transfer]);
+
+        // And exporting into an existing file should be refused:
+        assert!(store.export_to_json_file(&file).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn blocks_without_records_resumed_past() {
+        // Given a store with nothing but the blocks that had no records, skipped or empty:
+        let path = Store::disposable_path();
+        let store = Store::with_path(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+        store.mark_empty(777).await.unwrap();
+        store.mark_skipped(778).await.unwrap();
+        store.mark_empty(779).await.unwrap();
+
+        // When it gets written down and opened anew:
+        store.flush().await.unwrap();
+        drop(store);
+        let store = Store::with_path(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+
+        // Then the extraction should resume past them, none being taken for a record:
+        assert_eq!(store.last_known_block().await, Some(779));
+        assert_eq!(store.first_known_block().await, None);
+    }
+
+    #[tokio::test]
+    async fn first_known_block_persists() {
+        // Given a store with records of a few blocks, not in order:
+        let path = Store::disposable_path();
+        let store = Store::with_path(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+        for block_index in [800, 777, 900] {
+            let vote = fixtures::vote(block_index, 0);
+            store.save_vote(&vote).await.unwrap();
+        }
+
+        // When it gets opened anew:
+        drop(store);
+        let store = Store::with_path(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+
+        // Then the earliest of the blocks should be known still:
+        assert_eq!(store.first_known_block().await, Some(777));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn backfill_progress_kept_apart() {
+        // Given a store extracted continuously up to a certain block:
+        let store = Store::disposable().await.unwrap();
+        store.set_last_known_block(8888).await.unwrap();
+
+        // When a backfill gets part of the way through its blocks:
+        let store = store.with_backfill(100..=200).unwrap();
+        assert!(store.last_known_block().await.is_none());
+        store.set_last_known_block(150).await.unwrap();
+
+        // Then the checkpoint of the continuous extraction should stay as it was:
+        let persisted = store.db.get(LAST_KNOWN_BLOCK_KEY).unwrap().unwrap();
+        assert_eq!(postcard::from_bytes::<u64>(&persisted).unwrap(), 8888);
+
+        // And the same backfill should pick up where it left off, but not another one:
+        let store = store.with_backfill(100..=200).unwrap();
+        assert_eq!(store.last_known_block().await, Some(150));
+        let store = store.with_backfill(300..=400).unwrap();
+        assert!(store.last_known_block().await.is_none());
+    }
 
-    use solana_sdk::pubkey::Pubkey;
+    #[tokio::test]
+    async fn last_known_block_flushes_on_cadence() {
+        // Given a store that writes down the progress every third record:
+        let store = Store::disposable()
+            .await
+            .unwrap()
+            .with_flush_cadence(FlushCadence {
+                records: 3,
+                interval: Duration::from_secs(3600),
+            });
+        let vote = |block_index| fixtures::vote(block_index, 0);
+
+        // When we save fewer records than the cadence:
+        store.save_vote(&vote(10)).await.unwrap();
+        store.save_vote(&vote(11)).await.unwrap();
+
+        // Then the progress should be known but not written down:
+        assert_eq!(store.last_known_block().await, Some(11));
+        assert!(store.db.get(LAST_KNOWN_BLOCK_KEY).unwrap().is_none());
+
+        // And when we save one more record:
+        store.save_vote(&vote(12)).await.unwrap();
+
+        // Then the progress should be written down:
+        let persisted = store.db.get(LAST_KNOWN_BLOCK_KEY).unwrap().unwrap();
+        assert_eq!(postcard::from_bytes::<u64>(&persisted).unwrap(), 12);
+    }
 
-    impl Store {
-        fn disposable_path() -> std::path::PathBuf {
-            use rand::Rng;
+    #[tokio::test]
+    async fn unreadable_rows_fail_strict_reads() {
+        // Given a store with a garbled row:
+        let store = Store::disposable().await.unwrap();
+        let cf = store.db.cf_handle(VOTES_NS).unwrap();
+        store.db.put_cf(cf, b"garbage", b"\xff").unwrap();
 
-            let mut rng = rand::thread_rng();
-            let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-            path.push("...store");
-            path.push(rng.gen::<u64>().to_string());
-            path
-        }
+        // When we query all data leniently:
+        let gotten = store.find_all_votes().await.unwrap();
 
-        async fn disposable() -> Result<Self> {
-            Self::with_path(&Self::disposable_path()).await
-        }
+        // Then the garbled row should be skipped:
+        assert!(gotten.is_empty());
+
+        // And when we query all data strictly:
+        let store = store.with_strict_reads(true);
+        let gotten = store.find_all_votes().await;
+
+        // Then the query should fail:
+        assert!(gotten.is_err());
     }
 
     #[tokio::test]
-    async fn last_known_block_persists() {
-        // Given an empty store:
+    async fn checkpoint_opens_as_store() {
+        // Given a store with some data:
+        let vote = fixtures::vote(777, 0);
         let store = Store::disposable().await.unwrap();
+        store.save_vote(&vote).await.unwrap();
 
-        // When we query the last known block from it:
-        let last_known_block = store.last_known_block().await;
-
-        // Then it should be unset:
-        assert!(last_known_block.is_none());
+        // When we take a checkpoint of it and open that:
+        let path = Store::disposable_path();
+        store.checkpoint(&path).await.unwrap();
+        let snapshot = Store::with_path(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+
+        // Then it should have the same data and progress:
+        let found = snapshot.find_votes(&[vote.signature]).await.unwrap();
+        assert_eq!(found, vec![vote]);
+        assert_eq!(snapshot.last_known_block().await, Some(777));
+    }
 
-        // And when we set the last known block to a certain value:
-        let lucky_eight = 8888;
-        store.set_last_known_block(lucky_eight).await.unwrap();
+    #[tokio::test]
+    async fn stats_cover_every_cf() {
+        // Given a store with a flushed vote:
+        let vote = fixtures::vote(777, 0);
+        let store = Store::disposable().await.unwrap();
+        store.save_vote(&vote).await.unwrap();
+        let cf = store.db.cf_handle(VOTES_NS).unwrap();
+        store.db.flush_cf(cf).unwrap();
 
-        // And when we query it again:
-        let last_known_block = store.last_known_block().await;
+        // When we ask for the stats:
+        let stats = store.cf_stats().unwrap();
 
-        // Then it should be the same:
-        assert_eq!(last_known_block, Some(lucky_eight));
+        // Then every family should be there, and the votes should have a table file:
+        assert_eq!(stats.len(), COLUMN_FAMILIES.len());
+        let votes = stats.iter().find(|x| x.name == VOTES_NS).unwrap();
+        assert_eq!(votes.estimated_keys, 1);
+        assert_eq!(votes.sst_files, 1);
     }
 
     #[tokio::test]
@@ -329,19 +2775,16 @@ mod tests {
         let signature = Signature::new_unique();
         let vote = Vote {
             signature,
-            block_index: 777,
-            timestamp: 1234567890,
-            author: Pubkey::new_unique(),
-            target: Pubkey::new_unique(),
+            ..fixtures::vote(777, 0)
         };
         let store = Store::disposable().await.unwrap();
         store.save_vote(&vote).await.unwrap();
 
         // When we query a datum by its primary key:
-        let gotten = store.find_vote(&signature).await;
+        let gotten = store.find_votes(&[signature]).await.unwrap();
 
         // Then it should be the same:
-        assert_eq!(gotten, Some(vote.clone()));
+        assert_eq!(gotten, vec![vote.clone()]);
 
         // And when we query a datum by its secondary key:
         let gotten = store.find_all_votes().await.unwrap();
@@ -350,16 +2793,34 @@ mod tests {
         assert!(gotten.contains(&vote));
     }
 
+    #[tokio::test]
+    async fn votes_found_by_many_keys() {
+        // Given a store with some data:
+        let votes: Vec<_> = (0..3).map(|i| fixtures::vote(777, i)).collect();
+        let store = Store::disposable().await.unwrap();
+        for vote in &votes {
+            store.save_vote(vote).await.unwrap();
+        }
+
+        // When we query several keys at once, one of them missing:
+        let keys = [
+            votes[2].signature,
+            Signature::new_unique(),
+            votes[0].signature,
+        ];
+        let gotten = store.find_votes(&keys).await.unwrap();
+
+        // Then the found ones should come in the order asked:
+        assert_eq!(gotten, vec![votes[2].clone(), votes[0].clone()]);
+    }
+
     #[tokio::test]
     async fn votes_found_in_everything() {
         // Given a store with some data:
         let signature = Signature::new_unique();
         let vote = Vote {
             signature,
-            block_index: 777,
-            timestamp: 1234567890,
-            author: Pubkey::new_unique(),
-            target: Pubkey::new_unique(),
+            ..fixtures::vote(777, 0)
         };
         let store = Store::disposable().await.unwrap();
         store.save_vote(&vote).await.unwrap();
@@ -377,20 +2838,17 @@ mod tests {
         let signature = Signature::new_unique();
         let transfer = Transfer {
             signature,
-            block_index: 777,
-            timestamp: 1234567890,
-            source: Pubkey::new_unique(),
-            destination: Pubkey::new_unique(),
             lamports: 0,
+            ..fixtures::transfer(777, 0)
         };
         let store = Store::disposable().await.unwrap();
         store.save_transfer(&transfer).await.unwrap();
 
         // When we query a datum by its primary key:
-        let gotten = store.find_transfer(&signature).await;
+        let gotten = store.find_transfers(&[signature]).await.unwrap();
 
         // Then it should be the same:
-        assert_eq!(gotten, Some(transfer.clone()));
+        assert_eq!(gotten, vec![transfer.clone()]);
     }
 
     #[tokio::test]
@@ -399,11 +2857,8 @@ mod tests {
         let signature = Signature::new_unique();
         let transfer = Transfer {
             signature,
-            block_index: 777,
-            timestamp: 1234567890,
-            source: Pubkey::new_unique(),
-            destination: Pubkey::new_unique(),
             lamports: 0,
+            ..fixtures::transfer(777, 0)
         };
         let store = Store::disposable().await.unwrap();
         store.save_transfer(&transfer).await.unwrap();
@@ -421,18 +2876,12 @@ mod tests {
         let signature = Signature::new_unique();
         let vote = Vote {
             signature,
-            block_index: 777,
-            timestamp: 1234567890,
-            author: Pubkey::new_unique(),
-            target: Pubkey::new_unique(),
+            ..fixtures::vote(777, 0)
         };
         let transfer = Transfer {
             signature,
-            block_index: 777,
-            timestamp: 1234567890,
-            source: Pubkey::new_unique(),
-            destination: Pubkey::new_unique(),
             lamports: 0,
+            ..fixtures::transfer(777, 0)
         };
         let store = Store::disposable().await.unwrap();
         store.save_vote(&vote).await.unwrap();
@@ -459,18 +2908,13 @@ mod tests {
     async fn votes_found_by_index() {
         // Given a store with some data having the same block index:
         let vote = Vote {
-            signature: Signature::new_unique(),
-            block_index: 777,
-            timestamp: 1234567890,
-            author: Pubkey::new_unique(),
-            target: Pubkey::new_unique(),
+            program_id: Pubkey::new_unique(),
+            ..fixtures::vote(777, 0)
         };
         let vote2 = Vote {
-            signature: Signature::new_unique(),
-            block_index: 777,
             timestamp: 1234567891,
-            author: Pubkey::new_unique(),
-            target: Pubkey::new_unique(),
+            program_id: Pubkey::new_unique(),
+            ..fixtures::vote(777, 0)
         };
         let store = Store::disposable().await.unwrap();
         store.save_vote(&vote).await.unwrap();
@@ -483,26 +2927,35 @@ mod tests {
         assert!(gotten.contains(&vote));
         assert!(gotten.contains(&vote2));
         assert_eq!(gotten.len(), 2);
+
+        // And so should it by every other key written along with it:
+        let by_timestamp = store
+            .find_votes_by_timestamp_range(1234567890, 1234567890)
+            .await
+            .unwrap();
+        assert_eq!(by_timestamp, vec![vote.clone()]);
+        let by_accounts = store
+            .find_votes_by_account_pair(&vote.author, &vote.target)
+            .await
+            .unwrap();
+        assert_eq!(by_accounts, vec![vote.clone()]);
+        let by_program = store.find_votes_by_program(&vote.program_id).await.unwrap();
+        assert_eq!(by_program, vec![vote.clone()]);
+        let chronological = store.find_all_votes_chronological().await.unwrap();
+        assert!(chronological.contains(&vote));
     }
 
     #[tokio::test]
     async fn transfers_found_by_index() {
         // Given a store with some data having the same block index:
         let transfer = Transfer {
-            signature: Signature::new_unique(),
-            block_index: 777,
-            timestamp: 1234567890,
-            source: Pubkey::new_unique(),
-            destination: Pubkey::new_unique(),
             lamports: 0,
+            ..fixtures::transfer(777, 0)
         };
         let transfer2 = Transfer {
-            signature: Signature::new_unique(),
-            block_index: 777,
             timestamp: 1234567891,
-            source: Pubkey::new_unique(),
-            destination: Pubkey::new_unique(),
             lamports: 0,
+            ..fixtures::transfer(777, 0)
         };
         let store = Store::disposable().await.unwrap();
         store.save_transfer(&transfer).await.unwrap();
@@ -516,4 +2969,915 @@ mod tests {
         assert!(gotten.contains(&transfer2));
         assert_eq!(gotten.len(), 2);
     }
+
+    #[tokio::test]
+    async fn transfers_found_by_block_range() {
+        // Given transfers in and around a range of blocks,
+        // one of them moving as many lamports as a block index within the range:
+        let transfer = |block_index, lamports| Transfer {
+            lamports,
+            ..fixtures::transfer(block_index, 0)
+        };
+        let transfers = [
+            transfer(255, 1),
+            transfer(256, 300),
+            transfer(300, 1),
+            transfer(301, 1),
+        ];
+        let store = Store::disposable().await.unwrap();
+        for transfer in &transfers {
+            store.save_transfer(transfer).await.unwrap();
+        }
+
+        // When we query the range:
+        let gotten = store.find_transfers_by_block_range(256, 300).await.unwrap();
+
+        // Then only the ones within should be found, by the block:
+        assert_eq!(gotten, vec![transfers[1].clone(), transfers[2].clone()]);
+    }
+
+    #[tokio::test]
+    async fn recent_records_interleaved() {
+        // Given votes and transfers in alternating blocks:
+        let vote = |block_index| Vote {
+            timestamp: 1234567890 + block_index,
+            ..fixtures::vote(block_index, 0)
+        };
+        let transfer = |block_index| Transfer {
+            timestamp: 1234567890 + block_index,
+            ..fixtures::transfer(block_index, 0)
+        };
+        let store = Store::disposable().await.unwrap();
+        for block_index in [1, 3, 5] {
+            store.save_vote(&vote(block_index)).await.unwrap();
+        }
+        for block_index in [2, 4, 6] {
+            store.save_transfer(&transfer(block_index)).await.unwrap();
+        }
+
+        // When we ask for the few most recent ones:
+        let recent = store.recent_records(4).await.unwrap();
+
+        // Then they should come newest first, whatever their kind:
+        let blocks: Vec<_> = recent.iter().map(Record::block_index).collect();
+        assert_eq!(blocks, vec![6, 5, 4, 3]);
+    }
+
+    #[tokio::test]
+    async fn recent_records_whatever_tuning() {
+        // Given stores with the default tuning, and with the tailing scans:
+        let tailing = Tuning {
+            tailing: true,
+            ..Tuning::default()
+        };
+        for tuning in [Tuning::default(), tailing] {
+            let store = Store::disposable().await.unwrap().with_tuning(tuning);
+            for block_index in [1, 3] {
+                let vote = fixtures::vote(block_index, 0);
+                store.save_vote(&vote).await.unwrap();
+            }
+            for block_index in [2, 4] {
+                let transfer = fixtures::transfer(block_index, 0);
+                store.save_transfer(&transfer).await.unwrap();
+            }
+
+            // When we ask for the most recent ones:
+            let recent = store.recent_records(3).await.unwrap();
+
+            // Then they should come newest first either way:
+            let blocks: Vec<_> = recent.iter().map(Record::block_index).collect();
+            assert_eq!(blocks, vec![4, 3, 2]);
+        }
+    }
+
+    #[tokio::test]
+    async fn block_saved_at_once() {
+        // Given the records of a block, of both kinds:
+        let vote = fixtures::vote(777, 0);
+        let transfer = fixtures::transfer(777, 1);
+        let records = [
+            Record::Vote(vote.clone()),
+            Record::Transfer(transfer.clone()),
+        ];
+        let store = Store::disposable().await.unwrap();
+
+        // When we save them in one go:
+        store.save_block(&records).await.unwrap();
+
+        // Then they should be found by the block, and the block should be known:
+        let votes = store.find_votes_by_block_index(777).await.unwrap();
+        assert_eq!(votes, vec![vote]);
+        let transfers = store.find_transfers_by_block_index(777).await.unwrap();
+        assert_eq!(transfers, vec![transfer]);
+        assert_eq!(store.last_known_block().await, Some(777));
+    }
+
+    #[tokio::test]
+    async fn cleared_block_leaves_no_trace() {
+        // Given a store with a vote and a transfer in the same block, and one in the next:
+        let author = Pubkey::new_unique();
+        let vote = Vote {
+            author,
+            ..fixtures::vote(777, 0)
+        };
+        let transfer = |block_index| Transfer {
+            source: author,
+            ..fixtures::transfer(block_index, 1)
+        };
+        let reward = Reward {
+            block_index: 777,
+            reward_index: 0,
+            timestamp: 1234567890,
+            pubkey: author,
+            lamports: 5000,
+            post_balance: 10000,
+            reward_type: Some(RewardType::Fee),
+            commission: None,
+            indexed_at: None,
+        };
+        let (cleared, kept) = (transfer(777), transfer(778));
+        let store = Store::disposable().await.unwrap();
+        store.save_vote(&vote).await.unwrap();
+        store.save_transfer(&cleared).await.unwrap();
+        store.save_transfer(&kept).await.unwrap();
+        store.save_block(&[Record::Reward(reward)]).await.unwrap();
+
+        // When we clear the block:
+        let counts = store.clear_block(777).await.unwrap();
+
+        // Then all the records of it should be gone, from everywhere:
+        assert_eq!(counts, (1, 1, 1, 0, 0));
+        assert!(store
+            .find_votes(&[vote.signature])
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(store.raw_scan(VOTES_CHRONO_NS, &[], 10).unwrap().is_empty());
+        assert!(store.raw_scan(VOTES_INDEX_NS, &[], 10).unwrap().is_empty());
+        assert!(store.raw_scan(REWARDS_NS, &[], 10).unwrap().is_empty());
+        assert!(store
+            .raw_scan(REWARDS_INDEX_NS, &[], 10)
+            .unwrap()
+            .is_empty());
+        let by_account = store.find_transfers_by_account(&author).await.unwrap();
+        assert_eq!(by_account, vec![kept.clone()]);
+        let chronological = store.find_all_transfers_chronological().await.unwrap();
+        assert_eq!(chronological, vec![kept]);
+    }
+
+    #[tokio::test]
+    async fn gaps_told_apart() {
+        // Given records of a few blocks, one of them skipped by the cluster:
+        let vote = |block_index| fixtures::vote(block_index, 0);
+        let transfer = fixtures::transfer(781, 0);
+        let reward = Reward {
+            block_index: 783,
+            reward_index: 0,
+            timestamp: 1234567890,
+            pubkey: Pubkey::new_unique(),
+            lamports: 5000,
+            post_balance: 10000,
+            reward_type: Some(RewardType::Voting),
+            commission: None,
+            indexed_at: None,
+        };
+        let store = Store::disposable().await.unwrap();
+        store.save_vote(&vote(777)).await.unwrap();
+        store.save_vote(&vote(777)).await.unwrap();
+        store.save_vote(&vote(780)).await.unwrap();
+        store.save_transfer(&transfer).await.unwrap();
+        store.save_block(&[Record::Reward(reward)]).await.unwrap();
+        store.mark_skipped(778).await.unwrap();
+        store.mark_empty(782).await.unwrap();
+
+        // When we look for the gaps around them:
+        let gaps = store.find_block_gaps(776, 785).await.unwrap();
+
+        // Then the skipped block should be told apart from the ones never processed,
+        // and the empty one should not count:
+        let gap = |from, to, skipped| Gap { from, to, skipped };
+        assert_eq!(
+            gaps,
+            vec![
+                gap(776, 776, false),
+                gap(778, 778, true),
+                gap(779, 779, false),
+                gap(784, 785, false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn transfers_found_by_program() {
+        // Given transfers by two programs:
+        let transfer = |program_id| Transfer {
+            program_id,
+            ..fixtures::transfer(777, 0)
+        };
+        let program_id = Pubkey::new_unique();
+        let (ours, theirs) = (transfer(program_id), transfer(Pubkey::new_unique()));
+        let store = Store::disposable().await.unwrap();
+        store.save_transfer(&ours).await.unwrap();
+        store.save_transfer(&theirs).await.unwrap();
+
+        // When we query by one of the programs:
+        let gotten = store.find_transfers_by_program(&program_id).await.unwrap();
+
+        // Then only its transfer should be found:
+        assert_eq!(gotten, vec![ours]);
+    }
+
+    #[tokio::test]
+    async fn votes_found_chronologically() {
+        // Given a store with votes saved out of their on-chain order:
+        let vote = fixtures::vote;
+        let votes = [vote(300, 0), vote(2, 7), vote(2, 1), vote(256, 4)];
+        let store = Store::disposable().await.unwrap();
+        for vote in &votes {
+            store.save_vote(vote).await.unwrap();
+        }
+
+        // When we query all data chronologically:
+        let gotten = store.find_all_votes_chronological().await.unwrap();
+
+        // Then it should come by block, then by position:
+        let expected = vec![
+            votes[2].clone(),
+            votes[1].clone(),
+            votes[3].clone(),
+            votes[0].clone(),
+        ];
+        assert_eq!(gotten, expected);
+    }
+
+    #[tokio::test]
+    async fn transfers_found_by_account() {
+        // Given a store with transfers from and to the same account:
+        let account = Pubkey::new_unique();
+        let outgoing = Transfer {
+            source: account,
+            ..fixtures::transfer(777, 0)
+        };
+        let incoming = Transfer {
+            timestamp: 1234567891,
+            destination: account,
+            lamports: 250,
+            ..fixtures::transfer(778, 0)
+        };
+        let store = Store::disposable().await.unwrap();
+        store.save_transfer(&outgoing).await.unwrap();
+        store.save_transfer(&incoming).await.unwrap();
+
+        // When we query by that account:
+        let gotten = store.find_transfers_by_account(&account).await.unwrap();
+
+        // Then both should be found:
+        assert!(gotten.contains(&outgoing));
+        assert!(gotten.contains(&incoming));
+        assert_eq!(gotten.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rewards_found_by_block_and_pubkey() {
+        // Given rewards of two accounts across two blocks:
+        let (staker, voter) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let reward = |block_index, reward_index, pubkey| Reward {
+            block_index,
+            reward_index,
+            timestamp: 1234567890,
+            pubkey,
+            lamports: 5000,
+            post_balance: 10000,
+            reward_type: Some(RewardType::Staking),
+            commission: Some(10),
+            indexed_at: None,
+        };
+        let (first, second, third) = (
+            reward(778, 1, staker),
+            reward(778, 0, voter),
+            reward(777, 0, staker),
+        );
+        let store = Store::disposable().await.unwrap();
+        store
+            .save_block(&[
+                Record::Reward(first.clone()),
+                Record::Reward(second.clone()),
+            ])
+            .await
+            .unwrap();
+        store
+            .save_block(&[Record::Reward(third.clone())])
+            .await
+            .unwrap();
+
+        // When we look them up by the block:
+        let by_block = store.find_rewards_by_block_index(778).await.unwrap();
+
+        // Then only the ones of that block should be found, as the block lists them:
+        assert_eq!(by_block, vec![second, first.clone()]);
+
+        // And when we look them up by the account, they should come by the block:
+        let by_pubkey = store.find_rewards_by_pubkey(&staker).await.unwrap();
+        assert_eq!(by_pubkey, vec![third, first]);
+    }
+
+    #[tokio::test]
+    async fn account_pairs_intersected() {
+        // Given an author voting for two targets, a target voted for by someone else,
+        // and the author being voted for in turn:
+        let (author, target, other_target, other_author) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let vote = |block_index, author, target| Vote {
+            author,
+            target,
+            ..fixtures::vote(block_index, 0)
+        };
+        let store = Store::disposable().await.unwrap();
+        let votes = [
+            vote(777, author, target),
+            vote(778, author, other_target),
+            vote(779, other_author, target),
+            vote(780, author, target),
+            vote(781, target, author),
+        ];
+        for vote in &votes {
+            store.save_vote(vote).await.unwrap();
+        }
+        let blocks = |found: Vec<Vote>| -> BTreeSet<_> {
+            found.into_iter().map(|x| x.block_index).collect()
+        };
+
+        // When we look up the overlapping pair, either way round:
+        let found = store.find_votes_by_account_pair(&author, &target).await;
+        let found_reversed = store.find_votes_by_account_pair(&target, &author).await;
+
+        // Then only the votes with both should be found, whichever way they went,
+        // so that the caller tells the authors from the targets:
+        assert_eq!(blocks(found.unwrap()), BTreeSet::from([777, 780, 781]));
+        assert_eq!(
+            blocks(found_reversed.unwrap()),
+            BTreeSet::from([777, 780, 781])
+        );
+
+        // And when we look up a pair that never met, then nothing should be found:
+        let disjoint = store
+            .find_votes_by_account_pair(&other_author, &other_target)
+            .await;
+        assert!(disjoint.unwrap().is_empty());
+
+        // And when the same one is asked for on both sides, each vote should come once:
+        let both = store
+            .find_votes_by_account_pair(&author, &author)
+            .await
+            .unwrap();
+        assert_eq!(both.len(), 4);
+        assert_eq!(blocks(both), BTreeSet::from([777, 778, 780, 781]));
+    }
+
+    #[tokio::test]
+    async fn pages_pick_up_where_left() {
+        // Given votes across a few blocks, one of them by someone else:
+        let author = Pubkey::new_unique();
+        let vote = |block_index, instruction_index, author| Vote {
+            author,
+            ..fixtures::vote(block_index, instruction_index)
+        };
+        let store = Store::disposable().await.unwrap();
+        let someone_else = vote(778, 1, Pubkey::new_unique());
+        store.save_vote(&someone_else).await.unwrap();
+        for (block_index, instruction_index) in [(779, 0), (777, 0), (778, 2), (778, 0)] {
+            let vote = vote(block_index, instruction_index, author);
+            store.save_vote(&vote).await.unwrap();
+        }
+        let positions = |page: &Page<Vote>| -> Vec<_> {
+            let votes = page.records.iter();
+            votes
+                .map(|x| (x.block_index, x.instruction_index))
+                .collect()
+        };
+        let ours = |vote: &Vote| vote.author == author;
+
+        // When we go through the votes two at a time:
+        let first = store
+            .find_votes_page(None, 0..=u64::MAX, 0, 2, ours)
+            .await
+            .unwrap();
+        let after = first.next.as_deref();
+        let second = store
+            .find_votes_page(after, 0..=u64::MAX, 0, 2, ours)
+            .await
+            .unwrap();
+        let after = second.next.as_deref();
+        let third = store
+            .find_votes_page(after, 0..=u64::MAX, 0, 2, ours)
+            .await
+            .unwrap();
+
+        // Then each page should pick up right after the one before, in the on-chain order,
+        // the last one being empty without anywhere to go on from:
+        assert_eq!(positions(&first), vec![(777, 0), (778, 0)]);
+        assert_eq!(positions(&second), vec![(778, 2), (779, 0)]);
+        assert_eq!(positions(&third), vec![]);
+        assert_eq!(third.next, None);
+
+        // And when we go through a block alone, the page should end with it:
+        let block = store
+            .find_votes_page(None, 778..=778, 0, 10, ours)
+            .await
+            .unwrap();
+        assert_eq!(positions(&block), vec![(778, 0), (778, 2)]);
+        assert_eq!(block.next, None);
+
+        // And when we go on from the signature of the last vote of the first page instead,
+        // the page should be the same as the second one:
+        let signature = first.records.last().unwrap().signature;
+        let position = store.position_of_vote(&signature).await.unwrap();
+        let by_signature = store
+            .find_votes_page(position.as_deref(), 0..=u64::MAX, 0, 2, ours)
+            .await
+            .unwrap();
+        assert_eq!(positions(&by_signature), positions(&second));
+
+        // And when we skip a few of ours, only the ones past them should come:
+        let skipped = store
+            .find_votes_page(None, 0..=u64::MAX, 3, 10, ours)
+            .await
+            .unwrap();
+        assert_eq!(positions(&skipped), vec![(779, 0)]);
+    }
+
+    #[tokio::test]
+    async fn pages_give_up_passing_over_too_many() {
+        // Given a few votes, the last one alone of interest:
+        let store = Store::disposable().await.unwrap();
+        for block_index in 777..=781 {
+            store
+                .save_vote(&fixtures::vote(block_index, 0))
+                .await
+                .unwrap();
+        }
+        let start = || PageStart {
+            after: None,
+            blocks: 0..=u64::MAX,
+            offset: 0,
+        };
+        let last = |vote: &Vote| vote.block_index == 781;
+
+        // When a page may pass over fewer of them than there are before it:
+        let (chrono_ns, ns) = (VOTES_CHRONO_NS, VOTES_NS);
+        let cut_short = store.page_chronological(chrono_ns, ns, start(), 1, 3, last);
+
+        // Then it should give up rather than go on scanning:
+        assert!(
+            matches!(cut_short, Err(Error::TooBroad(3))),
+            "{cut_short:?}"
+        );
+
+        // And when it may pass over all of them, it should find the last one:
+        let page: Page<Vote> = store
+            .page_chronological(chrono_ns, ns, start(), 1, 4, last)
+            .unwrap();
+        assert_eq!(page.records.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn changes_found_past_block() {
+        // Given votes and rewards across a few blocks, saved out of order:
+        let vote = fixtures::vote;
+        let reward = |block_index| Reward {
+            block_index,
+            reward_index: 0,
+            timestamp: 1234567890,
+            pubkey: Pubkey::new_unique(),
+            lamports: 5000,
+            post_balance: 10000,
+            reward_type: Some(RewardType::Voting),
+            commission: None,
+            indexed_at: None,
+        };
+        let store = Store::disposable().await.unwrap();
+        for (block_index, instruction_index) in [(779, 0), (777, 0), (778, 1), (778, 0), (1, 0)] {
+            store
+                .save_vote(&vote(block_index, instruction_index))
+                .await
+                .unwrap();
+        }
+        for block_index in [777, 778] {
+            let records = [Record::Reward(reward(block_index))];
+            store.save_block(&records).await.unwrap();
+        }
+
+        // When we pull the votes past block 777:
+        let votes: Vec<_> = store
+            .iter_since(Kind::Vote, 777)
+            .map(|x| match x.unwrap() {
+                Record::Vote(vote) => (vote.block_index, vote.instruction_index),
+                other => panic!("not a vote: {other:?}"),
+            })
+            .collect();
+
+        // Then only the later ones should come, in their on-chain order:
+        assert_eq!(votes, vec![(778, 0), (778, 1), (779, 0)]);
+
+        // And the rewards should come the same way:
+        let rewards: Vec<_> = store
+            .iter_since(Kind::Reward, 777)
+            .map(|x| x.unwrap().block_index())
+            .collect();
+        assert_eq!(rewards, vec![778]);
+
+        // And nothing should come past the last block:
+        assert_eq!(store.iter_since(Kind::Vote, 779).count(), 0);
+    }
+
+    #[tokio::test]
+    async fn scans_agree_with_lookups() {
+        // Given a store keyed by the occurrence, with a transfer served under two blocks,
+        // and another one:
+        let store = Store::disposable()
+            .await
+            .unwrap()
+            .with_primary_key(PrimaryKey::Occurrence)
+            .unwrap();
+        let transfer = |signature, block_index| Transfer {
+            signature,
+            ..fixtures::transfer(block_index, 0)
+        };
+        let signature = Signature::new_unique();
+        let (first, second, other) = (
+            transfer(signature, 777),
+            transfer(signature, 778),
+            transfer(Signature::new_unique(), 779),
+        );
+        for each in [&first, &second, &other] {
+            store.save_transfer(each).await.unwrap();
+        }
+
+        // When we scan everything, and under the signature alone:
+        let everything: Vec<Transfer> = store.scan(TRANSFERS_NS, Scan::All).unwrap();
+        let prefix = postcard::to_stdvec(&signature).unwrap();
+        let under: Vec<Transfer> = store.scan(TRANSFERS_NS, Scan::Prefix(&prefix)).unwrap();
+
+        // Then the scans should yield what the lookups do:
+        assert_eq!(everything, store.find_all_transfers().await.unwrap());
+        assert_eq!(everything.len(), 3);
+        assert_eq!(under, store.find_transfers(&[signature]).await.unwrap());
+        assert_eq!(under, vec![first, second]);
+    }
+
+    #[tokio::test]
+    async fn occurrences_kept_apart() {
+        // Given a store keyed by the occurrence, and a transaction served under two blocks:
+        let store = Store::disposable()
+            .await
+            .unwrap()
+            .with_primary_key(PrimaryKey::Occurrence)
+            .unwrap();
+        let (signature, source, destination) = (
+            Signature::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let transfer = |block_index| Transfer {
+            signature,
+            source,
+            destination,
+            ..fixtures::transfer(block_index, 0)
+        };
+        let (first, second) = (transfer(777), transfer(778));
+
+        // When we save both:
+        store.save_transfer(&first).await.unwrap();
+        store.save_transfer(&second).await.unwrap();
+
+        // Then both should be found by the signature, and by any index:
+        let by_signature = store.find_transfers(&[first.signature]).await.unwrap();
+        assert_eq!(by_signature, vec![first.clone(), second.clone()]);
+        let by_account = store
+            .find_transfers_by_account(&first.source)
+            .await
+            .unwrap();
+        assert_eq!(by_account.len(), 2);
+        let chronological = store.find_all_transfers_chronological().await.unwrap();
+        assert_eq!(chronological, vec![first, second.clone()]);
+
+        // And clearing one block should leave the other:
+        store.clear_block(777).await.unwrap();
+        let by_signature = store.find_transfers(&[second.signature]).await.unwrap();
+        assert_eq!(by_signature, vec![second]);
+    }
+
+    #[tokio::test]
+    async fn instructions_keyed_by_signature_indexed_once() {
+        // Given a store keyed by the signature, and a transaction of three transfers,
+        // each between accounts of their own:
+        let store = Store::disposable().await.unwrap();
+        let signature = Signature::new_unique();
+        let transfers: Vec<_> = (0..3)
+            .map(|instruction_index| Transfer {
+                signature,
+                ..fixtures::transfer(777, instruction_index)
+            })
+            .collect();
+
+        // When its block gets saved, and then one of the transfers once again on its own:
+        let records: Vec<_> = transfers.iter().cloned().map(Record::Transfer).collect();
+        store.save_block(&records).await.unwrap();
+        let survivor = transfers[1].clone();
+        store.save_transfer(&survivor).await.unwrap();
+
+        // Then only the last one saved should be found, and only once, whichever way:
+        let chronological = store.find_all_transfers_chronological().await.unwrap();
+        assert_eq!(chronological, vec![survivor.clone()]);
+        let page = store
+            .find_transfers_page(None, 0..=u64::MAX, 0, 10, |_| true)
+            .await
+            .unwrap();
+        assert_eq!(page.records, vec![survivor.clone()]);
+        let changes: Vec<_> = store.iter_since(Kind::Transfer, 0).collect();
+        assert_eq!(changes.len(), 1);
+        for other in [&transfers[0], &transfers[2]] {
+            let by_account = store
+                .find_transfers_by_account(&other.source)
+                .await
+                .unwrap();
+            assert!(by_account.is_empty());
+        }
+        assert!(store.check_integrity().unwrap().is_consistent());
+
+        // And clearing the block should leave no entry behind:
+        store.clear_block(777).await.unwrap();
+        for ns in [TRANSFERS_INDEX_NS, TRANSFERS_CHRONO_NS] {
+            let cf = store.db.cf_handle(ns).unwrap();
+            let mut entries = store.db.iterator_cf(cf, rocksdb::IteratorMode::Start);
+            assert!(entries.next().is_none(), "`{ns}` left with entries");
+        }
+    }
+
+    #[tokio::test]
+    async fn rekeying_refused() {
+        // Given a store keyed by the signature, with something in it:
+        let path = Store::disposable_path();
+        let store = Store::with_path(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+        let vote = fixtures::vote(777, 0);
+        store.save_vote(&vote).await.unwrap();
+
+        // When we ask for it keyed by the occurrence:
+        let rekeyed = store.with_primary_key(PrimaryKey::Occurrence);
+
+        // Then it should be refused:
+        assert!(matches!(rekeyed, Err(Error::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn transfers_counted() {
+        // Given transfers across two blocks:
+        let transfer = |block_index, lamports| Transfer {
+            lamports,
+            ..fixtures::transfer(block_index, 0)
+        };
+        let store = Store::disposable().await.unwrap();
+        for each in [transfer(777, 100), transfer(777, 200), transfer(778, 100)] {
+            store.save_transfer(&each).await.unwrap();
+        }
+
+        // When we count them by the index, and by a scan:
+        let by_block = store.count_transfers_by_block_index(777).await.unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let by_scan = store
+            .count_transfers(deadline, |x| x.lamports == 100)
+            .await
+            .unwrap();
+
+        // Then both should tell how many there are:
+        assert_eq!(by_block, 2);
+        assert_eq!(by_scan, 2);
+
+        // And a scan past its deadline should give up:
+        let overdue = store.count_transfers(Instant::now(), |_| true).await;
+        assert!(matches!(overdue, Err(Error::TimedOut(_))));
+    }
+
+    #[tokio::test]
+    async fn counted_by_distinct_records() {
+        // Given a vote, and the index entries of its block
+        // both repeating its primary key and pointing at nothing:
+        let vote = fixtures::vote(777, 0);
+        let store = Store::disposable().await.unwrap();
+        store.save_vote(&vote).await.unwrap();
+        let key = store.primary_key(&vote.signature, vote.block_index, vote.instruction_index);
+        let block_index = vote.block_index.to_be_bytes();
+        let cf = store.db.cf_handle(VOTES_INDEX_NS).unwrap();
+        let repeated = index_key(IndexField::Block, &block_index, b"repeated");
+        store.db.put_cf(cf, repeated, &key).unwrap();
+        let dangling = index_key(IndexField::Block, &block_index, b"dangling");
+        store.db.put_cf(cf, dangling, b"dangling").unwrap();
+
+        // When we count the votes of the block:
+        let count = store.count_votes_by_block_index(777).await.unwrap();
+
+        // Then there should be just the one:
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn token_transfers_found_by_block_and_account() {
+        // Given token transfers of one mint across two blocks, by different owners:
+        let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let transfer = |block_index, instruction_index, owner| TokenTransfer {
+            signature: Signature::new_unique(),
+            block_index,
+            instruction_index,
+            timestamp: 1234567890,
+            mint,
+            owner,
+            source_ata: Pubkey::new_unique(),
+            destination_ata: Pubkey::new_unique(),
+            amount: 1_000_000,
+            indexed_at: None,
+        };
+        let (first, second, third) = (
+            transfer(778, 1, owner),
+            transfer(778, 0, Pubkey::new_unique()),
+            transfer(777, 3, owner),
+        );
+        let store = Store::disposable().await.unwrap();
+        let records = [&first, &second, &third].map(|x| Record::TokenTransfer(x.clone()));
+        store.save_block(&records).await.unwrap();
+
+        // When we look them up by the block:
+        let by_block = store
+            .find_token_transfers_by_block_index(778)
+            .await
+            .unwrap();
+
+        // Then only the ones of that block should be found, in their on-chain order:
+        assert_eq!(by_block, vec![second.clone(), first.clone()]);
+
+        // And by the owner, only theirs, while by the mint, all of them:
+        let by_owner = store.find_token_transfers_by_account(&owner).await.unwrap();
+        assert_eq!(by_owner, vec![third.clone(), first.clone()]);
+        let by_mint = store.find_token_transfers_by_account(&mint).await.unwrap();
+        assert_eq!(by_mint, vec![third, second, first]);
+
+        // And clearing a block should leave nothing of it behind:
+        assert_eq!(store.clear_block(778).await.unwrap(), (0, 0, 0, 2, 0));
+        let by_mint = store.find_token_transfers_by_account(&mint).await.unwrap();
+        assert_eq!(by_mint.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn stake_delegations_found_by_blocks_and_account() {
+        // Given delegations to one validator across three blocks:
+        let validator = Pubkey::new_unique();
+        let delegation = |block_index| StakeDelegation {
+            signature: Signature::new_unique(),
+            block_index,
+            instruction_index: 0,
+            timestamp: 1234567890,
+            stake_account: Pubkey::new_unique(),
+            vote_account: validator,
+            authority: Pubkey::new_unique(),
+            indexed_at: None,
+        };
+        let (first, second, third) = (delegation(777), delegation(778), delegation(780));
+        let store = Store::disposable().await.unwrap();
+        let records = [&third, &first, &second].map(|x| Record::StakeDelegation(x.clone()));
+        store.save_block(&records).await.unwrap();
+
+        // When we look them up by a range of blocks:
+        let by_blocks = (store.find_stake_delegations_by_block_range(778, 780).await).unwrap();
+
+        // Then only the ones within should be found, in their on-chain order:
+        assert_eq!(by_blocks, vec![second.clone(), third.clone()]);
+
+        // And by the validator, all of them, while by the stake account, only its own:
+        let by_validator = (store.find_stake_delegations_by_account(&validator).await).unwrap();
+        assert_eq!(by_validator, vec![first.clone(), second, third]);
+        let stake_account = first.stake_account;
+        let by_stake = (store
+            .find_stake_delegations_by_account(&stake_account)
+            .await)
+            .unwrap();
+        assert_eq!(by_stake, vec![first]);
+    }
+
+    /// Not a check but a measurement of how long a page and a whole scan take
+    /// while the committer keeps writing, with the reads pinning a snapshot, and tailing:
+    /// `cargo test --release -- --ignored --nocapture scans_timed_under_writes`.
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn scans_timed_under_writes() {
+        const RECORDS: u64 = 20_000;
+        const ROUNDS: usize = 50;
+
+        for (name, tailing) in [("snapshot", false), ("tailing", true)] {
+            // Given a store with plenty of transfers, being written to all along:
+            let tuning = Tuning {
+                tailing,
+                ..Tuning::default()
+            };
+            let store = Store::disposable().await.unwrap().with_tuning(tuning);
+            let store = Arc::new(store);
+            for block_index in 0..RECORDS {
+                let transfer = fixtures::transfer(block_index, 0);
+                store.save_transfer(&transfer).await.unwrap();
+            }
+            let stop = CancellationToken::new();
+            let writer = tokio::spawn({
+                let (store, stop) = (store.clone(), stop.clone());
+                async move {
+                    let mut block_index = RECORDS;
+                    while !stop.is_cancelled() {
+                        let transfer = fixtures::transfer(block_index, 0);
+                        store.save_transfer(&transfer).await.unwrap();
+                        block_index += 1;
+                    }
+                }
+            });
+
+            // When we time the first page, and the whole of them, over and over:
+            let (mut pages, mut wholes) = (Vec::new(), Vec::new());
+            for _ in 0..ROUNDS {
+                let started = Instant::now();
+                let page = store
+                    .find_transfers_page(None, 0..=u64::MAX, 0, 1000, |_| true)
+                    .await
+                    .unwrap();
+                pages.push(started.elapsed());
+                assert_eq!(page.records.len(), 1000);
+
+                let started = Instant::now();
+                let all = store.find_all_transfers_chronological().await.unwrap();
+                wholes.push(started.elapsed());
+                assert!(all.len() as u64 >= RECORDS);
+            }
+            stop.cancel();
+            writer.await.unwrap();
+
+            // Then tell how long they took:
+            for (what, times) in [("page", &mut pages), ("whole", &mut wholes)] {
+                times.sort();
+                let (median, worst) = (times[ROUNDS / 2], times[ROUNDS - 1]);
+                println!("{name} {what}: median {median:?}, worst {worst:?}");
+            }
+        }
+    }
+
+    /// How long looking up the votes by the pair takes, against going through all of them;
+    /// `cargo test --release -- --ignored --nocapture account_pairs_timed`.
+    #[tokio::test]
+    #[ignore]
+    async fn account_pairs_timed() {
+        const RECORDS: u64 = 100_000;
+        const ROUNDS: usize = 20;
+
+        // Given plenty of votes, a few validators voting for one another in turns:
+        let validators: Vec<_> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        let store = Store::disposable().await.unwrap();
+        for block_index in 0..RECORDS {
+            let vote = Vote {
+                author: validators[block_index as usize % validators.len()],
+                target: validators[block_index as usize % 7],
+                ..fixtures::vote(block_index, 0)
+            };
+            store.save_vote(&vote).await.unwrap();
+        }
+        let (author, target) = (validators[3], validators[5]);
+
+        // When we look them up over and over, either way:
+        let (mut pairs, mut scans) = (Vec::new(), Vec::new());
+        for _ in 0..ROUNDS {
+            let started = Instant::now();
+            let by_pair = store
+                .find_votes_by_account_pair(&author, &target)
+                .await
+                .unwrap();
+            pairs.push(started.elapsed());
+
+            let started = Instant::now();
+            let by_scan: Vec<_> = store
+                .find_all_votes()
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|x| x.author == author && x.target == target)
+                .collect();
+            scans.push(started.elapsed());
+            assert!(by_pair.len() >= by_scan.len());
+        }
+
+        // Then tell how long they took:
+        for (what, times) in [("pair", &mut pairs), ("scan", &mut scans)] {
+            times.sort();
+            let (median, worst) = (times[ROUNDS / 2], times[ROUNDS - 1]);
+            println!("{what}: median {median:?}, worst {worst:?}");
+        }
+    }
 }
@@ -0,0 +1,739 @@
+//! Bringing the databases written by the older versions up to date.
+//!
+//! Each version gets a step that upgrades a database from the previous one.
+//! The new column families get created on open, so the steps only deal with the data:
+//! re-encoding the records whose layout has changed, and filling the new indexes.
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use super::{
+    Store, REWARDS_NS, STAKE_DELEGATIONS_NS, TOKEN_TRANSFERS_NS, TRANSFERS_CHRONO_NS,
+    TRANSFERS_INDEX_NS, TRANSFERS_NS, VOTES_CHRONO_NS, VOTES_INDEX_NS, VOTES_NS,
+};
+use crate::record::{Transfer, Vote};
+use crate::result::Error;
+use crate::Result;
+
+/// The layout this build reads and writes.
+pub const CURRENT_VERSION: u32 = 9;
+
+const SCHEMA_VERSION_KEY: &[u8] = b"\x1b\x5c";
+
+/// A vote as stored by version 1.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct VoteV1 {
+    signature: Signature,
+    block_index: u64,
+    timestamp: u64,
+    author: Pubkey,
+    target: Pubkey,
+}
+
+/// A transfer as stored by version 1.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct TransferV1 {
+    signature: Signature,
+    block_index: u64,
+    timestamp: u64,
+    source: Pubkey,
+    destination: Pubkey,
+    lamports: u64,
+}
+
+/// A vote as stored by versions 2 and 3.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct VoteV3 {
+    signature: Signature,
+    block_index: u64,
+    instruction_index: u32,
+    timestamp: u64,
+    author: Pubkey,
+    target: Pubkey,
+    compute_unit_price: Option<u64>,
+    compute_unit_limit: Option<u32>,
+}
+
+/// A transfer as stored by versions 2 and 3.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct TransferV3 {
+    signature: Signature,
+    block_index: u64,
+    instruction_index: u32,
+    timestamp: u64,
+    source: Pubkey,
+    destination: Pubkey,
+    lamports: u64,
+    compute_unit_price: Option<u64>,
+    compute_unit_limit: Option<u32>,
+}
+
+/// A vote as stored by version 4.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct VoteV4 {
+    signature: Signature,
+    block_index: u64,
+    instruction_index: u32,
+    timestamp: u64,
+    author: Pubkey,
+    target: Pubkey,
+    compute_unit_price: Option<u64>,
+    compute_unit_limit: Option<u32>,
+    program_id: Pubkey,
+}
+
+/// A transfer as stored by version 4.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct TransferV4 {
+    signature: Signature,
+    block_index: u64,
+    instruction_index: u32,
+    timestamp: u64,
+    source: Pubkey,
+    destination: Pubkey,
+    lamports: u64,
+    compute_unit_price: Option<u64>,
+    compute_unit_limit: Option<u32>,
+    program_id: Pubkey,
+}
+
+/// A transfer as stored by version 5.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct TransferV5 {
+    signature: Signature,
+    block_index: u64,
+    instruction_index: u32,
+    timestamp: u64,
+    source: Pubkey,
+    destination: Pubkey,
+    lamports: u64,
+    compute_unit_price: Option<u64>,
+    compute_unit_limit: Option<u32>,
+    program_id: Pubkey,
+    indexed_at: Option<u64>,
+}
+
+impl From<VoteV3> for VoteV4 {
+    fn from(old: VoteV3) -> Self {
+        Self {
+            signature: old.signature,
+            block_index: old.block_index,
+            instruction_index: old.instruction_index,
+            timestamp: old.timestamp,
+            author: old.author,
+            target: old.target,
+            compute_unit_price: old.compute_unit_price,
+            compute_unit_limit: old.compute_unit_limit,
+            // The only program the votes have come from so far.
+            program_id: solana_sdk::vote::program::ID,
+        }
+    }
+}
+
+impl From<TransferV3> for TransferV4 {
+    fn from(old: TransferV3) -> Self {
+        Self {
+            signature: old.signature,
+            block_index: old.block_index,
+            instruction_index: old.instruction_index,
+            timestamp: old.timestamp,
+            source: old.source,
+            destination: old.destination,
+            lamports: old.lamports,
+            compute_unit_price: old.compute_unit_price,
+            compute_unit_limit: old.compute_unit_limit,
+            // The only program the transfers have come from so far.
+            program_id: solana_sdk::system_program::ID,
+        }
+    }
+}
+
+impl From<VoteV4> for Vote {
+    fn from(old: VoteV4) -> Self {
+        Self {
+            signature: old.signature,
+            block_index: old.block_index,
+            instruction_index: old.instruction_index,
+            timestamp: old.timestamp,
+            author: old.author,
+            target: old.target,
+            compute_unit_price: old.compute_unit_price,
+            compute_unit_limit: old.compute_unit_limit,
+            program_id: old.program_id,
+            indexed_at: None,
+        }
+    }
+}
+
+impl From<TransferV4> for TransferV5 {
+    fn from(old: TransferV4) -> Self {
+        Self {
+            signature: old.signature,
+            block_index: old.block_index,
+            instruction_index: old.instruction_index,
+            timestamp: old.timestamp,
+            source: old.source,
+            destination: old.destination,
+            lamports: old.lamports,
+            compute_unit_price: old.compute_unit_price,
+            compute_unit_limit: old.compute_unit_limit,
+            program_id: old.program_id,
+            indexed_at: None,
+        }
+    }
+}
+
+impl From<TransferV5> for Transfer {
+    fn from(old: TransferV5) -> Self {
+        Self {
+            signature: old.signature,
+            block_index: old.block_index,
+            instruction_index: old.instruction_index,
+            timestamp: old.timestamp,
+            source: old.source,
+            destination: old.destination,
+            lamports: old.lamports,
+            compute_unit_price: old.compute_unit_price,
+            compute_unit_limit: old.compute_unit_limit,
+            program_id: old.program_id,
+            indexed_at: old.indexed_at,
+            instruction_kind: None,
+        }
+    }
+}
+
+impl From<VoteV1> for Vote {
+    fn from(old: VoteV1) -> Self {
+        Self {
+            signature: old.signature,
+            block_index: old.block_index,
+            instruction_index: 0,
+            timestamp: old.timestamp,
+            author: old.author,
+            target: old.target,
+            compute_unit_price: None,
+            compute_unit_limit: None,
+            program_id: solana_sdk::vote::program::ID,
+            indexed_at: None,
+        }
+    }
+}
+
+impl From<TransferV1> for Transfer {
+    fn from(old: TransferV1) -> Self {
+        Self {
+            signature: old.signature,
+            block_index: old.block_index,
+            instruction_index: 0,
+            timestamp: old.timestamp,
+            source: old.source,
+            destination: old.destination,
+            lamports: old.lamports,
+            compute_unit_price: None,
+            compute_unit_limit: None,
+            program_id: solana_sdk::system_program::ID,
+            indexed_at: None,
+            instruction_kind: None,
+        }
+    }
+}
+
+/// Decode a record stored by version 1, or already re-saved by an earlier run of the same step
+/// cut off partway. The current layout goes first: a record in it would pass for an old one,
+/// postcard leaving whatever follows be, while an old one is too short to pass for it.
+fn decode_v1_or_current<Current, V1>(bytes: &[u8]) -> Result<Current>
+where
+    Current: serde::de::DeserializeOwned + From<V1>,
+    V1: serde::de::DeserializeOwned,
+{
+    if let Ok(current) = postcard::from_bytes::<Current>(bytes) {
+        return Ok(current);
+    }
+    Ok(postcard::from_bytes::<V1>(bytes)?.into())
+}
+
+/// Decode a record stored in the current layout, or in that of version 4 or 3.
+/// The steps re-save the records in the current layout as they go,
+/// so a later step may find any of them; the new fields go last,
+/// so that an older layout never passes for a newer one, as long as the newer gets tried first.
+fn decode_any<Current, V4, V3>(bytes: &[u8]) -> Result<Current>
+where
+    Current: serde::de::DeserializeOwned + From<V4>,
+    V4: serde::de::DeserializeOwned + From<V3>,
+    V3: serde::de::DeserializeOwned,
+{
+    if let Ok(current) = postcard::from_bytes::<Current>(bytes) {
+        return Ok(current);
+    }
+    if let Ok(old) = postcard::from_bytes::<V4>(bytes) {
+        return Ok(old.into());
+    }
+    let old = postcard::from_bytes::<V3>(bytes)?;
+    Ok(V4::from(old).into())
+}
+
+/// Decode a transfer stored in the current layout, or in that of any version since 3,
+/// the transfers having changed once more than the votes.
+fn decode_any_transfer(bytes: &[u8]) -> Result<Transfer> {
+    if let Ok(current) = postcard::from_bytes::<Transfer>(bytes) {
+        return Ok(current);
+    }
+    decode_any::<TransferV5, TransferV4, TransferV3>(bytes).map(Transfer::from)
+}
+
+impl Store {
+    /// The version of the layout the database was written with, if it says so.
+    fn schema_version(&self) -> Result<Option<u32>> {
+        let Some(gotten) = self.db.get_pinned(SCHEMA_VERSION_KEY)? else {
+            return Ok(None);
+        };
+        Ok(Some(postcard::from_bytes(&gotten)?))
+    }
+
+    fn set_schema_version(&self, version: u32) -> Result<()> {
+        let bytes = postcard::to_stdvec(&version).unwrap();
+        self.db
+            .put_opt(SCHEMA_VERSION_KEY, bytes, &self.write_opts())?;
+        Ok(())
+    }
+
+    /// Whether nothing has been written to the database yet.
+    pub(super) fn is_pristine(&self) -> bool {
+        [VOTES_NS, TRANSFERS_NS].iter().all(|ns| {
+            let cf = self.db.cf_handle(ns).unwrap();
+            let mut rows = self.db.iterator_cf(cf, rocksdb::IteratorMode::Start);
+            rows.next().is_none()
+        })
+    }
+
+    /// The version of the layout the database is in, counting a new one as current.
+    fn effective_schema_version(&self) -> Result<u32> {
+        let version = match self.schema_version()? {
+            Some(version) => version,
+            None if self.is_pristine() => CURRENT_VERSION,
+            // Written before the versions were kept.
+            None => 1,
+        };
+        if version > CURRENT_VERSION {
+            return Err(Error::SchemaTooNew {
+                found: version,
+                supported: CURRENT_VERSION,
+            });
+        }
+        Ok(version)
+    }
+
+    /// Make sure the database is in the current layout, writing the version down if it is new,
+    /// or refuse to touch it otherwise, as reading an older layout would yield garbage.
+    pub(super) fn check_schema(&self) -> Result<()> {
+        let version = self.effective_schema_version()?;
+        if version < CURRENT_VERSION {
+            return Err(Error::SchemaMismatch {
+                found: version,
+                expected: CURRENT_VERSION,
+            });
+        }
+        self.set_schema_version(version)
+    }
+
+    /// Upgrade the database to the current layout step by step,
+    /// or refuse to touch it if it comes from a newer build.
+    pub(super) async fn migrate(&self) -> Result<()> {
+        let mut version = self.effective_schema_version()?;
+
+        while version < CURRENT_VERSION {
+            tracing::info!(
+                "Migrating the database from version {version} to {}...",
+                version + 1
+            );
+            match version {
+                1 => self.add_positions_and_compute_budget().await?,
+                2 => self.reindex_with_ordered_keys().await?,
+                3 => self.add_program_ids().await?,
+                4 => self.add_indexing_times().await?,
+                5 => self.add_instruction_kinds().await?,
+                6 => self.index_timestamps().await?,
+                7 => self.drop_displaced_entries().await?,
+                8 => self.seed_first_known_block()?,
+                _ => unreachable!("no migration from version {version}"),
+            }
+            version += 1;
+            self.set_schema_version(version)?;
+        }
+        self.flush().await?;
+
+        self.set_schema_version(version)
+    }
+
+    /// Version 2 keeps where the instructions stand within their blocks,
+    /// indexes the records chronologically, and keeps the compute budget.
+    /// None of those can be told from the old records, so those get the defaults.
+    async fn add_positions_and_compute_budget(&self) -> Result<()> {
+        let cf = self.db.cf_handle(VOTES_NS).unwrap();
+        for each in self.db.iterator_cf(cf, rocksdb::IteratorMode::Start) {
+            let (_k, v) = each?;
+            let vote = decode_v1_or_current::<Vote, VoteV1>(&v)?;
+            self.save_vote(&vote).await?;
+        }
+
+        let cf = self.db.cf_handle(TRANSFERS_NS).unwrap();
+        for each in self.db.iterator_cf(cf, rocksdb::IteratorMode::Start) {
+            let (_k, v) = each?;
+            let transfer = decode_v1_or_current::<Transfer, TransferV1>(&v)?;
+            self.save_transfer(&transfer).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Version 3 keys the secondary indexes by the field, with the numbers big-endian,
+    /// so the old entries go, and the records get indexed anew.
+    async fn reindex_with_ordered_keys(&self) -> Result<()> {
+        self.clear(&[VOTES_INDEX_NS, TRANSFERS_INDEX_NS])?;
+
+        let cf = self.db.cf_handle(VOTES_NS).unwrap();
+        for each in self.db.iterator_cf(cf, rocksdb::IteratorMode::Start) {
+            let (_k, v) = each?;
+            let vote = decode_any::<Vote, VoteV4, VoteV3>(&v)?;
+            self.save_vote(&vote).await?;
+        }
+
+        let cf = self.db.cf_handle(TRANSFERS_NS).unwrap();
+        for each in self.db.iterator_cf(cf, rocksdb::IteratorMode::Start) {
+            let (_k, v) = each?;
+            let transfer = decode_any_transfer(&v)?;
+            self.save_transfer(&transfer).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Version 4 keeps the program each record comes from, and indexes it.
+    async fn add_program_ids(&self) -> Result<()> {
+        self.resave_all().await
+    }
+
+    /// Version 5 keeps when each record was written down, unknown for the ones so far.
+    async fn add_indexing_times(&self) -> Result<()> {
+        self.resave_all().await
+    }
+
+    /// Version 6 keeps what instruction each transfer comes from, unknown for the ones so far.
+    async fn add_instruction_kinds(&self) -> Result<()> {
+        self.resave_all().await
+    }
+
+    /// Version 7 indexes the records by the time of their blocks.
+    async fn index_timestamps(&self) -> Result<()> {
+        self.resave_all().await
+    }
+
+    /// Version 8 leaves a single chronological entry to each record keyed by the signature alone,
+    /// rather than one to every instruction of the transaction that has taken its place before,
+    /// and likewise for the rest of the index entries. Those cannot be told apart from the others
+    /// without the records, so all of them go, and the records get indexed anew.
+    async fn drop_displaced_entries(&self) -> Result<()> {
+        self.clear(&[
+            VOTES_INDEX_NS,
+            TRANSFERS_INDEX_NS,
+            VOTES_CHRONO_NS,
+            TRANSFERS_CHRONO_NS,
+        ])?;
+        self.resave_all().await
+    }
+
+    /// Version 9 keeps the earliest block of any record, written down as the records come,
+    /// so the ones written before get it from the first of the entries keyed by the block.
+    fn seed_first_known_block(&self) -> Result<()> {
+        let keyed_by_block = [
+            VOTES_CHRONO_NS,
+            TRANSFERS_CHRONO_NS,
+            REWARDS_NS,
+            TOKEN_TRANSFERS_NS,
+            STAKE_DELEGATIONS_NS,
+        ];
+        for ns in keyed_by_block {
+            let cf = self.db.cf_handle(ns).unwrap();
+            let mut rows = self.db.iterator_cf(cf, rocksdb::IteratorMode::Start);
+            let Some(row) = rows.next() else {
+                continue;
+            };
+            let (k, _v) = row?;
+            if let Some(block) = k.get(..8).and_then(|b| <[u8; 8]>::try_from(b).ok()) {
+                self.lower_first_known_block(u64::from_be_bytes(block))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove everything from the given column families.
+    fn clear(&self, names: &[&str]) -> Result<()> {
+        for ns in names {
+            let cf = self.db.cf_handle(ns).unwrap();
+            let mut batch = rocksdb::WriteBatch::default();
+            for each in self.db.iterator_cf(cf, rocksdb::IteratorMode::Start) {
+                let (k, _v) = each?;
+                batch.delete_cf(cf, k);
+            }
+            self.db.write_opt(batch, &self.write_opts())?;
+        }
+        Ok(())
+    }
+
+    /// Decode every record in whatever layout it is, and write it down anew in the current one.
+    async fn resave_all(&self) -> Result<()> {
+        let cf = self.db.cf_handle(VOTES_NS).unwrap();
+        for each in self.db.iterator_cf(cf, rocksdb::IteratorMode::Start) {
+            let (_k, v) = each?;
+            let vote = decode_any::<Vote, VoteV4, VoteV3>(&v)?;
+            self.save_vote(&vote).await?;
+        }
+
+        let cf = self.db.cf_handle(TRANSFERS_NS).unwrap();
+        for each in self.db.iterator_cf(cf, rocksdb::IteratorMode::Start) {
+            let (_k, v) = each?;
+            let transfer = decode_any_transfer(&v)?;
+            self.save_transfer(&transfer).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::StoreConfig;
+
+    #[tokio::test]
+    async fn version_one_gets_migrated() {
+        // Given a database written before the versions were kept:
+        let path = Store::disposable_path();
+        let old = VoteV1 {
+            signature: Signature::new_unique(),
+            block_index: 777,
+            timestamp: 1234567890,
+            author: Pubkey::new_unique(),
+            target: Pubkey::new_unique(),
+        };
+        {
+            let store = Store::with_path(&path, &StoreConfig::default())
+                .await
+                .unwrap();
+            let cf = store.db.cf_handle(VOTES_NS).unwrap();
+            let key = postcard::to_stdvec(&old.signature).unwrap();
+            let value = postcard::to_stdvec(&old).unwrap();
+            store.db.put_cf(cf, key, value).unwrap();
+            store.db.delete(SCHEMA_VERSION_KEY).unwrap();
+        }
+
+        // When we open it again, migrating:
+        let store = Store::with_path_migrated(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+
+        // Then the record should be readable in the current layout:
+        let gotten = store.find_all_votes_chronological().await.unwrap();
+        assert_eq!(gotten.len(), 1);
+        assert_eq!(gotten[0].signature, old.signature);
+        assert_eq!(gotten[0].author, old.author);
+
+        // And it should be indexed by the block, by the time, and by the program:
+        let gotten = store.find_votes_by_block_index(777).await.unwrap();
+        assert_eq!(gotten.len(), 1);
+        let time = old.timestamp;
+        let gotten = store
+            .find_votes_by_timestamp_range(time, time)
+            .await
+            .unwrap();
+        assert_eq!(gotten.len(), 1);
+        let program_id = solana_sdk::vote::program::ID;
+        let gotten = store.find_votes_by_program(&program_id).await.unwrap();
+        assert_eq!(gotten.len(), 1);
+
+        // And the database should say it is current:
+        assert_eq!(store.schema_version().unwrap(), Some(CURRENT_VERSION));
+    }
+
+    #[tokio::test]
+    async fn version_one_migrated_again_once_cut_off() {
+        // Given a database written before the versions were kept,
+        // with one vote already re-saved by a migration cut off partway:
+        let path = Store::disposable_path();
+        let old = VoteV1 {
+            signature: Signature::new_unique(),
+            block_index: 777,
+            timestamp: 1234567890,
+            author: Pubkey::new_unique(),
+            target: Pubkey::new_unique(),
+        };
+        let resaved = crate::record::fixtures::vote(778, 3);
+        {
+            let store = Store::with_path(&path, &StoreConfig::default())
+                .await
+                .unwrap();
+            let cf = store.db.cf_handle(VOTES_NS).unwrap();
+            let key = postcard::to_stdvec(&old.signature).unwrap();
+            let value = postcard::to_stdvec(&old).unwrap();
+            store.db.put_cf(cf, key, value).unwrap();
+            store.save_vote(&resaved).await.unwrap();
+            store.db.delete(SCHEMA_VERSION_KEY).unwrap();
+        }
+
+        // When we migrate it anew:
+        let store = Store::with_path_migrated(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+
+        // Then both should be read right, the re-saved one not taken for an old one:
+        let gotten = store.find_all_votes_chronological().await.unwrap();
+        assert_eq!(gotten, vec![Vote::from(old), resaved]);
+    }
+
+    #[tokio::test]
+    async fn version_five_transfers_keep_indexing_times() {
+        // Given a transfer written down by version 5, along with when it was:
+        let path = Store::disposable_path();
+        let old = TransferV5 {
+            signature: Signature::new_unique(),
+            block_index: 777,
+            instruction_index: 0,
+            timestamp: 1234567890,
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            lamports: 5000,
+            compute_unit_price: None,
+            compute_unit_limit: None,
+            program_id: solana_sdk::system_program::ID,
+            indexed_at: Some(1234567999),
+        };
+        {
+            let store = Store::with_path(&path, &StoreConfig::default())
+                .await
+                .unwrap();
+            let cf = store.db.cf_handle(TRANSFERS_NS).unwrap();
+            let key = postcard::to_stdvec(&old.signature).unwrap();
+            let value = postcard::to_stdvec(&old).unwrap();
+            store.db.put_cf(cf, key, value).unwrap();
+            store.set_schema_version(5).unwrap();
+        }
+
+        // When we open it again, migrating:
+        let store = Store::with_path_migrated(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+
+        // Then it should be readable in the current layout, still knowing when it was written down:
+        let gotten = store.find_all_transfers().await.unwrap();
+        assert_eq!(gotten, vec![Transfer::from(old)]);
+        assert_eq!(gotten[0].indexed_at, Some(1234567999));
+        assert_eq!(gotten[0].instruction_kind, None);
+    }
+
+    #[tokio::test]
+    async fn version_seven_displaced_entries_dropped() {
+        // Given a vote written down by version 7 over another instruction of its transaction,
+        // whose chronological entry was left pointing to it:
+        let path = Store::disposable_path();
+        let vote = crate::record::fixtures::vote(777, 1);
+        {
+            let store = Store::with_path(&path, &StoreConfig::default())
+                .await
+                .unwrap();
+            store.save_vote(&vote).await.unwrap();
+            let cf = store.db.cf_handle(VOTES_CHRONO_NS).unwrap();
+            let key = store.primary_key(&vote.signature, 777, 0);
+            let displaced = crate::store::chrono_key(777, 0, &vote.signature);
+            store.db.put_cf(cf, displaced, key).unwrap();
+            store.set_schema_version(7).unwrap();
+        }
+
+        // When we open it again, migrating:
+        let store = Store::with_path_migrated(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+
+        // Then the vote should be found once, at its own position:
+        let gotten = store.find_all_votes_chronological().await.unwrap();
+        assert_eq!(gotten, vec![vote]);
+        assert!(store.check_integrity().unwrap().is_consistent());
+    }
+
+    #[tokio::test]
+    async fn version_eight_first_block_seeded() {
+        // Given records written down by version 8, which kept no first block:
+        let path = Store::disposable_path();
+        {
+            let store = Store::with_path(&path, &StoreConfig::default())
+                .await
+                .unwrap();
+            let vote = crate::record::fixtures::vote(800, 0);
+            store.save_vote(&vote).await.unwrap();
+            let transfer = crate::record::fixtures::transfer(777, 0);
+            store.save_transfer(&transfer).await.unwrap();
+            store
+                .db
+                .delete(crate::store::FIRST_KNOWN_BLOCK_KEY)
+                .unwrap();
+            store.set_schema_version(8).unwrap();
+        }
+
+        // When we open it again, migrating:
+        let store = Store::with_path_migrated(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+
+        // Then the earliest of the blocks should be known, and stay so once opened anew:
+        assert_eq!(store.first_known_block().await, Some(777));
+        drop(store);
+        let store = Store::with_path(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(store.first_known_block().await, Some(777));
+    }
+
+    #[tokio::test]
+    async fn older_version_refused_until_migrated() {
+        // Given a database written by the previous build:
+        let path = Store::disposable_path();
+        {
+            let store = Store::with_path(&path, &StoreConfig::default())
+                .await
+                .unwrap();
+            store.set_schema_version(CURRENT_VERSION - 1).unwrap();
+        }
+
+        // When we open it again as is:
+        let store = Store::with_path(&path, &StoreConfig::default()).await;
+
+        // Then it should be refused:
+        assert!(matches!(store, Err(Error::SchemaMismatch { .. })));
+
+        // And when we migrate it, it should be current, and open as is from then on:
+        let store = Store::with_path_migrated(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(store.schema_version().unwrap(), Some(CURRENT_VERSION));
+        drop(store);
+        Store::with_path(&path, &StoreConfig::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn newer_version_refused() {
+        // Given a database written by a newer build:
+        let path = Store::disposable_path();
+        {
+            let store = Store::with_path(&path, &StoreConfig::default())
+                .await
+                .unwrap();
+            store.set_schema_version(CURRENT_VERSION + 1).unwrap();
+        }
+
+        // When we open it again:
+        let store = Store::with_path(&path, &StoreConfig::default()).await;
+
+        // Then it should be refused:
+        assert!(matches!(store, Err(Error::SchemaTooNew { .. })));
+    }
+}
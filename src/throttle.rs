@@ -0,0 +1,28 @@
+//! Keeping the repetitive warnings from flooding the logs.
+
+use std::time::{Duration, Instant};
+
+/// Lets something happen at most once per period.
+#[derive(Debug)]
+pub struct Throttle {
+    period: Duration,
+    last: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn new(period: Duration) -> Self {
+        Self { period, last: None }
+    }
+
+    /// Whether it may happen now; if so, the period starts anew.
+    pub fn ready(&mut self) -> bool {
+        let ready = match self.last {
+            None => true,
+            Some(last) => last.elapsed() >= self.period,
+        };
+        if ready {
+            self.last = Some(Instant::now());
+        }
+        ready
+    }
+}
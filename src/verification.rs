@@ -0,0 +1,127 @@
+//! Checking the stored records against the chain.
+
+use rand::seq::IteratorRandom;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::extraction::{parse_transaction, Connection};
+use crate::record::{Kind, Record, Transfer, Vote};
+use crate::store::Store;
+use crate::Result;
+
+/// What came out of the checking.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    /// How many records were checked.
+    pub checked: usize,
+    /// How many of those do not match what is on chain.
+    pub mismatched: usize,
+    /// How many could not be checked, their transactions failing to come.
+    pub failed: usize,
+}
+
+/// Whether the two records describe the same thing, not minding their positions,
+/// as those cannot be told from a lone transaction, nor when they were written down.
+fn same(stored: &Record, fresh: &Record) -> bool {
+    match (stored, fresh) {
+        (Record::Vote(stored), Record::Vote(fresh)) => {
+            *stored
+                == Vote {
+                    instruction_index: stored.instruction_index,
+                    indexed_at: stored.indexed_at,
+                    ..fresh.clone()
+                }
+        }
+        (Record::Transfer(stored), Record::Transfer(fresh)) => {
+            *stored
+                == Transfer {
+                    instruction_index: stored.instruction_index,
+                    indexed_at: stored.indexed_at,
+                    // Unknown for the records from before it was kept:
+                    instruction_kind: (stored.instruction_kind.as_ref())
+                        .and(fresh.instruction_kind.clone()),
+                    ..fresh.clone()
+                }
+        }
+        _ => false,
+    }
+}
+
+/// Pick up to `sample` stored records at random, fetch their transactions anew,
+/// and see whether parsing those again yields the same records.
+pub async fn verify_sample(
+    store: &Store,
+    connection: &Connection,
+    sample: usize,
+) -> Result<Report> {
+    let client = connection.client()?;
+
+    // Going over the records one by one, so that only the sample is ever held.
+    let mut unreadable = None;
+    let picked = store
+        .iter_all(Kind::Vote)
+        .chain(store.iter_all(Kind::Transfer))
+        .filter_map(|record| match record {
+            Ok(record) => Some(record),
+            Err(e) => {
+                unreadable.get_or_insert(e);
+                None
+            }
+        })
+        .choose_multiple(&mut rand::thread_rng(), sample);
+    if let Some(e) = unreadable {
+        return Err(e);
+    }
+    tracing::info!("Verifying {} records...", picked.len());
+
+    let mut report = Report::default();
+    for stored in picked {
+        // Only the records coming from transactions can be fetched anew.
+        let Some(signature) = stored.signature() else {
+            continue;
+        };
+        let records = match refetch(&client, signature, stored.kind()) {
+            Ok(records) => records,
+            Err(e) => {
+                tracing::warn!("Failed to fetch {signature} anew: {e}");
+                report.failed += 1;
+                continue;
+            }
+        };
+
+        report.checked += 1;
+        if !records.iter().any(|fresh| same(&stored, fresh)) {
+            report.mismatched += 1;
+            tracing::warn!("Stored {stored:?} matches none of the freshly parsed {records:?}");
+        }
+    }
+    Ok(report)
+}
+
+/// The records of the given kind of the transaction as parsed from it anew.
+fn refetch(client: &RpcClient, signature: &Signature, kind: Kind) -> Result<Vec<Record>> {
+    let fresh = client.get_transaction_with_config(
+        signature,
+        RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::JsonParsed),
+            commitment: Some(CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
+        },
+    )?;
+    let block_time = match fresh.block_time {
+        Some(block_time) => block_time as u64,
+        None => client.get_block_time(fresh.slot)? as u64,
+    };
+    // Not knowing where the transaction stands within its block, so starting anywhere.
+    let mut position = 0;
+    parse_transaction(
+        &[kind],
+        &fresh.transaction,
+        &fresh.slot,
+        &block_time,
+        &mut position,
+    )
+}